@@ -0,0 +1,98 @@
+//! Timing benchmarks for the hot paths, run with `cargo bench`.
+//!
+//! Each benchmark runs its closure repeatedly for about a second and
+//! reports the median time per run over several samples.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use mandelbrot::{cmplx, escape_time, render, render_parallel, render_tiled, Params, Region};
+
+const SAMPLES: usize = 10;
+const SAMPLE_TIME: Duration = Duration::from_millis(100);
+
+/// Time `f` and print the median duration of one call.
+fn bench<F: FnMut()>(name: &str, mut f: F) {
+    // Find how many calls fill one sample.
+    let mut calls = 1u32;
+    loop {
+        let start = Instant::now();
+        for _ in 0..calls {
+            f();
+        }
+        if start.elapsed() >= SAMPLE_TIME / 10 {
+            break;
+        }
+        calls *= 2;
+    }
+    let calls = calls * 10;
+
+    let mut times: Vec<Duration> = (0..SAMPLES)
+        .map(|_| {
+            let start = Instant::now();
+            for _ in 0..calls {
+                f();
+            }
+            start.elapsed() / calls
+        })
+        .collect();
+    times.sort();
+
+    println!("{:<24} {:>12?}/iter", name, times[SAMPLES / 2]);
+}
+
+fn main() {
+    // A mix of points inside the set, on its boundary and far outside.
+    let points = [
+        cmplx!(),
+        cmplx!(-1.0, 0.0),
+        cmplx!(-0.75, 0.1),
+        cmplx!(0.25, 0.5),
+        cmplx!(-1.25, 0.05),
+        cmplx!(2.0, 2.0),
+    ];
+    bench("escape_time/mixed", || {
+        for &c in &points {
+            black_box(escape_time(cmplx!(), black_box(c), 255));
+        }
+    });
+    bench("escape_time/interior", || {
+        black_box(escape_time(cmplx!(), black_box(cmplx!(-0.1, 0.1)), 255));
+    });
+    bench("escape_time/exterior", || {
+        black_box(escape_time(cmplx!(), black_box(cmplx!(1.0, 1.0)), 255));
+    });
+
+    let region = Region::new((160, 120), cmplx!(-2.0, 1.2), cmplx!(1.0, -1.2));
+    let params = Params::default();
+    let mut pixels = vec![0u8; 160 * 120];
+    bench("render/160x120", || {
+        render(&mut pixels, &region, &params);
+        black_box(&pixels);
+    });
+    let simd = Params {
+        simd: true,
+        ..params
+    };
+    bench("render/160x120/simd", || {
+        render(&mut pixels, &region, &simd);
+        black_box(&pixels);
+    });
+
+    // The set fills the top of this view and the bottom is open exterior,
+    // so fixed bands of rows, one per thread, would split the work unevenly.
+    // Threads pulling rows or tiles off a queue stay balanced, so given the
+    // cores four threads take close to a quarter of the time of one.
+    let lopsided = Region::new((320, 240), cmplx!(-2.0, 1.2), cmplx!(1.0, -4.8));
+    let mut pixels = vec![0u8; 320 * 240];
+    for threads in [1, 4] {
+        bench(&format!("render_parallel/lopsided/{}", threads), || {
+            render_parallel(&mut pixels, &lopsided, &params, threads);
+            black_box(&pixels);
+        });
+        bench(&format!("render_tiled/lopsided/{}", threads), || {
+            render_tiled(&mut pixels, &lopsided, &params, threads, 64);
+            black_box(&pixels);
+        });
+    }
+}
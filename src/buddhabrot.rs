@@ -0,0 +1,146 @@
+//! The Buddhabrot: rather than coloring each point by how long its orbit
+//! takes to escape, it draws where the orbits of escaping points go, by
+//! how many times they pass through each pixel.
+//!
+//! The points are sampled at random, so the image is noisy; the more
+//! samples per pixel, the smoother it comes out.
+
+use std::time::Instant;
+
+use crate::random::SplitMix;
+use crate::{cmplx, in_rows, worker_index, Params, Progress, Region, Sample, Stats};
+
+/// Half the side of the square, centered on the origin, that the points
+/// whose orbits are traced are sampled from. It holds the whole set.
+const SAMPLE_RADIUS: f64 = 2.0;
+
+/// The number of times the orbits of points sampled at random pass through
+/// each pixel of `region`, row by row, counting only orbits that escape
+/// before `params.limit`, after the sampled point itself. `params.samples`
+/// points are sampled per pixel, from `params.seed`,
+/// one row's worth at a time, on `threads` threads; each finished row's
+/// worth is counted in `progress`, if given, and once it's cancelled no
+/// more are started. Orbits start at `params.z0` and are iterated in `f64`.
+pub fn buddhabrot_hits(
+    region: &Region,
+    params: &Params,
+    threads: usize,
+    progress: Option<&Progress>,
+) -> Vec<u32> {
+    let (width, height) = region.bounds;
+    let per_row = u64::from(params.samples) * width as u64;
+    let (fractal, power, bailout, z0) = (params.fractal, params.power, params.bailout, params.z0);
+
+    let trace = |seeds: &mut [u64], top: usize| {
+        let start = Instant::now();
+        let mut hits = vec![0; width * height];
+        let mut orbit = Vec::new();
+        for &seed in &*seeds {
+            if progress.is_some_and(Progress::is_cancelled) {
+                break;
+            }
+            let mut random = SplitMix(seed);
+            for _ in 0..per_row {
+                let c = cmplx!(random.uniform(), random.uniform()) * SAMPLE_RADIUS;
+                orbit.clear();
+                let visit = |z| orbit.push(z);
+                if fractal
+                    .escape_orbit(power, bailout, z0, c, params.limit, visit)
+                    .is_none()
+                {
+                    continue;
+                }
+                for &z in &orbit[1..] {
+                    if let Some((col, row)) = region.point_to_pixel(z) {
+                        hits[row * width + col] += 1;
+                    }
+                }
+            }
+            if let Some(progress) = progress {
+                progress.add_rows(1);
+            }
+        }
+        if let Some(progress) = progress {
+            progress.add_band(worker_index(), top, seeds.len(), start.elapsed());
+        }
+        hits
+    };
+    // Each row's worth of samples is drawn from its own generator, so
+    // renders come out the same on any number of threads.
+    let seed = params.seed.rotate_left(32);
+    let mut seeds: Vec<u64> = (0..height as u64).map(|row| row ^ seed).collect();
+    in_rows(&mut seeds, 1, threads, trace, Vec::new, add_hits)
+}
+
+/// The sum of two sets of hit counts, either of which may be empty.
+fn add_hits(mut hits: Vec<u32>, other: Vec<u32>) -> Vec<u32> {
+    if hits.is_empty() {
+        return other;
+    }
+    for (count, other) in hits.iter_mut().zip(other) {
+        *count += other;
+    }
+    hits
+}
+
+/// Render the Buddhabrot of `region` into `pixels`, which holds
+/// `params.channels()` samples per pixel, shading each pixel by its
+/// `buddhabrot_hits` relative to the most of any pixel, scaled by
+/// `params.scale`, in gray or along the palette. The escape values
+/// reported in `Stats` are the hits, and pixels without any count as
+/// inside the set.
+pub fn render_buddhabrot<P: Sample>(
+    pixels: &mut [P],
+    region: &Region,
+    params: &Params,
+    threads: usize,
+    progress: Option<&Progress>,
+) -> Stats {
+    let bounds = region.bounds;
+    assert!(pixels.len() == bounds.0 * bounds.1 * params.channels());
+    let hits = buddhabrot_hits(region, params, threads, progress);
+    let most = f64::from(hits.iter().copied().max().unwrap_or(0).max(1));
+
+    let mut stats = Stats::default();
+    for (pixel, &count) in pixels.chunks_mut(params.channels()).zip(&hits) {
+        let count = f64::from(count);
+        let color = params.split_alpha(count == 0.0, pixel);
+        params.fill_level(params.scale.apply(count, most) / most, color);
+        params.apply_gamma(pixel);
+        stats.record((count > 0.0).then_some(count));
+    }
+
+    stats
+}
+
+#[cfg(test)]
+mod test {
+    use super::buddhabrot_hits;
+    use crate::{cmplx, render, render_parallel, Fractal, Params, Region};
+
+    #[test]
+    fn renders_orbits() {
+        let bounds = (40, 30);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.5), cmplx!(1.0, -1.5));
+        let params = Params {
+            fractal: Fractal::Buddhabrot,
+            samples: 10,
+            ..Params::default()
+        };
+        let hits = buddhabrot_hits(&region, &params, 1, None);
+        assert_eq!(hits, buddhabrot_hits(&region, &params, 4, None));
+        // Orbits are symmetric about the real axis.
+        let (top, bottom) = hits.split_at(hits.len() / 2);
+        let (top, bottom) = (top.iter().sum::<u32>(), bottom.iter().sum::<u32>());
+        assert!(top.abs_diff(bottom) * 10 < top, "{} {}", top, bottom);
+
+        let mut pixels = vec![0u8; bounds.0 * bounds.1];
+        let stats = render(&mut pixels, &region, &params);
+        assert!(pixels.contains(&255));
+        assert!(pixels.iter().filter(|&&p| p > 0).count() > pixels.len() / 4);
+        assert_eq!(stats.max, f64::from(*hits.iter().max().unwrap()));
+        let mut parallel = vec![0u8; pixels.len()];
+        render_parallel(&mut parallel, &region, &params, 3);
+        assert_eq!(parallel, pixels);
+    }
+}
@@ -0,0 +1,199 @@
+//! Checkpoints of renders in tiles: the image so far and which of its
+//! tiles are finished, so that a render stopped partway can be picked up
+//! where it left off by `render_tiled_resumable`.
+//!
+//! A checkpoint file holds, all little-endian, a magic number, a hash of
+//! the render's region and parameters, the width, height, channels, bits
+//! per channel and tile size, the escape statistics of the finished tiles,
+//! a byte for each tile saying whether it's finished, and every sample of
+//! the image as a `u16`.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+
+use crate::{Params, Region, Sample, Stats, TiledState};
+
+/// The start of every checkpoint file.
+const MAGIC: &[u8; 8] = b"mbrotck1";
+
+/// A hash of everything about the render of `region` with `params` that
+/// goes into its pixels, so that a checkpoint isn't resumed into some
+/// other render.
+pub fn fingerprint(region: &Region, params: &Params) -> u64 {
+    // FNV-1a.
+    let text = format!("{:?} {:?}", region, params);
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// The bits per channel of samples of type `P`.
+fn bits<P: Sample>() -> u8 {
+    match P::MAX {
+        0xff => 8,
+        _ => 16,
+    }
+}
+
+/// Write the image `pixels` of the render of `region` with `params` and
+/// its progress `state` to the checkpoint file `filename`. The file is
+/// written under another name first and then moved into place, so an
+/// earlier checkpoint is never left half overwritten.
+pub fn write_checkpoint<P: Sample>(
+    filename: &str,
+    region: &Region,
+    params: &Params,
+    pixels: &[P],
+    state: &TiledState,
+) -> io::Result<()> {
+    let temp = format!("{}.partial", filename);
+    let mut output = BufWriter::new(File::create(&temp)?);
+    output.write_all(MAGIC)?;
+    output.write_all(&fingerprint(region, params).to_le_bytes())?;
+    for value in [region.bounds.0, region.bounds.1] {
+        output.write_all(&(value as u64).to_le_bytes())?;
+    }
+    output.write_all(&[params.channels() as u8, bits::<P>()])?;
+    output.write_all(&(state.tile_size as u64).to_le_bytes())?;
+    let stats = &state.stats;
+    for value in [stats.pixels, stats.interior] {
+        output.write_all(&value.to_le_bytes())?;
+    }
+    for value in [stats.min, stats.max, stats.sum] {
+        output.write_all(&value.to_le_bytes())?;
+    }
+    output.write_all(&(state.done.len() as u64).to_le_bytes())?;
+    let done: Vec<u8> = state.done.iter().map(|&done| u8::from(done)).collect();
+    output.write_all(&done)?;
+    for &sample in pixels {
+        output.write_all(&(sample.to_f64() as u16).to_le_bytes())?;
+    }
+    output.into_inner().map_err(io::Error::from)?.sync_all()?;
+
+    fs::rename(temp, filename)
+}
+
+/// Read the checkpoint file `filename` of the render of `region` with
+/// `params` and samples of type `P`, returning the image and the progress
+/// it holds. A checkpoint of any other render, or that isn't a checkpoint
+/// at all, fails with `io::ErrorKind::InvalidData`.
+pub fn read_checkpoint<P: Sample>(
+    filename: &str,
+    region: &Region,
+    params: &Params,
+) -> io::Result<(Vec<P>, TiledState)> {
+    let bytes = fs::read(filename)?;
+    let mut input = Input(&bytes);
+    if input.take(MAGIC.len())? != MAGIC {
+        return Err(invalid("not a checkpoint file"));
+    }
+    let hash = input.u64()?;
+    let (width, height) = (input.u64()? as usize, input.u64()? as usize);
+    let layout = input.take(2)?;
+    if hash != fingerprint(region, params)
+        || (width, height) != region.bounds
+        || layout != [params.channels() as u8, bits::<P>()]
+    {
+        return Err(invalid("the checkpoint is of a different render"));
+    }
+    let tile_size = input.u64()? as usize;
+    let (pixels, interior) = (input.u64()?, input.u64()?);
+    let (min, max, sum) = (input.f64()?, input.f64()?, input.f64()?);
+    let stats = Stats {
+        pixels,
+        interior,
+        min,
+        max,
+        sum,
+    };
+    let tiles = input.u64()? as usize;
+    if tile_size == 0 || TiledState::new(region.bounds, tile_size).done.len() != tiles {
+        return Err(invalid("the checkpoint's tiles don't cover the image"));
+    }
+    let done = input.take(tiles)?.iter().map(|&done| done != 0).collect();
+    let samples = width * height * params.channels();
+    let samples = input.take(samples * 2)?.chunks(2);
+    let samples = samples.map(|bytes| u64::from(u16::from_le_bytes([bytes[0], bytes[1]])));
+    let samples: Vec<u64> = samples.collect();
+    if !input.0.is_empty() || samples.iter().any(|&sample| sample > P::MAX) {
+        return Err(invalid("the checkpoint's image is corrupt"));
+    }
+    let image = samples.into_iter().map(P::from_u64).collect();
+
+    let state = TiledState {
+        tile_size,
+        done,
+        stats,
+    };
+    Ok((image, state))
+}
+
+/// An `InvalidData` error saying `what`.
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, what)
+}
+
+/// The bytes of a checkpoint file not yet read.
+struct Input<'a>(&'a [u8]);
+
+impl<'a> Input<'a> {
+    /// The next `len` bytes, failing if the file ends first.
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(invalid("the checkpoint file is cut short"));
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn f64(&mut self) -> io::Result<f64> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{fingerprint, read_checkpoint, write_checkpoint};
+    use crate::{cmplx, Params, Region, TiledState};
+    use std::io::ErrorKind;
+
+    #[test]
+    fn round_trip() {
+        let region = Region::new((5, 3), cmplx!(-2.0, 1.0), cmplx!(1.0, -1.0));
+        let params = Params::default();
+        let pixels: Vec<u16> = (0..15).map(|i| i * 4000).collect();
+        let mut state = TiledState::new(region.bounds, 2);
+        state.done[1] = true;
+        state.stats.record(Some(2.5));
+        state.stats.record(None);
+        let path = std::env::temp_dir().join("mandelbrot-checkpoint-test.ck");
+        let file = path.to_str().unwrap();
+
+        write_checkpoint(file, &region, &params, &pixels, &state).unwrap();
+        assert_eq!(
+            read_checkpoint::<u16>(file, &region, &params).unwrap(),
+            (pixels, state)
+        );
+        // Other renders, and other sample types, are refused.
+        let deeper = Params {
+            limit: params.limit + 1,
+            ..params
+        };
+        assert_ne!(fingerprint(&region, &params), fingerprint(&region, &deeper));
+        let err = read_checkpoint::<u16>(file, &region, &deeper).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        let err = read_checkpoint::<u8>(file, &region, &params).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        let bytes = std::fs::read(file).unwrap();
+        std::fs::write(file, &bytes[..bytes.len() - 1]).unwrap();
+        let err = read_checkpoint::<u16>(file, &region, &params).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
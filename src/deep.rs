@@ -0,0 +1,420 @@
+//! Deep zooms by perturbation: one reference orbit is iterated in extended
+//! precision at the center of the view, and every pixel follows only its
+//! difference from that orbit, in `f64`.
+//!
+//! Plain `f64` coordinates run out of bits at zooms of about 1e-13, where
+//! neighboring pixels round to the same point. Differences from the center
+//! keep their precision however small they get, so the limit becomes that
+//! of `DoubleDouble`, at zooms of about 1e-28.
+
+use std::cmp::Ordering;
+use std::ops::{Add, Mul, Neg, Sub};
+use std::str::FromStr;
+use std::time::Instant;
+
+use num_complex::Complex64;
+
+use crate::{cmplx, in_rows, worker_index, Params, Progress, Sample, Shading, Stats, BAILOUT};
+
+/// A float with about twice the precision of `f64`, as the unevaluated sum
+/// of two `f64`s, `lo` being less than half an ulp of `hi`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DoubleDouble {
+    pub hi: f64,
+    pub lo: f64,
+}
+
+impl DoubleDouble {
+    /// The sum `hi + lo`, rounded to the nearest `f64`.
+    pub fn to_f64(self) -> f64 {
+        self.hi + self.lo
+    }
+
+    /// The sum of `a` and `b`, exactly, given that `|a| >= |b|`.
+    fn quick_two_sum(a: f64, b: f64) -> DoubleDouble {
+        let hi = a + b;
+        DoubleDouble {
+            hi,
+            lo: b - (hi - a),
+        }
+    }
+
+    /// The sum of `a` and `b`, exactly.
+    fn two_sum(a: f64, b: f64) -> DoubleDouble {
+        let hi = a + b;
+        let v = hi - a;
+        DoubleDouble {
+            hi,
+            lo: (a - (hi - v)) + (b - v),
+        }
+    }
+
+    /// The product of `a` and `b`, exactly.
+    fn two_prod(a: f64, b: f64) -> DoubleDouble {
+        let hi = a * b;
+        DoubleDouble {
+            hi,
+            lo: a.mul_add(b, -hi),
+        }
+    }
+
+    /// `self` divided by `other`, to within a few ulps of `lo`.
+    fn div(self, other: DoubleDouble) -> DoubleDouble {
+        let q1 = self.hi / other.hi;
+        let r = self - other * DoubleDouble::from(q1);
+        let q2 = r.hi / other.hi;
+        let r = r - other * DoubleDouble::from(q2);
+        let q3 = r.hi / other.hi;
+        DoubleDouble::quick_two_sum(q1, q2) + DoubleDouble::from(q3)
+    }
+}
+
+impl From<f64> for DoubleDouble {
+    fn from(hi: f64) -> DoubleDouble {
+        DoubleDouble { hi, lo: 0.0 }
+    }
+}
+
+impl Add for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn add(self, other: DoubleDouble) -> DoubleDouble {
+        let s = DoubleDouble::two_sum(self.hi, other.hi);
+        let t = DoubleDouble::two_sum(self.lo, other.lo);
+        let s = DoubleDouble::quick_two_sum(s.hi, s.lo + t.hi);
+        DoubleDouble::quick_two_sum(s.hi, s.lo + t.lo)
+    }
+}
+
+impl Neg for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn neg(self) -> DoubleDouble {
+        DoubleDouble {
+            hi: -self.hi,
+            lo: -self.lo,
+        }
+    }
+}
+
+impl Sub for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn sub(self, other: DoubleDouble) -> DoubleDouble {
+        self + -other
+    }
+}
+
+impl Mul for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn mul(self, other: DoubleDouble) -> DoubleDouble {
+        let p = DoubleDouble::two_prod(self.hi, other.hi);
+        let lo = p.lo + (self.hi * other.lo + self.lo * other.hi);
+        DoubleDouble::quick_two_sum(p.hi, lo)
+    }
+}
+
+/// Parses decimal numbers like `-0.7436438870371587`, `5` or `1.5e-3`,
+/// keeping the digits past the precision of `f64`.
+impl FromStr for DoubleDouble {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<DoubleDouble, ()> {
+        let s = s.trim();
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (mantissa, exponent) = match s.find(['e', 'E']) {
+            Some(at) => (&s[..at], s[at + 1..].parse::<i32>().map_err(|_| ())?),
+            None => (s, 0),
+        };
+        let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let digits = || whole.chars().chain(fraction.chars());
+        if digits().next().is_none() || !digits().all(|c| c.is_ascii_digit()) {
+            return Err(());
+        }
+
+        let ten = DoubleDouble::from(10.0);
+        let mut value = DoubleDouble::default();
+        for digit in digits() {
+            value = value * ten + DoubleDouble::from(f64::from(digit as u8 - b'0'));
+        }
+        let exponent = exponent - fraction.len() as i32;
+        let mut scale = DoubleDouble::from(1.0);
+        for _ in 0..exponent.unsigned_abs() {
+            scale = scale * ten;
+        }
+        value = match exponent.cmp(&0) {
+            Ordering::Less => value.div(scale),
+            _ => value * scale,
+        };
+
+        Ok(if negative { -value } else { value })
+    }
+}
+
+/// A view centered on a point given in extended precision.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DeepView {
+    /// The real part of the center of the view.
+    pub re: DoubleDouble,
+    /// The imaginary part of the center of the view.
+    pub im: DoubleDouble,
+    /// Half the width of the view along the real axis, as for
+    /// `corners_from_center`.
+    pub zoom: f64,
+    /// The width and height of the image in pixels.
+    pub bounds: (usize, usize),
+    /// Point the imaginary axis down the image, as for `Region::flip_y`.
+    pub flip_y: bool,
+}
+
+impl DeepView {
+    /// The view of size `bounds` centered on `center`, written like
+    /// `"RE,IM"`, with `zoom` as for `corners_from_center`.
+    pub fn parse(center: &str, zoom: f64, bounds: (usize, usize)) -> Option<DeepView> {
+        let (re, im) = crate::parse_pair(center, ',').ok()?;
+        Some(DeepView {
+            re,
+            im,
+            zoom,
+            bounds,
+            flip_y: false,
+        })
+    }
+
+    /// The offset from the center of the view of the point the pixel
+    /// `pixel` covers, which `Region::pixel_to_point` would give for the
+    /// same view.
+    pub fn pixel_to_delta(&self, pixel: (usize, usize)) -> Complex64 {
+        let (width, height) = (self.bounds.0 as f64, self.bounds.1 as f64);
+        let row = match self.flip_y {
+            false => pixel.1,
+            true => self.bounds.1 - 1 - pixel.1,
+        };
+        let half_height = self.zoom * height / width;
+        cmplx!(
+            self.zoom * (2.0 * pixel.0 as f64 / width - 1.0),
+            half_height * (1.0 - 2.0 * row as f64 / height)
+        )
+    }
+}
+
+/// The orbit of the Mandelbrot set's `c = re + im·i`, iterated in extended
+/// precision and rounded to `f64`, from `z = 0` up to and including the
+/// first point outside the circle of radius `BAILOUT`, or for `limit`
+/// iterations.
+pub fn reference_orbit(re: DoubleDouble, im: DoubleDouble, limit: u32) -> Vec<Complex64> {
+    let (mut x, mut y) = (DoubleDouble::default(), DoubleDouble::default());
+    let mut orbit = vec![cmplx!()];
+    for _ in 0..limit {
+        let xy = x * y;
+        (x, y) = (x * x - y * y + re, xy + xy + im);
+        let z = cmplx!(x.to_f64(), y.to_f64());
+        orbit.push(z);
+        if z.norm_sqr() > BAILOUT * BAILOUT {
+            break;
+        }
+    }
+
+    orbit
+}
+
+/// The escape time of the point `delta` away from the `c` of `orbit`, a
+/// `reference_orbit`, counted as `escape_time` counts it. The difference
+/// is carried along the reference orbit and rebased onto its start
+/// whenever the orbit runs out or the point comes closer to zero than
+/// the difference, where the difference would lose its precision.
+pub fn perturbed_escape_time(orbit: &[Complex64], delta: Complex64, limit: u32) -> Option<u32> {
+    let mut dz = cmplx!();
+    let mut m = 0;
+    for i in 0..limit {
+        dz = (orbit[m] * 2.0 + dz) * dz + delta;
+        m += 1;
+        let z = orbit[m] + dz;
+        if z.norm_sqr() > BAILOUT * BAILOUT {
+            return Some(i);
+        }
+        if z.norm_sqr() < dz.norm_sqr() || m == orbit.len() - 1 {
+            dz = z;
+            m = 0;
+        }
+    }
+
+    None
+}
+
+/// Render `view` into `pixels`, which holds `params.channels()` samples
+/// per pixel, on `threads` threads like `render_parallel`. `params` must
+/// ask for `plain_escape_counts`.
+pub fn render_deep<P: Sample>(
+    pixels: &mut [P],
+    view: &DeepView,
+    params: &Params,
+    threads: usize,
+) -> Stats {
+    render_deep_inner(pixels, view, params, threads, None)
+}
+
+/// Like `render_deep`, counting finished rows in `progress` as
+/// `render_parallel_with_progress` does.
+pub fn render_deep_with_progress<P: Sample>(
+    pixels: &mut [P],
+    view: &DeepView,
+    params: &Params,
+    threads: usize,
+    progress: &Progress,
+) -> Stats {
+    render_deep_inner(pixels, view, params, threads, Some(progress))
+}
+
+fn render_deep_inner<P: Sample>(
+    pixels: &mut [P],
+    view: &DeepView,
+    params: &Params,
+    threads: usize,
+    progress: Option<&Progress>,
+) -> Stats {
+    assert!(
+        params.plain_escape_counts(),
+        "deep zoom of an unsupported render"
+    );
+    let (bounds, channels) = (view.bounds, params.channels());
+    assert!(pixels.len() == bounds.0 * bounds.1 * channels);
+    let orbit = reference_orbit(view.re, view.im, params.limit);
+
+    let paint = |band: &mut [P], top: usize| {
+        let start = Instant::now();
+        let mut stats = Stats::default();
+        for (i, line) in band.chunks_mut(bounds.0 * channels).enumerate() {
+            if progress.is_some_and(Progress::is_cancelled) {
+                break;
+            }
+            for (col, pixel) in line.chunks_mut(channels).enumerate() {
+                let delta = view.pixel_to_delta((col, top + i));
+                let value = perturbed_escape_time(&orbit, delta, params.limit).map(f64::from);
+                params.fill(value, Shading::Linear, pixel);
+                params.apply_gamma(pixel);
+                stats.record(value);
+            }
+            if let Some(progress) = progress {
+                progress.add_rows(1);
+            }
+        }
+        if let Some(progress) = progress {
+            let rows = band.len() / (bounds.0 * channels).max(1);
+            progress.add_band(worker_index(), top, rows, start.elapsed());
+        }
+        stats
+    };
+    in_rows(
+        pixels,
+        bounds.0 * channels,
+        threads,
+        paint,
+        Stats::default,
+        Stats::merge,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{perturbed_escape_time, reference_orbit, render_deep, DeepView, DoubleDouble};
+    use crate::{cmplx, corners_from_center, escape_counts, render, Palette, Params, Region};
+
+    #[test]
+    fn double_double_arithmetic() {
+        let third = DoubleDouble::from(1.0).div(DoubleDouble::from(3.0));
+        let one = third * DoubleDouble::from(3.0);
+        assert!((one - DoubleDouble::from(1.0)).to_f64().abs() < 1e-30);
+        // 1 + 2^-70 doesn't fit in an f64, but survives in the low part.
+        let tiny = 2f64.powi(-70);
+        let sum = DoubleDouble::from(1.0) + DoubleDouble::from(tiny);
+        assert_eq!((sum.hi, sum.lo), (1.0, tiny));
+        assert_eq!((sum - DoubleDouble::from(1.0)).to_f64(), tiny);
+    }
+
+    #[test]
+    fn parse() {
+        let pp = |s: &str| s.parse::<DoubleDouble>();
+        assert_eq!(pp("2"), Ok(DoubleDouble::from(2.0)));
+        assert_eq!(pp("-1.5e-3").map(DoubleDouble::to_f64), Ok(-1.5e-3));
+        assert_eq!(pp("+.25").map(DoubleDouble::to_f64), Ok(0.25));
+        // The digits past 17 are kept.
+        let long = pp("0.10000000000000000000001").unwrap();
+        let diff = long - pp("0.1").unwrap();
+        assert!((diff.to_f64() - 1e-23).abs() < 1e-32, "{:?}", diff);
+        for bad in ["", "-", ".", "1.2.3", "1e", "0x10", "inf", "1,5"] {
+            assert_eq!(pp(bad), Err(()), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn perturbation_matches_direct_escape_times() {
+        let center = (-0.743643887037151, 0.131825904205330);
+        let (bounds, zoom, limit) = ((64, 48), 1e-6, 1000);
+        let view = DeepView::parse(&format!("{:?},{:?}", center.0, center.1), zoom, bounds);
+        let view = view.unwrap();
+        let orbit = reference_orbit(view.re, view.im, limit);
+        assert_eq!(orbit[1], cmplx!(center.0, center.1));
+
+        let (upper_l, lower_r) = corners_from_center(cmplx!(center.0, center.1), zoom, bounds);
+        let region = Region::new(bounds, upper_l, lower_r);
+        let params = Params {
+            limit,
+            ..Params::default()
+        };
+        let direct = escape_counts(&region, &params, 1);
+        let mut same = 0;
+        for row in 0..bounds.1 {
+            for col in 0..bounds.0 {
+                let delta = view.pixel_to_delta((col, row));
+                let deep = perturbed_escape_time(&orbit, delta, limit).unwrap_or(limit);
+                same += usize::from(deep == direct[row * bounds.0 + col]);
+            }
+        }
+        assert!(same * 100 >= bounds.0 * bounds.1 * 99, "{}", same);
+
+        for palette in [None, Some(Palette::Fire)] {
+            let params = Params { palette, ..params };
+            let mut expected = vec![0u8; bounds.0 * bounds.1 * params.channels()];
+            render(&mut expected, &region, &params);
+            let mut pixels = vec![0u8; expected.len()];
+            render_deep(&mut pixels, &view, &params, 3);
+            let same = pixels.iter().zip(&expected).filter(|(p, q)| p == q);
+            assert!(same.count() * 100 >= expected.len() * 99);
+        }
+    }
+
+    #[test]
+    fn resolves_past_f64() {
+        // At this zoom every row of a direct render rounds to the same
+        // imaginary part, so they all come out alike.
+        // c = i is on the boundary of the set, which is as intricate
+        // around it however far in one zooms.
+        let center = "0,1";
+        let (bounds, zoom) = ((32, 24), 1e-20);
+        let view = DeepView::parse(center, zoom, bounds).unwrap();
+        let params = Params::default();
+        let mut pixels = vec![0u8; bounds.0 * bounds.1];
+        render_deep(&mut pixels, &view, &params, 2);
+        let distinct_rows = |pixels: &[u8]| {
+            let mut rows: Vec<&[u8]> = pixels.chunks(bounds.0).collect();
+            rows.sort();
+            rows.dedup();
+            rows.len()
+        };
+        assert!(distinct_rows(&pixels) > bounds.1 / 2);
+
+        let (upper_l, lower_r) = corners_from_center(cmplx!(0.0, 1.0), zoom, bounds);
+        let mut direct = vec![0u8; pixels.len()];
+        render(&mut direct, &Region::new(bounds, upper_l, lower_r), &params);
+        assert_eq!(distinct_rows(&direct), 1);
+        let mut shades = pixels.clone();
+        shades.sort();
+        shades.dedup();
+        assert!(shades.len() > 10, "{}", shades.len());
+    }
+}
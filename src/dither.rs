@@ -0,0 +1,163 @@
+//! Reducing wider samples to 8 bits without the banding rounding leaves in
+//! smooth gradients.
+
+use crate::Sample;
+
+/// How rounding to 8 bits spreads its error.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Dither {
+    /// Round each sample to the nearest value.
+    #[default]
+    None,
+    /// Offset each sample by a threshold from an 8×8 Bayer matrix before
+    /// rounding.
+    Ordered,
+    /// Pass each sample's rounding error on to its unrounded neighbors to
+    /// the right and below.
+    FloydSteinberg,
+}
+
+impl Dither {
+    /// Every way of dithering, in the order they're listed in the help.
+    pub const ALL: [Dither; 3] = [Dither::None, Dither::Ordered, Dither::FloydSteinberg];
+
+    /// Look up a way of dithering by the name used on the command line.
+    pub fn from_name(name: &str) -> Option<Dither> {
+        Dither::ALL.into_iter().find(|dither| dither.name() == name)
+    }
+
+    /// The name of this way of dithering on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Dither::None => "none",
+            Dither::Ordered => "ordered",
+            Dither::FloydSteinberg => "floyd-steinberg",
+        }
+    }
+}
+
+/// The 8×8 Bayer matrix, whose entries order the thresholds of ordered
+/// dithering so that every level is spread evenly over the square.
+const BAYER: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// Reduces the rows of an image to 8 bits a few at a time, top to bottom,
+/// so that strips of an image dither as the whole image would.
+pub struct Ditherer {
+    dither: Dither,
+    width: usize,
+    channels: usize,
+    /// The index of the next row.
+    row: usize,
+    /// Error carried into each sample of the next row.
+    below: Vec<f64>,
+}
+
+impl Ditherer {
+    /// A ditherer for images `width` pixels wide with `channels` samples
+    /// per pixel.
+    pub fn new(dither: Dither, width: usize, channels: usize) -> Ditherer {
+        Ditherer {
+            dither,
+            width,
+            channels,
+            row: 0,
+            below: vec![0.0; width * channels],
+        }
+    }
+
+    /// The whole rows `rows`, which follow any given before, reduced to 8
+    /// bits.
+    pub fn quantize<P: Sample>(&mut self, rows: &[P]) -> Vec<u8> {
+        let row_len = self.width * self.channels;
+        let scale = 255.0 / P::MAX as f64;
+        let mut out = Vec::with_capacity(rows.len());
+        for row in rows.chunks(row_len.max(1)) {
+            let mut here = std::mem::replace(&mut self.below, vec![0.0; row_len]);
+            for (i, &sample) in row.iter().enumerate() {
+                let x = i / self.channels;
+                let value = sample.to_f64() * scale;
+                let rounded = match self.dither {
+                    Dither::None => value.round(),
+                    Dither::Ordered => {
+                        let rank = f64::from(BAYER[self.row % 8][x % 8]);
+                        (value + (rank + 0.5) / 64.0 - 0.5).round()
+                    }
+                    Dither::FloydSteinberg => {
+                        let value = value + here[i];
+                        let rounded = value.round().clamp(0.0, 255.0);
+                        let error = value - rounded;
+                        let c = self.channels;
+                        if x + 1 < self.width {
+                            here[i + c] += error * 7.0 / 16.0;
+                            self.below[i + c] += error / 16.0;
+                        }
+                        if x > 0 {
+                            self.below[i - c] += error * 3.0 / 16.0;
+                        }
+                        self.below[i] += error * 5.0 / 16.0;
+                        rounded
+                    }
+                };
+                out.push(rounded.clamp(0.0, 255.0) as u8);
+            }
+            self.row += 1;
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Dither, Ditherer};
+
+    #[test]
+    fn from_name() {
+        for dither in Dither::ALL {
+            assert_eq!(Dither::from_name(dither.name()), Some(dither));
+        }
+        assert_eq!(Dither::from_name("bayer"), None);
+    }
+
+    #[test]
+    fn keeps_the_mean() {
+        // A flat 16-bit gray a third of the way between two 8-bit levels.
+        let (width, height) = (64, 64);
+        let level: f64 = 100.0 + 1.0 / 3.0;
+        let gray = vec![(level * 257.0).round() as u16; width * height];
+        for dither in [Dither::Ordered, Dither::FloydSteinberg] {
+            let out = Ditherer::new(dither, width, 1).quantize(&gray);
+            let mean = out.iter().map(|&v| f64::from(v)).sum::<f64>() / out.len() as f64;
+            assert!(out.iter().all(|&v| v == 100 || v == 101), "{:?}", dither);
+            assert!((mean - level).abs() < 0.02, "{:?} {}", dither, mean);
+        }
+        let plain = Ditherer::new(Dither::None, width, 1).quantize(&gray);
+        assert!(plain.iter().all(|&v| v == 100));
+    }
+
+    #[test]
+    fn strips_match_whole_image() {
+        let (width, height) = (13, 10);
+        let rgb: Vec<u16> = (0..width * height * 3)
+            .map(|i| (i * 977 % 65536) as u16)
+            .collect();
+        for dither in Dither::ALL {
+            let whole = Ditherer::new(dither, width, 3).quantize(&rgb);
+            let mut ditherer = Ditherer::new(dither, width, 3);
+            let strips: Vec<u8> = rgb
+                .chunks(4 * width * 3)
+                .flat_map(|strip| ditherer.quantize(strip))
+                .collect();
+            assert_eq!(strips, whole, "{:?}", dither);
+        }
+    }
+}
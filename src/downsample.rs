@@ -0,0 +1,173 @@
+//! Shrinking images rendered at a multiple of their size back down to it,
+//! which smooths their edges much as supersampling does, but with a choice
+//! of how the samples are weighted.
+
+use image::imageops::{self, FilterType};
+use image::{ImageBuffer, Luma, Pixel, Rgb, Rgba};
+
+use crate::Sample;
+
+/// How the pixels of an image are combined when it's shrunk.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Filter {
+    /// Average each block of pixels that becomes one.
+    #[default]
+    Box,
+    /// Weight pixels by their distance, blending each with its neighbours'
+    /// blocks too.
+    Triangle,
+    /// The sharper Lanczos window of three lobes, which can ring slightly
+    /// around hard edges.
+    Lanczos,
+}
+
+impl Filter {
+    /// Every filter, in the order they're listed on the command line.
+    pub const ALL: [Filter; 3] = [Filter::Box, Filter::Triangle, Filter::Lanczos];
+
+    /// Look up a filter by the name used on the command line.
+    pub fn from_name(name: &str) -> Option<Filter> {
+        Filter::ALL.into_iter().find(|filter| filter.name() == name)
+    }
+
+    /// The name of this filter on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Filter::Box => "box",
+            Filter::Triangle => "triangle",
+            Filter::Lanczos => "lanczos",
+        }
+    }
+}
+
+/// Shrink the image `pixels` of size `bounds`, with `channels` samples per
+/// pixel, by `factor` along each axis with `filter`. Both dimensions must
+/// be multiples of `factor`.
+pub fn downsample<P: Sample>(
+    pixels: &[P],
+    bounds: (usize, usize),
+    channels: usize,
+    factor: usize,
+    filter: Filter,
+) -> Vec<P> {
+    assert!(factor > 0 && bounds.0.is_multiple_of(factor) && bounds.1.is_multiple_of(factor));
+    assert!(pixels.len() == bounds.0 * bounds.1 * channels);
+    let small = (bounds.0 / factor, bounds.1 / factor);
+    let filter = match filter {
+        Filter::Box => return average_blocks(pixels, bounds, channels, factor),
+        Filter::Triangle => FilterType::Triangle,
+        Filter::Lanczos => FilterType::Lanczos3,
+    };
+
+    let max = P::MAX as f64;
+    let samples = pixels.iter().map(|&p| (p.to_f64() / max) as f32).collect();
+    let resized = match channels {
+        1 => resize::<Luma<f32>>(samples, bounds, small, filter),
+        3 => resize::<Rgb<f32>>(samples, bounds, small, filter),
+        4 => resize::<Rgba<f32>>(samples, bounds, small, filter),
+        _ => unreachable!("images have 1, 3 or 4 channels"),
+    };
+    resized
+        .into_iter()
+        .map(|v| P::from_u64((f64::from(v) * max).round().clamp(0.0, max) as u64))
+        .collect()
+}
+
+/// The image `pixels` of size `bounds` shrunk by averaging each block of
+/// `factor` by `factor` pixels.
+fn average_blocks<P: Sample>(
+    pixels: &[P],
+    bounds: (usize, usize),
+    channels: usize,
+    factor: usize,
+) -> Vec<P> {
+    let (width, height) = (bounds.0 / factor, bounds.1 / factor);
+    let row_len = bounds.0 * channels;
+    let count = (factor * factor) as f64;
+    let mut small = Vec::with_capacity(width * height * channels);
+    for row in 0..height {
+        for col in 0..width {
+            for channel in 0..channels {
+                let mut sum = 0.0;
+                for y in row * factor..(row + 1) * factor {
+                    let line = &pixels[y * row_len..(y + 1) * row_len];
+                    for x in col * factor..(col + 1) * factor {
+                        sum += line[x * channels + channel].to_f64();
+                    }
+                }
+                small.push(P::from_u64((sum / count).round() as u64));
+            }
+        }
+    }
+
+    small
+}
+
+/// The samples of an image of size `from` and pixel type `Px` resized to
+/// `to` with `filter`.
+fn resize<Px: Pixel<Subpixel = f32> + 'static>(
+    samples: Vec<f32>,
+    from: (usize, usize),
+    to: (usize, usize),
+    filter: FilterType,
+) -> Vec<f32> {
+    let image = ImageBuffer::<Px, _>::from_raw(from.0 as u32, from.1 as u32, samples)
+        .expect("the buffer holds the whole image");
+    imageops::resize(&image, to.0 as u32, to.1 as u32, filter).into_raw()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{downsample, Filter};
+    use crate::{cmplx, render, Palette, Params, Region};
+
+    #[test]
+    fn from_name() {
+        for filter in Filter::ALL {
+            assert_eq!(Filter::from_name(filter.name()), Some(filter));
+        }
+        assert_eq!(Filter::from_name("bicubic"), None);
+    }
+
+    #[test]
+    fn box_averages_blocks() {
+        let params = Params {
+            palette: Some(Palette::Fire),
+            smooth: true,
+            ..Params::default()
+        };
+        let (bounds, channels) = ((16, 12), params.channels());
+        let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(1.0, -1.2));
+        let mut pixels = vec![0u8; bounds.0 * bounds.1 * channels];
+        render(&mut pixels, &region, &params);
+
+        let small = downsample(&pixels, bounds, channels, 2, Filter::Box);
+        assert_eq!(small.len(), pixels.len() / 4);
+        let at = |col: usize, row: usize, c: usize| {
+            u32::from(pixels[(row * bounds.0 + col) * channels + c])
+        };
+        for row in 0..bounds.1 / 2 {
+            for col in 0..bounds.0 / 2 {
+                for c in 0..channels {
+                    let (x, y) = (col * 2, row * 2);
+                    let sum = at(x, y, c) + at(x + 1, y, c) + at(x, y + 1, c) + at(x + 1, y + 1, c);
+                    let average = (f64::from(sum) / 4.0).round() as u8;
+                    assert_eq!(small[(row * bounds.0 / 2 + col) * channels + c], average);
+                }
+            }
+        }
+        assert_ne!(
+            small,
+            downsample(&pixels, bounds, channels, 2, Filter::Triangle)
+        );
+    }
+
+    #[test]
+    fn filters_keep_flat_images() {
+        let flat = vec![1000u16; 12 * 6];
+        for filter in Filter::ALL {
+            assert_eq!(downsample(&flat, (12, 6), 1, 3, filter), vec![1000u16; 8]);
+        }
+        assert_eq!(downsample(&flat, (12, 6), 1, 1, Filter::Box), flat);
+    }
+}
@@ -0,0 +1,965 @@
+//! Escape-time iteration of the quadratic map `z = z * z + c` and its
+//! variants: other exponents, the Burning Ship and the Phoenix, whose next
+//! point depends on the last two. Also Newton's method for
+//! `z³ - 1`, whose points converge to a root rather than escape.
+//!
+//! Everything here is generic over the float type, so callers can trade
+//! precision for speed by iterating in `f32` instead of `f64`.
+
+use num_complex::{Complex, Complex64};
+use num_traits::Float;
+
+/// The map iterated for each point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fractal {
+    /// `z = z² + c`.
+    Mandelbrot,
+    /// `z = (|Re z| + i|Im z|)² + c`.
+    BurningShip,
+    /// `z = conj(z)² + c`, also called the Mandelbar.
+    Tricorn,
+    /// `z = |Re z²| + i Im z² + c`.
+    Celtic,
+    /// `z = (|Re z| + i Im z)² + c`, whose set is heart-shaped.
+    Heart,
+    /// `z = z² + c + p·z'`, where `z'` is the point before `z` in the orbit,
+    /// zero for the first step, and `p` is carried here.
+    Phoenix(Complex64),
+    /// Newton's method for `z³ - 1`, `z = z - (z³ - 1) / 3z²`, from each
+    /// point of the plane. Its orbits converge rather than escape; see
+    /// `newton_basin`.
+    Newton,
+    /// The map of `Mandelbrot`, drawn as the density of the orbits of the
+    /// points that escape rather than by escape time; see `buddhabrot`.
+    Buddhabrot,
+}
+
+impl Fractal {
+    /// Look up a fractal by the name used on the command line.
+    pub fn from_name(name: &str) -> Option<Fractal> {
+        match name {
+            "mandelbrot" => Some(Fractal::Mandelbrot),
+            "burning-ship" => Some(Fractal::BurningShip),
+            "tricorn" => Some(Fractal::Tricorn),
+            "celtic" => Some(Fractal::Celtic),
+            "heart" => Some(Fractal::Heart),
+            "phoenix" => Some(Fractal::Phoenix(PHOENIX_P)),
+            "newton" => Some(Fractal::Newton),
+            "buddhabrot" => Some(Fractal::Buddhabrot),
+            _ => None,
+        }
+    }
+
+    /// The name of this fractal on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Fractal::Mandelbrot => "mandelbrot",
+            Fractal::BurningShip => "burning-ship",
+            Fractal::Tricorn => "tricorn",
+            Fractal::Celtic => "celtic",
+            Fractal::Heart => "heart",
+            Fractal::Phoenix(_) => "phoenix",
+            Fractal::Newton => "newton",
+            Fractal::Buddhabrot => "buddhabrot",
+        }
+    }
+
+    /// Apply the map once to `z`, which came after `previous` in the orbit,
+    /// raising to `power` instead of squaring. Only the Phoenix looks at
+    /// `previous`, and Newton's method ignores `power` and `c`.
+    fn step<T: Float>(
+        self,
+        power: Power,
+        z: Complex<T>,
+        previous: Complex<T>,
+        c: Complex<T>,
+    ) -> Complex<T> {
+        match self {
+            Fractal::Mandelbrot | Fractal::Buddhabrot => power.apply(z) + c,
+            Fractal::BurningShip => power.apply(Complex::new(z.re.abs(), z.im.abs())) + c,
+            Fractal::Tricorn => power.apply(z.conj()) + c,
+            Fractal::Celtic => {
+                let w = power.apply(z);
+                Complex::new(w.re.abs(), w.im) + c
+            }
+            Fractal::Heart => power.apply(Complex::new(z.re.abs(), z.im)) + c,
+            Fractal::Phoenix(p) => {
+                let p: Complex<T> = Complex::new(float(p.re), float(p.im));
+                power.apply(z) + c + p * previous
+            }
+            Fractal::Newton => newton_step(z),
+        }
+    }
+
+    /// Like the free function `escape_time`, but iterating this map with
+    /// the exponent `power` until `|z|` exceeds `bailout`.
+    pub fn escape_time<T: Float>(
+        self,
+        power: Power,
+        bailout: f64,
+        z0: Complex<T>,
+        c: Complex<T>,
+        limit: u32,
+    ) -> Option<u32> {
+        orbit(self, power, z0, c, limit, float(bailout * bailout), |_| ()).map(|(i, _)| i)
+    }
+
+    /// Like `escape_time`, but call `visit` with each point of the orbit
+    /// after `z0`, up to and including the first outside `bailout`. Orbits
+    /// seen to be periodic stop early, and points inside the main bulbs of
+    /// the quadratic Mandelbrot set aren't iterated at all.
+    pub fn escape_orbit<T: Float>(
+        self,
+        power: Power,
+        bailout: f64,
+        z0: Complex<T>,
+        c: Complex<T>,
+        limit: u32,
+        visit: impl FnMut(Complex<T>),
+    ) -> Option<u32> {
+        orbit(self, power, z0, c, limit, float(bailout * bailout), visit).map(|(i, _)| i)
+    }
+
+    /// The orbit of `z0` with constant `c`: `z0` itself and the points
+    /// after it, up to and including the first outside `bailout` or else
+    /// `limit` of them. Unlike `escape_orbit`, points that never escape are
+    /// iterated all the way to the limit.
+    pub fn full_orbit(
+        self,
+        power: Power,
+        bailout: f64,
+        z0: Complex64,
+        c: Complex64,
+        limit: u32,
+    ) -> Vec<Complex64> {
+        let mut orbit = vec![z0];
+        let (mut z, mut previous) = (z0, Complex64::new(0.0, 0.0));
+        for _ in 0..limit {
+            (z, previous) = (self.step(power, z, previous, c), z);
+            orbit.push(z);
+            if z.norm_sqr() > bailout * bailout {
+                break;
+            }
+        }
+        orbit
+    }
+
+    /// Like the free function `smooth_escape_time`, but iterating this map
+    /// with the exponent `power`. `bailout` only applies if it is larger
+    /// than `SMOOTH_BAILOUT`.
+    pub fn smooth_escape_time<T: Float>(
+        self,
+        power: Power,
+        bailout: f64,
+        z0: Complex<T>,
+        c: Complex<T>,
+        limit: u32,
+    ) -> Option<f64> {
+        let bailout = bailout.max(SMOOTH_BAILOUT);
+        let bailout_sqr = float(bailout * bailout);
+        let (i, z) = orbit(self, power, z0, c, limit, bailout_sqr, |_| ())?;
+        let value = f64::from(i) + 1.0 - power.log(z.norm().to_f64().unwrap().ln());
+
+        Some(value.max(0.0))
+    }
+
+    /// Like `escape_time`, but return the argument of `z` when the orbit
+    /// escapes, between -π and π, rather than the iteration count.
+    pub fn escape_angle<T: Float>(
+        self,
+        power: Power,
+        bailout: f64,
+        z0: Complex<T>,
+        c: Complex<T>,
+        limit: u32,
+    ) -> Option<f64> {
+        let (_, z) = orbit(self, power, z0, c, limit, float(bailout * bailout), |_| ())?;
+
+        Some(z.arg().to_f64().unwrap())
+    }
+
+    /// The stripe average of the orbit, between 0.0 and 1.0, if it escapes:
+    /// the mean of `(1 + sin(freq · arg z)) / 2` over the orbit, blended
+    /// between its last two values by how far past the bailout the orbit
+    /// went, so that it varies smoothly. `bailout` only applies if it is
+    /// larger than `SMOOTH_BAILOUT`.
+    pub fn stripe_average<T: Float>(
+        self,
+        power: Power,
+        bailout: f64,
+        z0: Complex<T>,
+        c: Complex<T>,
+        limit: u32,
+        freq: f64,
+    ) -> Option<f64> {
+        let bailout = bailout.max(SMOOTH_BAILOUT);
+        let (mut sum, mut last, mut count) = (0.0, 0.0, 0.0);
+        let stripe = |z: Complex<T>| {
+            last = 0.5 + 0.5 * (freq * z.arg().to_f64().unwrap()).sin();
+            sum += last;
+            count += 1.0;
+        };
+        let (_, z) = orbit(self, power, z0, c, limit, float(bailout * bailout), stripe)?;
+
+        let mean = sum / count;
+        let previous = if count > 1.0 {
+            (sum - last) / (count - 1.0)
+        } else {
+            mean
+        };
+        let ratio = z.norm().to_f64().unwrap().ln() / bailout.ln();
+        let frac = (1.0 - power.log(ratio)).clamp(0.0, 1.0);
+
+        Some(previous + (mean - previous) * frac)
+    }
+
+    /// The closest the orbit comes to `trap`, if it escapes: the least
+    /// distance to it of any point of the orbit after `z0`.
+    pub fn orbit_trap<T: Float>(
+        self,
+        power: Power,
+        bailout: f64,
+        z0: Complex<T>,
+        c: Complex<T>,
+        limit: u32,
+        trap: Trap,
+    ) -> Option<f64> {
+        let mut nearest = f64::INFINITY;
+        let visit = |z| nearest = nearest.min(trap.distance(z));
+        orbit(self, power, z0, c, limit, float(bailout * bailout), visit)?;
+
+        Some(nearest)
+    }
+}
+
+/// A shape that `Fractal::orbit_trap` measures how close orbits come to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trap {
+    /// The origin.
+    Point,
+    /// The real axis.
+    Line,
+}
+
+impl Trap {
+    /// Look up a trap by the name used on the command line.
+    pub fn from_name(name: &str) -> Option<Trap> {
+        match name {
+            "point" => Some(Trap::Point),
+            "line" => Some(Trap::Line),
+            _ => None,
+        }
+    }
+
+    /// The distance from `z` to this trap.
+    fn distance<T: Float>(self, z: Complex<T>) -> f64 {
+        let distance = match self {
+            Trap::Point => z.norm(),
+            Trap::Line => z.im.abs(),
+        };
+        distance.to_f64().unwrap()
+    }
+}
+
+/// How an orbit that never escaped behaved, for coloring the interior.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrbitSummary {
+    /// `|z|` after the last iteration.
+    pub last: f64,
+    /// The mean of `|z|` over all iterations.
+    pub mean: f64,
+}
+
+impl Fractal {
+    /// Iterate this map like `escape_time` and summarize the orbit if it
+    /// doesn't escape, or return `None` if it does. Unlike `escape_time`
+    /// this can't skip iterating points known to be inside, so it's only
+    /// worth calling for those.
+    pub fn interior_orbit<T: Float>(
+        self,
+        power: Power,
+        bailout: f64,
+        z0: Complex<T>,
+        c: Complex<T>,
+        limit: u32,
+    ) -> Option<OrbitSummary> {
+        let bailout_sqr = float(bailout * bailout);
+        let epsilon_sqr = float(PERIOD_EPSILON * PERIOD_EPSILON);
+        let zero = Complex::new(T::zero(), T::zero());
+        let (mut z, mut previous, mut reference) = (z0, zero, (z0, zero));
+        let mut sum = 0.0;
+        let mut iterations = 0;
+        for i in 0..limit {
+            (z, previous) = (self.step(power, z, previous, c), z);
+            if z.norm_sqr() > bailout_sqr {
+                return None;
+            }
+            sum += z.norm().to_f64().unwrap();
+            iterations += 1;
+            if self.repeats((z, previous), reference, epsilon_sqr) {
+                break;
+            }
+            if i % PERIOD_CHECK_INTERVAL == 0 {
+                reference = (z, previous);
+            }
+        }
+
+        Some(OrbitSummary {
+            last: z.norm().to_f64().unwrap(),
+            mean: sum / f64::from(iterations.max(1)),
+        })
+    }
+
+    /// Whether an orbit at `z` after `previous` has come back to within
+    /// `epsilon_sqr` of where it was at `reference`, so that it's periodic.
+    /// The next point of the Phoenix depends on both of its last two, and
+    /// those of the others on the last alone.
+    fn repeats<T: Float>(
+        self,
+        (z, previous): (Complex<T>, Complex<T>),
+        reference: (Complex<T>, Complex<T>),
+        epsilon_sqr: T,
+    ) -> bool {
+        let near = |a: Complex<T>, b: Complex<T>| (a - b).norm_sqr() < epsilon_sqr;
+        match self {
+            Fractal::Phoenix(_) => near(z, reference.0) && near(previous, reference.1),
+            _ => near(z, reference.0),
+        }
+    }
+}
+
+/// The exponent `d` of the map `z = z^d + c`. Integer exponents are
+/// computed by repeated multiplication, others through polar form.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Power {
+    Int(u32),
+    Real(f64),
+}
+
+impl Default for Power {
+    fn default() -> Self {
+        Power::Int(2)
+    }
+}
+
+impl Power {
+    /// Parse an exponent as given on the command line. Exponents must be
+    /// greater than one, or no point would ever escape.
+    pub fn parse(s: &str) -> Option<Power> {
+        if let Ok(d) = s.parse::<u32>() {
+            return if d > 1 { Some(Power::Int(d)) } else { None };
+        }
+        match s.parse::<f64>() {
+            Ok(d) if d.is_finite() && d > 1.0 => Some(Power::Real(d)),
+            _ => None,
+        }
+    }
+
+    /// Raise `z` to this power.
+    fn apply<T: Float>(self, z: Complex<T>) -> Complex<T> {
+        match self {
+            Power::Int(2) => z * z,
+            Power::Int(d) => z.powu(d),
+            Power::Real(d) => z.powf(float(d)),
+        }
+    }
+
+    /// The logarithm of `x` to the base of this power.
+    fn log(self, x: f64) -> f64 {
+        match self {
+            Power::Int(2) => x.log2(),
+            Power::Int(d) => x.ln() / f64::from(d).ln(),
+            Power::Real(d) => x.ln() / d.ln(),
+        }
+    }
+}
+
+/// The `p` of `Fractal::Phoenix` unless another is given, which with the
+/// Julia constant 0.5667 gives the best-known Phoenix set.
+pub const PHOENIX_P: Complex64 = Complex64::new(-0.5, 0.0);
+
+/// The radius of the circle an orbit has to leave to escape, unless
+/// configured otherwise. No orbit of the Mandelbrot set that leaves it
+/// ever comes back.
+pub const BAILOUT: f64 = 2.0;
+
+/// Iterate `z = z * z + c` starting from `z0` and return the number of
+/// iterations it takes to leave the circle of radius `BAILOUT`, or `None`
+/// if the orbit stays inside for `limit` iterations.
+pub fn escape_time<T: Float>(z0: Complex<T>, c: Complex<T>, limit: u32) -> Option<u32> {
+    Fractal::Mandelbrot.escape_time(Power::default(), BAILOUT, z0, c, limit)
+}
+
+/// Whether `c` is in the Mandelbrot set, as far as `limit` iterations can
+/// tell: points that escape later than that are counted as inside.
+pub fn is_in_set<T: Float>(c: Complex<T>, limit: u32) -> bool {
+    escape_time(Complex::new(T::zero(), T::zero()), c, limit).is_none()
+}
+
+/// Whether `c` lies in the main cardioid or the period-2 bulb of the
+/// Mandelbrot set. Such points never escape, so they can skip iterating
+/// to the limit.
+pub fn in_main_bulbs<T: Float>(c: Complex<T>) -> bool {
+    let (x, y) = (c.re.to_f64().unwrap(), c.im.to_f64().unwrap());
+    let q = (x - 0.25) * (x - 0.25) + y * y;
+    let cardioid = q * (q + (x - 0.25)) <= 0.25 * y * y;
+    let bulb = (x + 1.0) * (x + 1.0) + y * y <= 1.0 / 16.0;
+
+    cardioid || bulb
+}
+
+/// Bailout radius used by `smooth_escape_time`. The continuous escape value
+/// is only accurate once `|z|` is much larger than two.
+pub const SMOOTH_BAILOUT: f64 = 256.0;
+
+/// Like `escape_time`, but bail out at `SMOOTH_BAILOUT` and return the
+/// continuous escape value `n + 1 - log2(ln |z|)`, which varies smoothly
+/// between neighbouring points instead of jumping at each iteration.
+pub fn smooth_escape_time<T: Float>(z0: Complex<T>, c: Complex<T>, limit: u32) -> Option<f64> {
+    Fractal::Mandelbrot.smooth_escape_time(Power::default(), BAILOUT, z0, c, limit)
+}
+
+/// Estimate the distance from `c` to the Mandelbrot set, from the
+/// derivative `dz` of the orbit with respect to `c`, which follows
+/// `dz = 2 * z * dz + 1`. The estimate is within a factor of four of the
+/// true distance. Returns `None` for points that don't escape within
+/// `limit` iterations.
+pub fn distance_estimate<T: Float>(c: Complex<T>, limit: u32) -> Option<f64> {
+    if in_main_bulbs(c) {
+        return None;
+    }
+    let zero = Complex::new(T::zero(), T::zero());
+    derivative_orbit(zero, zero, c, limit, T::one())
+}
+
+/// Like `distance_estimate`, but for the distance from `z0` to the Julia
+/// set of `c`, with the derivative taken with respect to `z0`.
+pub fn julia_distance_estimate<T: Float>(z0: Complex<T>, c: Complex<T>, limit: u32) -> Option<f64> {
+    let one = Complex::new(T::one(), T::zero());
+    derivative_orbit(z0, one, c, limit, T::zero())
+}
+
+/// Iterate `z = z * z + c` from `z0` along with `dz = 2 * z * dz + step`
+/// from `dz0` until `|z|` exceeds `SMOOTH_BAILOUT`, and return the distance
+/// estimate `2 |z| ln |z| / |dz|`.
+fn derivative_orbit<T: Float>(
+    z0: Complex<T>,
+    dz0: Complex<T>,
+    c: Complex<T>,
+    limit: u32,
+    step: T,
+) -> Option<f64> {
+    let bailout_sqr = float(SMOOTH_BAILOUT * SMOOTH_BAILOUT);
+    let two = float::<T>(2.0);
+    let (mut z, mut dz) = (z0, dz0);
+    for _ in 0..limit {
+        dz = z * dz * two + step;
+        z = z * z + c;
+        if z.norm_sqr() > bailout_sqr {
+            let (r, dr) = (z.norm().to_f64().unwrap(), dz.norm().to_f64().unwrap());
+            return Some(2.0 * r * r.ln() / dr);
+        }
+    }
+
+    None
+}
+
+/// The roots of `z³ - 1`, the cube roots of unity, in the order
+/// `newton_basin` numbers them.
+pub const NEWTON_ROOTS: [(f64, f64); 3] = [
+    (1.0, 0.0),
+    (-0.5, 0.866_025_403_784_438_6),
+    (-0.5, -0.866_025_403_784_438_6),
+];
+
+/// How close Newton's method has to get to a root to have converged.
+pub const NEWTON_TOLERANCE: f64 = 1e-6;
+
+/// Iterate Newton's method for `z³ - 1` from `z0` until it comes within
+/// `NEWTON_TOLERANCE` of a root, and return the index of that root in
+/// `NEWTON_ROOTS` and the iteration at which it got there. Returns `None`
+/// if it doesn't converge within `limit` iterations, as on the boundaries
+/// between basins.
+pub fn newton_basin<T: Float>(z0: Complex<T>, limit: u32) -> Option<(usize, u32)> {
+    let tolerance_sqr = float(NEWTON_TOLERANCE * NEWTON_TOLERANCE);
+    let roots = NEWTON_ROOTS.map(|(re, im)| Complex::new(float::<T>(re), float(im)));
+    let mut z = z0;
+    for i in 0..limit {
+        z = newton_step(z);
+        let root = roots
+            .iter()
+            .position(|&r| (z - r).norm_sqr() < tolerance_sqr);
+        if let Some(root) = root {
+            return Some((root, i));
+        }
+    }
+
+    None
+}
+
+/// One step of Newton's method for `z³ - 1`.
+fn newton_step<T: Float>(z: Complex<T>) -> Complex<T> {
+    let z2 = z * z;
+    z - (z2 * z - T::one()) / (z2 * float::<T>(3.0))
+}
+
+/// How often, in iterations, the orbit's reference point for periodicity
+/// checking is replaced. Cycles up to this length are detected.
+pub const PERIOD_CHECK_INTERVAL: u32 = 32;
+
+/// How close, as a distance, the orbit has to return to the reference point
+/// to be considered periodic.
+pub const PERIOD_EPSILON: f64 = 1e-12;
+
+/// Iterate `fractal` raised to `power` from `z0` until `|z|²` exceeds `bailout_sqr`,
+/// returning the iteration at which that happened and the final `z`.
+/// Returns `None` once `limit` iterations have passed, or as soon as the
+/// orbit is known never to escape. Each `z` of the orbit, including the
+/// one that escaped, is passed to `visit`.
+fn orbit<T: Float>(
+    fractal: Fractal,
+    power: Power,
+    z0: Complex<T>,
+    c: Complex<T>,
+    limit: u32,
+    bailout_sqr: T,
+    mut visit: impl FnMut(Complex<T>),
+) -> Option<(u32, Complex<T>)> {
+    let origin = z0 == Complex::new(T::zero(), T::zero());
+    let mandelbrot = matches!(fractal, Fractal::Mandelbrot | Fractal::Buddhabrot);
+    let quadratic = mandelbrot && power == Power::Int(2);
+    if quadratic && origin && in_main_bulbs(c) {
+        return None;
+    }
+
+    let epsilon_sqr = float(PERIOD_EPSILON * PERIOD_EPSILON);
+    let zero = Complex::new(T::zero(), T::zero());
+    let (mut z, mut previous, mut reference) = (z0, zero, (z0, zero));
+    for i in 0..limit {
+        (z, previous) = (fractal.step(power, z, previous, c), z);
+        visit(z);
+        if z.norm_sqr() > bailout_sqr {
+            return Some((i, z));
+        }
+        if fractal.repeats((z, previous), reference, epsilon_sqr) {
+            return None;
+        }
+        if i % PERIOD_CHECK_INTERVAL == 0 {
+            reference = (z, previous);
+        }
+    }
+
+    None
+}
+
+/// Convert the constant `x` to the float type `T`.
+fn float<T: Float>(x: f64) -> T {
+    T::from(x).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::NEWTON_ROOTS;
+    use super::PHOENIX_P;
+    use super::{distance_estimate, julia_distance_estimate, newton_basin, OrbitSummary, Trap};
+    use super::{escape_time, in_main_bulbs, smooth_escape_time, Fractal, Power, BAILOUT};
+    use crate::cmplx;
+    use num_complex::{Complex32, Complex64};
+
+    #[test]
+    fn smooth_escape_is_continuous() {
+        let c = cmplx!(0.5, 0.5);
+        let count = escape_time(cmplx!(), c, 255).unwrap();
+        let value = smooth_escape_time(cmplx!(), c, 255).unwrap();
+        assert!(value.fract() != 0.0);
+        assert!((value - f64::from(count)).abs() < 4.0);
+
+        // Nearby points get nearby values.
+        let (a, b) = (cmplx!(0.5, 0.5), cmplx!(0.5, 0.5001));
+        let (va, vb) = (
+            smooth_escape_time(cmplx!(), a, 255).unwrap(),
+            smooth_escape_time(cmplx!(), b, 255).unwrap(),
+        );
+        assert!((va - vb).abs() < 0.5);
+        assert_eq!(smooth_escape_time(cmplx!(), cmplx!(), 255), None);
+    }
+
+    #[test]
+    fn main_bulbs_short_circuit() {
+        for c in [
+            cmplx!(),
+            cmplx!(-0.5, 0.3),
+            cmplx!(0.2, 0.0),
+            cmplx!(-1.0, 0.1),
+        ] {
+            assert!(in_main_bulbs(c), "{}", c);
+            // Iterating to this limit would take far too long.
+            assert_eq!(escape_time(cmplx!(), c, u32::MAX), None);
+            assert_eq!(smooth_escape_time(cmplx!(), c, u32::MAX), None);
+        }
+    }
+
+    #[test]
+    fn main_bulbs_leave_exterior_alone() {
+        let naive = |c: Complex64| {
+            let mut z = cmplx!();
+            (0..255).find(|_| {
+                z = z * z + c;
+                z.norm_sqr() > 4.0
+            })
+        };
+
+        for c in [
+            cmplx!(0.3, 0.0),
+            cmplx!(-0.75, 0.1),
+            cmplx!(-1.3, 0.0),
+            cmplx!(0.26, 0.0),
+        ] {
+            assert!(!in_main_bulbs(c), "{}", c);
+            assert_eq!(escape_time(cmplx!(), c, 255), naive(c), "{}", c);
+        }
+        // Orbits that don't start at zero aren't affected.
+        assert!(escape_time(cmplx!(1.5, 0.0), cmplx!(), 255).is_some());
+    }
+
+    #[test]
+    fn periodic_orbits_stop_early() {
+        // Centers of the period-3 and period-4 bulbs, outside the main ones.
+        for c in [
+            cmplx!(-0.1226, 0.7449),
+            cmplx!(-1.3107, 0.0),
+            cmplx!(-0.12, 0.75),
+        ] {
+            assert!(!in_main_bulbs(c), "{}", c);
+            assert_eq!(escape_time(cmplx!(), c, u32::MAX), None, "{}", c);
+        }
+    }
+
+    #[test]
+    fn f32_agrees_with_f64() {
+        // A coarse grid, skipping points close to the boundary of the set.
+        for row in 0..=12 {
+            for col in 0..=16 {
+                let c = cmplx!(-2.0 + col as f64 * 0.1875, 1.2 - row as f64 * 0.2);
+                let c32 = Complex32::new(c.re as f32, c.im as f32);
+                let wide = escape_time(cmplx!(), c, 64);
+                if wide.is_some_and(|count| count > 10) {
+                    continue;
+                }
+                assert_eq!(
+                    escape_time(Complex32::new(0.0, 0.0), c32, 64),
+                    wide,
+                    "{}",
+                    c
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn from_name() {
+        assert_eq!(Fractal::from_name("mandelbrot"), Some(Fractal::Mandelbrot));
+        assert_eq!(
+            Fractal::from_name("burning-ship"),
+            Some(Fractal::BurningShip)
+        );
+        assert_eq!(Fractal::from_name("ship"), None);
+        for fractal in [
+            Fractal::Mandelbrot,
+            Fractal::BurningShip,
+            Fractal::Tricorn,
+            Fractal::Celtic,
+            Fractal::Heart,
+            Fractal::Newton,
+            Fractal::Buddhabrot,
+        ] {
+            assert_eq!(Fractal::from_name(fractal.name()), Some(fractal));
+        }
+    }
+
+    #[test]
+    fn newton_basins() {
+        for (index, &(re, im)) in NEWTON_ROOTS.iter().enumerate() {
+            let root = cmplx!(re, im);
+            assert!((root * root * root - 1.0).norm() < 1e-12);
+            for offset in [cmplx!(0.1, 0.0), cmplx!(0.0, -0.2), cmplx!(-0.15, 0.1)] {
+                let (basin, i) = newton_basin(root + offset, 50).unwrap();
+                assert_eq!(basin, index, "{}", root + offset);
+                assert!(i < 10);
+            }
+            // Far out along a root's direction, points still head for it.
+            assert_eq!(newton_basin(root * 20.0, 50).map(|(b, _)| b), Some(index));
+            assert_eq!(newton_basin(root + 1e-9, 50), Some((index, 0)));
+        }
+        // Zero is a pole of the step, and converges nowhere.
+        assert_eq!(newton_basin(cmplx!(), 50), None);
+        assert_eq!(newton_basin(Complex32::new(0.9, 0.1), 50).unwrap().0, 0);
+    }
+
+    #[test]
+    fn burning_ship_folds_orbit() {
+        // The first step from zero is the same, later ones see |Re z|, |Im z|.
+        let c = cmplx!(-0.5, -0.5);
+        let ship = Fractal::BurningShip;
+        let d = Power::default();
+        let mandel = Fractal::Mandelbrot;
+        assert_eq!(
+            ship.step(d, cmplx!(), cmplx!(), c),
+            mandel.step(d, cmplx!(), cmplx!(), c)
+        );
+        assert_eq!(
+            ship.step(d, cmplx!(-1.0, -2.0), cmplx!(), c),
+            cmplx!(-3.5, 3.5)
+        );
+        // Points in the Mandelbrot cardioid aren't all inside the ship.
+        assert_eq!(escape_time(cmplx!(), cmplx!(0.2, 0.5), 255), None);
+        assert!(ship
+            .escape_time(d, BAILOUT, cmplx!(), cmplx!(0.2, 0.5), 255)
+            .is_some());
+    }
+
+    #[test]
+    fn celtic_and_heart_fold_parts() {
+        let c = cmplx!(-0.5, -0.5);
+        let d = Power::default();
+        let (celtic, heart) = (Fractal::Celtic, Fractal::Heart);
+        // (-1 - 2i)² is -3 + 4i, and (1 - 2i)² is -3 - 4i.
+        assert_eq!(
+            celtic.step(d, cmplx!(-1.0, -2.0), cmplx!(), c),
+            cmplx!(2.5, 3.5)
+        );
+        assert_eq!(
+            heart.step(d, cmplx!(-1.0, -2.0), cmplx!(), c),
+            cmplx!(-3.5, -4.5)
+        );
+        assert_eq!(
+            heart.step(d, cmplx!(1.0, -2.0), cmplx!(), c),
+            cmplx!(-3.5, -4.5)
+        );
+        // Points in the Mandelbrot cardioid aren't all inside either.
+        let deep =
+            |fractal: Fractal| fractal.escape_time(d, BAILOUT, cmplx!(), cmplx!(0.2, 0.5), 255);
+        assert_eq!(deep(Fractal::Mandelbrot), None);
+        assert!(deep(celtic).is_some() && deep(heart).is_some());
+    }
+
+    #[test]
+    fn phoenix_remembers_previous_point() {
+        let (d, c) = (Power::default(), cmplx!(0.5, 0.0));
+        let phoenix = Fractal::Phoenix(cmplx!(-0.5, 0.0));
+        // (1 + i)² + 0.5 - 0.5 · 2i
+        let z = phoenix.step(d, cmplx!(1.0, 1.0), cmplx!(0.0, 2.0), c);
+        assert_eq!(z, cmplx!(0.5, 1.0));
+        // The first step sees a previous point of zero.
+        let orbit = phoenix.full_orbit(d, BAILOUT, cmplx!(1.0, 0.0), c, 2);
+        assert_eq!(
+            orbit,
+            [cmplx!(1.0, 0.0), cmplx!(1.5, 0.0), cmplx!(2.25, 0.0)]
+        );
+        assert_eq!(
+            Fractal::from_name("phoenix"),
+            Some(Fractal::Phoenix(PHOENIX_P))
+        );
+    }
+
+    #[test]
+    fn power_parse() {
+        assert_eq!(Power::parse("3"), Some(Power::Int(3)));
+        assert_eq!(Power::parse("2.5"), Some(Power::Real(2.5)));
+        assert_eq!(Power::parse("1"), None);
+        assert_eq!(Power::parse("0.5"), None);
+        assert_eq!(Power::parse("inf"), None);
+        assert_eq!(Power::parse("two"), None);
+    }
+
+    /// Escape counts of `power` on a coarse grid over `-1.5..=1.5` squared.
+    fn multibrot_grid(power: Power, rotate: Complex64) -> Vec<Option<u32>> {
+        let mandel = Fractal::Mandelbrot;
+        let mut counts = Vec::new();
+        for row in 0..=30 {
+            for col in 0..=30 {
+                let c = cmplx!(col as f64 * 0.1 - 1.5, row as f64 * 0.1 - 1.5) * rotate;
+                counts.push(mandel.escape_time(power, BAILOUT, cmplx!(), c, 100));
+            }
+        }
+        counts
+    }
+
+    #[test]
+    fn multibrot_symmetry() {
+        // Power 3 is symmetric under rotation by a half turn, which negates
+        // every iterate exactly.
+        let cubic = Power::Int(3);
+        assert_eq!(
+            multibrot_grid(cubic, cmplx!(1.0, 0.0)),
+            multibrot_grid(cubic, cmplx!(-1.0, 0.0))
+        );
+
+        // Power 4 is symmetric under a third of a turn; rounding in the
+        // rotation moves a few boundary points.
+        let quartic = Power::Int(4);
+        let third = Complex64::from_polar(1.0, std::f64::consts::TAU / 3.0);
+        let (a, b) = (
+            multibrot_grid(quartic, cmplx!(1.0, 0.0)),
+            multibrot_grid(quartic, third),
+        );
+        let same = a.iter().zip(&b).filter(|(x, y)| x == y).count();
+        assert!(same * 100 >= a.len() * 98, "{} of {}", same, a.len());
+        // But not under a half turn.
+        assert_ne!(a, multibrot_grid(quartic, cmplx!(-1.0, 0.0)));
+    }
+
+    #[test]
+    fn power_two_is_mandelbrot() {
+        let (int, real) = (Power::Int(2), Power::Real(2.0));
+        let mandel = Fractal::Mandelbrot;
+        for c in [cmplx!(0.5, 0.5), cmplx!(-0.75, 0.1), cmplx!(-2.0, 0.0)] {
+            assert_eq!(
+                mandel.escape_time(int, BAILOUT, cmplx!(), c, 255),
+                escape_time(cmplx!(), c, 255)
+            );
+        }
+        // Polar form rounds differently, but not by a whole iteration here.
+        let c = cmplx!(0.5, 0.5);
+        assert_eq!(
+            mandel.escape_time(real, BAILOUT, cmplx!(), c, 255),
+            escape_time(cmplx!(), c, 255)
+        );
+    }
+
+    #[test]
+    fn larger_bailout_escapes_later() {
+        let (mandel, d) = (Fractal::Mandelbrot, Power::default());
+        for c in [
+            cmplx!(-0.75, 0.1),
+            cmplx!(0.26, 0.0),
+            cmplx!(-1.3107, 0.0),
+            cmplx!(0.3, 0.5),
+        ] {
+            let near = mandel.escape_time(d, BAILOUT, cmplx!(), c, 10_000);
+            let far = mandel.escape_time(d, 256.0, cmplx!(), c, 10_000);
+            assert_eq!(near.is_some(), far.is_some(), "{}", c);
+            if let (Some(near), Some(far)) = (near, far) {
+                assert!(far > near, "{}", c);
+            }
+        }
+    }
+
+    #[test]
+    fn largest_bailout_escapes() {
+        // The largest radius whose square is still finite.
+        let bailout = f64::MAX.sqrt();
+        assert!((bailout * bailout).is_finite());
+        let (mandel, d) = (Fractal::Mandelbrot, Power::default());
+        let escaped = mandel.escape_time(d, bailout, cmplx!(), cmplx!(1.0, 0.0), 100);
+        assert!(escaped.is_some_and(|n| n > 5));
+        assert_eq!(
+            mandel.escape_time(d, bailout * 1.01, cmplx!(), cmplx!(1.0, 0.0), 100),
+            None
+        );
+    }
+
+    #[test]
+    fn distance_estimate_is_finite() {
+        for row in 0..=20 {
+            for col in 0..=30 {
+                let c = cmplx!(col as f64 * 0.1 - 2.0, row as f64 * 0.1 - 1.0);
+                if let Some(d) = distance_estimate(c, 1000) {
+                    assert!(d.is_finite() && d > 0.0, "{} at {}", d, c);
+                }
+            }
+        }
+        // 1 is 0.75 from the cusp of the cardioid, its nearest point.
+        let d = distance_estimate(cmplx!(1.0, 0.0), 1000).unwrap();
+        assert!((0.75 / 4.0..=0.75 * 4.0).contains(&d), "{}", d);
+        // Points farther out are farther away.
+        assert!(distance_estimate(cmplx!(2.0, 0.0), 1000).unwrap() > d);
+        assert_eq!(distance_estimate(cmplx!(-0.1, 0.1), 1000), None);
+
+        // The basilica, the Julia set of -1.
+        let c = cmplx!(-1.0, 0.0);
+        let d = julia_distance_estimate(cmplx!(1.5, 0.5), c, 1000).unwrap();
+        assert!(d.is_finite() && d > 0.0);
+        assert_eq!(julia_distance_estimate(cmplx!(), c, 1000), None);
+    }
+
+    #[test]
+    fn interior_orbit() {
+        let (mandel, d) = (Fractal::Mandelbrot, Power::default());
+        // 0 is a fixed point.
+        let zero = mandel.interior_orbit(d, BAILOUT, cmplx!(), cmplx!(), 100);
+        assert_eq!(
+            zero,
+            Some(OrbitSummary {
+                last: 0.0,
+                mean: 0.0
+            })
+        );
+        // -1 cycles -1, 0, -1, where the period check stops it.
+        let cycle = mandel.interior_orbit(d, BAILOUT, cmplx!(), cmplx!(-1.0, 0.0), 100);
+        let cycle = cycle.unwrap();
+        assert_eq!(cycle.last, 1.0);
+        assert!((cycle.mean - 2.0 / 3.0).abs() < 1e-12, "{:?}", cycle);
+        // Escaping points have no summary.
+        let c = cmplx!(1.0, 1.0);
+        assert_eq!(mandel.interior_orbit(d, BAILOUT, cmplx!(), c, 100), None);
+    }
+
+    #[test]
+    fn is_in_set() {
+        assert!(super::is_in_set(cmplx!(), 255));
+        assert!(super::is_in_set(cmplx!(-1.0, 0.0), 255));
+        assert!(!super::is_in_set(cmplx!(2.0, 2.0), 255));
+        assert!(!super::is_in_set(cmplx!(0.26, 0.0), 1000));
+        // Too few iterations to tell.
+        assert!(super::is_in_set(cmplx!(0.26, 0.0), 10));
+    }
+
+    #[test]
+    fn escape_angle() {
+        let (mandel, d) = (Fractal::Mandelbrot, Power::default());
+        let angle = |c| mandel.escape_angle(d, BAILOUT, cmplx!(), c, 100);
+        // 2 escapes to 6 on the second iteration, 3i to itself on the first.
+        assert_eq!(angle(cmplx!(2.0, 0.0)), Some(0.0));
+        assert_eq!(angle(cmplx!(0.0, 3.0)), Some(std::f64::consts::FRAC_PI_2));
+        assert_eq!(angle(cmplx!(-3.0, 0.0)), Some(std::f64::consts::PI));
+        assert_eq!(angle(cmplx!()), None);
+    }
+
+    #[test]
+    fn stripe_average() {
+        let (mandel, d) = (Fractal::Mandelbrot, Power::default());
+        let stripes = |c, freq| mandel.stripe_average(d, BAILOUT, cmplx!(), c, 1000, freq);
+        assert_eq!(stripes(cmplx!(), 5.0), None);
+        // On the positive real axis every arg is 0, so every stripe is 0.5.
+        assert!((stripes(cmplx!(0.5, 0.0), 5.0).unwrap() - 0.5).abs() < 1e-12);
+
+        // Across a line of exterior points it stays in range but varies.
+        let values: Vec<f64> = (0..50)
+            .filter_map(|i| stripes(cmplx!(-0.75 + 0.01 * f64::from(i), 1.1), 5.0))
+            .collect();
+        assert!(values.len() > 20);
+        assert!(values.iter().all(|v| (0.0..=1.0).contains(v)));
+        let (min, max) = values
+            .iter()
+            .fold((1.0f64, 0.0f64), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+        assert!(max - min > 0.2, "{} {}", min, max);
+        // Neighboring points get nearby values.
+        let near = |x: f64| stripes(cmplx!(x, 0.5), 5.0).unwrap();
+        assert!((near(0.4) - near(0.4 + 1e-9)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn orbit_trap() {
+        let (mandel, d) = (Fractal::Mandelbrot, Power::default());
+        let trap = |c, trap| mandel.orbit_trap(d, BAILOUT, cmplx!(), c, 100, trap);
+        // 0.5+0.5i goes to 0.5+i, -0.25+1.5i, -1.6875-0.25i and then out,
+        // with 0 itself left out.
+        let c = cmplx!(0.5, 0.5);
+        assert_eq!(trap(c, Trap::Point), Some(c.norm()));
+        assert_eq!(trap(c, Trap::Line), Some(0.25));
+        assert_eq!(trap(cmplx!(), Trap::Point), None);
+        assert_eq!(Trap::from_name("line"), Some(Trap::Line));
+        assert_eq!(Trap::from_name("circle"), None);
+    }
+}
@@ -0,0 +1,105 @@
+//! Histogram equalization of escape values, so that each part of the
+//! palette covers about the same number of pixels.
+
+/// How many samples of a render escaped at each iteration count.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Histogram {
+    counts: Vec<u64>,
+}
+
+impl Histogram {
+    /// An empty histogram for escape values up to `limit`.
+    pub fn new(limit: u32) -> Histogram {
+        Histogram {
+            counts: vec![0; limit as usize + 1],
+        }
+    }
+
+    /// Count a sample with escape value `value`. Continuous values are
+    /// counted with their whole iteration, and samples inside the set
+    /// aren't counted at all.
+    pub fn record(&mut self, value: Option<f64>) {
+        if let Some(value) = value {
+            let bucket = self.bucket(value);
+            self.counts[bucket] += 1;
+        }
+    }
+
+    /// Combine the histograms of two disjoint sets of samples.
+    pub fn merge(mut self, other: Histogram) -> Histogram {
+        for (count, other) in self.counts.iter_mut().zip(other.counts) {
+            *count += other;
+        }
+        self
+    }
+
+    /// The index of the bucket counting `value`.
+    fn bucket(&self, value: f64) -> usize {
+        (value.max(0.0) as usize).min(self.counts.len() - 1)
+    }
+
+    /// The cumulative distribution of this histogram, for coloring by rank.
+    pub fn equalizer(&self) -> Equalizer {
+        let total = self.counts.iter().sum::<u64>().max(1) as f64;
+        let mut below = Vec::with_capacity(self.counts.len() + 1);
+        let mut sum = 0;
+        below.push(0.0);
+        for &count in &self.counts {
+            sum += count;
+            below.push(sum as f64 / total);
+        }
+
+        Equalizer { below }
+    }
+}
+
+/// Maps escape values to their rank among all escaping samples.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Equalizer {
+    /// `below[k]` is the fraction of samples that escaped before count `k`.
+    below: Vec<f64>,
+}
+
+impl Equalizer {
+    /// The fraction of samples, from 0.0 to 1.0, that escaped before
+    /// `value`. The fractional part of a continuous value interpolates
+    /// across the samples of its own iteration.
+    pub fn equalize(&self, value: f64) -> f64 {
+        let last = self.below.len() - 2;
+        let k = (value.max(0.0) as usize).min(last);
+        let frac = (value - k as f64).clamp(0.0, 1.0);
+
+        self.below[k] + (self.below[k + 1] - self.below[k]) * frac
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Histogram;
+
+    #[test]
+    fn equalize_by_rank() {
+        let mut histogram = Histogram::new(10);
+        for value in [0.0, 0.0, 0.0, 1.0, 5.5, 9.0, 40.0] {
+            histogram.record(Some(value));
+        }
+        histogram.record(None);
+        let other = Histogram::new(10);
+        let equalizer = histogram.merge(other).equalizer();
+
+        assert_eq!(equalizer.equalize(0.0), 0.0);
+        assert_eq!(equalizer.equalize(1.0), 3.0 / 7.0);
+        assert_eq!(equalizer.equalize(5.0), 4.0 / 7.0);
+        assert!((equalizer.equalize(5.5) - 4.5 / 7.0).abs() < 1e-12);
+        assert_eq!(equalizer.equalize(6.0), 5.0 / 7.0);
+        // Values past the limit share the last bucket.
+        assert_eq!(equalizer.equalize(10.0), 6.0 / 7.0);
+        assert!(equalizer.equalize(10.5) < 1.0);
+    }
+
+    #[test]
+    fn empty() {
+        let equalizer = Histogram::new(4).equalizer();
+        assert_eq!(equalizer.equalize(2.0), 0.0);
+    }
+}
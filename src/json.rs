@@ -0,0 +1,284 @@
+//! Just enough JSON to describe a render for other tools, and to read the
+//! description back.
+
+use std::fmt;
+
+/// A JSON value. Object members keep the order they were given in.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    /// A number; one that isn't finite is written as `null`.
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// An object with the members `members`.
+    pub fn object<'a>(members: impl IntoIterator<Item = (&'a str, Json)>) -> Json {
+        Json::Object(
+            members
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    /// The member `key` of an object, or `None` if this isn't an object or
+    /// has no such member.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Json::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Parse the JSON text `s`, returning `None` if it isn't exactly one
+    /// well-formed value.
+    pub fn parse(s: &str) -> Option<Json> {
+        let mut parser = Parser { rest: s };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        parser.rest.is_empty().then_some(value)
+    }
+}
+
+impl From<f64> for Json {
+    fn from(n: f64) -> Json {
+        Json::Number(n)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Json {
+        Json::String(s.to_string())
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Json {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) if n.is_finite() => write!(f, "{}", n),
+            Json::Number(_) => f.write_str("null"),
+            Json::String(s) => write_string(f, s),
+            Json::Array(items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str("]")
+            }
+            Json::Object(members) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ": {}", value)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+/// Write `s` as a quoted JSON string.
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if c < ' ' => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+/// A recursive descent parser over the text not yet consumed.
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start_matches([' ', '\t', '\n', '\r']);
+    }
+
+    /// Consume `token` after any whitespace, if the text starts with it.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        match self.rest.strip_prefix(token) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn value(&mut self) -> Option<Json> {
+        self.skip_whitespace();
+        if self.eat("null") {
+            Some(Json::Null)
+        } else if self.eat("true") {
+            Some(Json::Bool(true))
+        } else if self.eat("false") {
+            Some(Json::Bool(false))
+        } else if self.eat("[") {
+            self.items("]", Parser::value).map(Json::Array)
+        } else if self.eat("{") {
+            self.items("}", Parser::member).map(Json::Object)
+        } else if self.rest.starts_with('"') {
+            self.string().map(Json::String)
+        } else {
+            self.number().map(Json::Number)
+        }
+    }
+
+    /// Comma separated items each parsed by `item`, up to `close`.
+    fn items<T>(&mut self, close: &str, item: fn(&mut Self) -> Option<T>) -> Option<Vec<T>> {
+        let mut items = Vec::new();
+        if self.eat(close) {
+            return Some(items);
+        }
+        loop {
+            items.push(item(self)?);
+            if self.eat(close) {
+                return Some(items);
+            }
+            if !self.eat(",") {
+                return None;
+            }
+        }
+    }
+
+    fn member(&mut self) -> Option<(String, Json)> {
+        self.skip_whitespace();
+        let key = self.string()?;
+        if !self.eat(":") {
+            return None;
+        }
+        Some((key, self.value()?))
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let mut chars = self.rest.strip_prefix('"')?.char_indices();
+        let mut s = String::new();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.rest = &self.rest[1 + i + 1..];
+                    return Some(s);
+                }
+                '\\' => s.push(match chars.next()?.1 {
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'u' => {
+                        let hex: String = (0..4)
+                            .filter_map(|_| chars.next())
+                            .map(|(_, c)| c)
+                            .collect();
+                        char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+                    }
+                    c @ ('"' | '\\' | '/') => c,
+                    _ => return None,
+                }),
+                c if c < ' ' => return None,
+                c => s.push(c),
+            }
+        }
+        None
+    }
+
+    fn number(&mut self) -> Option<f64> {
+        let end = self
+            .rest
+            .find(|c: char| !matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E'))
+            .unwrap_or(self.rest.len());
+        let n = self.rest[..end].parse().ok()?;
+        self.rest = &self.rest[end..];
+        Some(n)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Json;
+
+    #[test]
+    fn round_trip() {
+        let value = Json::object([
+            ("name", Json::from("a \"quoted\"\nline\\")),
+            ("n", Json::from(-1.5e-3)),
+            ("count", Json::from(1234567.0)),
+            ("none", Json::from(None::<f64>)),
+            (
+                "flags",
+                Json::Array(vec![Json::Bool(true), Json::Bool(false)]),
+            ),
+            ("empty", Json::object([])),
+        ]);
+        let text = value.to_string();
+
+        assert!(text.contains("\"count\": 1234567,"), "{}", text);
+        assert_eq!(Json::parse(&text), Some(value.clone()));
+        assert_eq!(value.get("n").and_then(Json::as_f64), Some(-1.5e-3));
+        assert_eq!(Json::from(f64::INFINITY).to_string(), "null");
+    }
+
+    #[test]
+    fn parse() {
+        let value = Json::parse(" { \"a\" : [1, 2.5e1, \"\\u00e9\\/\"] } ").unwrap();
+        let items = Json::Array(vec![Json::from(1.0), Json::from(25.0), Json::from("é/")]);
+        assert_eq!(value.get("a"), Some(&items));
+
+        for bad in [
+            "",
+            "[1, 2",
+            "{\"a\" 1}",
+            "[1,]",
+            "1 2",
+            "\"open",
+            "nul",
+            "1.2.3",
+        ] {
+            assert_eq!(Json::parse(bad), None, "{}", bad);
+        }
+    }
+}
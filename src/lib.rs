@@ -0,0 +1,3587 @@
+//! Render the Mandelbrot set to grayscale or color images.
+
+pub mod buddhabrot;
+pub mod checkpoint;
+#[cfg(feature = "deep-zoom")]
+pub mod deep;
+pub mod dither;
+pub mod downsample;
+pub mod escape;
+pub mod histogram;
+pub mod json;
+pub mod output;
+pub mod overlay;
+pub mod palette;
+pub mod precise;
+pub mod preset;
+pub mod progress;
+pub mod random;
+pub mod region;
+pub mod sample;
+pub mod simd;
+pub mod stats;
+pub mod webp;
+
+#[cfg(not(feature = "rayon"))]
+use std::cell::Cell;
+use std::convert::Infallible;
+use std::f64::consts::TAU;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::Mutex;
+#[cfg(feature = "rayon")]
+use std::sync::{Arc, PoisonError};
+use std::time::{Duration, Instant};
+
+pub use buddhabrot::{buddhabrot_hits, render_buddhabrot};
+pub use checkpoint::{read_checkpoint, write_checkpoint};
+#[cfg(not(feature = "rayon"))]
+use crossbeam::thread::Scope;
+#[cfg(feature = "deep-zoom")]
+pub use deep::{render_deep, render_deep_with_progress, DeepView, DoubleDouble};
+pub use dither::{Dither, Ditherer};
+pub use downsample::{downsample, Filter};
+pub use escape::{
+    distance_estimate, escape_time, in_main_bulbs, is_in_set, julia_distance_estimate,
+};
+pub use escape::{newton_basin, smooth_escape_time, Fractal, OrbitSummary, Power, Trap};
+pub use escape::{BAILOUT, NEWTON_ROOTS, PHOENIX_P};
+pub use histogram::{Equalizer, Histogram};
+use image::ColorType;
+pub use json::Json;
+pub use num_complex::{Complex32, Complex64};
+pub use output::WriteOptions;
+pub use output::{
+    encode_image, write_image, write_image_with, write_raw, Format, GifFrames, TiffCompression,
+};
+pub use output::{write_exr, PngStrips, STDOUT};
+pub use overlay::{draw_contours, draw_grid, draw_orbit, draw_scale_bar, scale_bar_length};
+pub use palette::{Colorizer, Palette};
+pub use precise::{render_precise, render_precise_with_progress, Fixed, PreciseRegion};
+pub use preset::Preset;
+pub use progress::{BandTime, Progress};
+use random::SplitMix;
+pub use region::Region;
+pub use sample::Sample;
+pub use stats::Stats;
+
+/// Build a `Complex64`: `cmplx!()` is zero, `cmplx!(x)` is `x + xi`
+/// and `cmplx!(re, im)` is `re + im·i`.
+#[macro_export]
+macro_rules! cmplx {
+    () => {
+        $crate::Complex64::new(0.0, 0.0)
+    };
+
+    ($re: expr) => {
+        $crate::Complex64::new($re, $re)
+    };
+
+    ($re: expr, $im: expr) => {
+        $crate::Complex64::new($re, $im)
+    };
+}
+
+/// Iteration limit used when none is given explicitly.
+pub const DEFAULT_LIMIT: u32 = 255;
+
+/// How much each iteration Newton's method takes to converge darkens the
+/// color of its root.
+pub const NEWTON_FADE: f64 = 0.93;
+
+/// Stripes around the origin for stripe average coloring when no number
+/// is given explicitly.
+pub const DEFAULT_STRIPE_FREQ: f64 = 5.0;
+
+/// How far apart, as a fraction of the full range of a channel, the colors
+/// at a pixel's corners have to be for adaptive supersampling to count it
+/// as an edge.
+pub const ADAPTIVE_CONTRAST: f64 = 0.05;
+
+/// Points sampled per pixel for the Buddhabrot when no number is given.
+/// More samples make for a smoother image, in proportionally more time.
+pub const DEFAULT_SAMPLES: u32 = 20;
+
+/// The largest `Params::supersample`, which already samples each pixel at
+/// 65536 points; larger values are treated as this.
+pub const MAX_SUPERSAMPLE: u32 = 256;
+
+/// The most samples a pixel is rendered with: red, green, blue and alpha.
+const MAX_CHANNELS: usize = 4;
+
+/// The number of render threads to use when none is given: one per
+/// available core, or a single thread if that can't be determined.
+pub fn default_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// The float type the orbit of each point is computed in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Precision {
+    /// Faster, and precise enough for shallow zooms.
+    F32,
+    F64,
+}
+
+impl Precision {
+    /// Look up a precision by the name used on the command line.
+    pub fn from_name(name: &str) -> Option<Precision> {
+        match name {
+            "f32" => Some(Precision::F32),
+            "f64" => Some(Precision::F64),
+            _ => None,
+        }
+    }
+}
+
+/// How escape values are mapped to shades or palette positions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorMode {
+    /// In proportion to the escape value.
+    Linear,
+    /// By rank among all escape values of the image, which takes a second
+    /// pass over the image.
+    Histogram,
+    /// By the estimated distance to the set, relative to the pixel size,
+    /// which draws thin filaments crisply. The escape values reported in
+    /// `Stats` are then the distances. Distances are always estimated for
+    /// the quadratic map, whatever `fractal` and `power` say.
+    Distance,
+    /// By the argument of `z` when the orbit escapes, as a hue, which
+    /// always produces RGB output. The escape values reported in `Stats`
+    /// are then the angles.
+    Angle,
+    /// By the stripe average of the orbit, with `stripe_freq` stripes
+    /// around the origin, which brings out detail along the filaments. The
+    /// escape values reported in `Stats` are then the averages.
+    Stripes,
+    /// By how close the orbit comes to `trap`, brighter the closer. The
+    /// escape values reported in `Stats` are then the levels it's colored
+    /// at, from 0.0 to 1.0.
+    OrbitTrap,
+}
+
+impl ColorMode {
+    /// Look up a color mode by the name used on the command line.
+    pub fn from_name(name: &str) -> Option<ColorMode> {
+        match name {
+            "linear" => Some(ColorMode::Linear),
+            "histogram" => Some(ColorMode::Histogram),
+            "distance" => Some(ColorMode::Distance),
+            "angle" => Some(ColorMode::Angle),
+            "stripes" => Some(ColorMode::Stripes),
+            "orbit-trap" => Some(ColorMode::OrbitTrap),
+            _ => None,
+        }
+    }
+}
+
+/// How points inside the set are colored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InteriorColor {
+    /// Solid black.
+    Black,
+    /// By `|z|` at the end of the orbit.
+    Last,
+    /// By the mean of `|z|` over the orbit.
+    Mean,
+}
+
+impl InteriorColor {
+    /// Look up an interior coloring by the name used on the command line.
+    pub fn from_name(name: &str) -> Option<InteriorColor> {
+        match name {
+            "black" => Some(InteriorColor::Black),
+            "last" => Some(InteriorColor::Last),
+            "mean" => Some(InteriorColor::Mean),
+            _ => None,
+        }
+    }
+}
+
+/// How escape values are scaled before `ColorMode::Linear` maps them to
+/// shades or palette positions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scale {
+    /// In proportion to the escape value.
+    Linear,
+    /// By `log(1 + value)`, normalized so that the limit maps to itself,
+    /// which spreads out the small escape values most of an image has.
+    Log,
+    /// By the square root of the value, a gentler stretch than `Log`.
+    Sqrt,
+}
+
+impl Scale {
+    /// Look up a scale by the name used on the command line.
+    pub fn from_name(name: &str) -> Option<Scale> {
+        match name {
+            "linear" => Some(Scale::Linear),
+            "log" => Some(Scale::Log),
+            "sqrt" => Some(Scale::Sqrt),
+            _ => None,
+        }
+    }
+
+    /// The escape value `value`, between 0.0 and `limit`, rescaled to the
+    /// same range.
+    pub fn apply(self, value: f64, limit: f64) -> f64 {
+        let value = value.max(0.0);
+        match self {
+            Scale::Linear => value,
+            Scale::Log => limit * value.ln_1p() / limit.ln_1p(),
+            Scale::Sqrt => (value * limit).sqrt(),
+        }
+    }
+}
+
+/// How `Params::fill` turns an escape value into a color.
+#[derive(Clone, Copy)]
+enum Shading<'a> {
+    /// In proportion to the value.
+    Linear,
+    /// By the value's rank.
+    Ranked(&'a Equalizer),
+    /// By a distance estimate, relative to this pixel width.
+    Distance(f64),
+    /// By the value as an angle, mapped to a hue.
+    Angle,
+    /// By the value as a position from 0.0 to 1.0.
+    Level,
+}
+
+/// How each point of the image is iterated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Params {
+    /// Number of iterations after which a point is considered inside the set.
+    pub limit: u32,
+    /// Render the Julia set for this constant instead of the Mandelbrot set.
+    pub julia: Option<Complex64>,
+    /// Where the orbit of each point starts when not rendering a Julia set.
+    /// Distance estimates ignore it and always start at zero.
+    pub z0: Complex64,
+    /// Shade with the continuous escape value instead of the iteration count.
+    pub smooth: bool,
+    /// Color with this palette, producing RGB output. Without a palette the
+    /// image is inverted grayscale with one byte per pixel.
+    pub palette: Option<Palette>,
+    /// The float type orbits are computed in.
+    pub precision: Precision,
+    /// The map iterated for each point.
+    pub fractal: Fractal,
+    /// The exponent the map raises `z` to.
+    pub power: Power,
+    /// The radius an orbit has to leave to escape.
+    pub bailout: f64,
+    /// Sample each pixel on a grid of this many points per axis and average
+    /// the resulting colors. 1 samples only the pixel's corner, and at most
+    /// `MAX_SUPERSAMPLE` are taken.
+    pub supersample: u32,
+    /// Sample each pixel at `samples` points placed at random within it
+    /// instead, drawn from `seed`, so that fine filaments don't come out
+    /// in the moiré of a regular grid.
+    pub jitter: bool,
+    /// How escape values are mapped to colors.
+    pub color_mode: ColorMode,
+    /// How escape values are scaled under `ColorMode::Linear`.
+    pub scale: Scale,
+    /// Under `ColorMode::Linear`, run through the grays or the palette
+    /// every this many iterations rather than once up to the limit, in
+    /// place of `scale`.
+    pub cycle_period: Option<f64>,
+    /// How far along its period the cycle starts, in iterations. Stepping
+    /// it from frame to frame cycles the colors.
+    pub cycle_offset: f64,
+    /// How points inside the set are colored.
+    pub interior: InteriorColor,
+    /// The number of stripes around the origin for `ColorMode::Stripes`.
+    pub stripe_freq: f64,
+    /// The shape orbits are measured against for `ColorMode::OrbitTrap`.
+    pub trap: Trap,
+    /// Each channel `v`, from 0.0 to 1.0, is written as `v^(1/gamma)`, so
+    /// values above 1.0 brighten the midtones.
+    pub gamma: f64,
+    /// Iterate several points at once with SIMD instructions, where the
+    /// CPU has them. Only plain escape counts of the quadratic Mandelbrot
+    /// set in double precision can be; other renders ignore this.
+    pub simd: bool,
+    /// The number of points sampled per pixel for `Fractal::Buddhabrot`,
+    /// or under `jitter`.
+    pub samples: u32,
+    /// Seeds the random points of `jitter` and `Fractal::Buddhabrot`.
+    pub seed: u64,
+    /// Only supersample pixels on an edge, where the colors at the pixel's
+    /// corners differ by more than `ADAPTIVE_CONTRAST`, and color the rest
+    /// by one sample. `render_colorized` supersamples every pixel anyway,
+    /// and `jitter` ignores this.
+    pub adaptive: bool,
+    /// Average the samples of supersampled pixels in linear light rather
+    /// than as their sRGB-encoded values, so that edges between light and
+    /// dark don't come out too dark. `None` does so for RGB output only,
+    /// leaving grays as plain averages.
+    pub linear_averaging: Option<bool>,
+    /// Swap black and white in grayscale, making the inside of the set
+    /// white, and run the palette the other way. Hues are left as they are.
+    pub invert: bool,
+    /// Render RGBA, with the inside of the set transparent and the rest
+    /// opaque. Grays are written as RGB.
+    pub alpha_interior: bool,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            limit: DEFAULT_LIMIT,
+            julia: None,
+            z0: cmplx!(),
+            smooth: false,
+            palette: None,
+            precision: Precision::F64,
+            fractal: Fractal::Mandelbrot,
+            power: Power::default(),
+            bailout: BAILOUT,
+            supersample: 1,
+            jitter: false,
+            color_mode: ColorMode::Linear,
+            scale: Scale::Linear,
+            cycle_period: None,
+            cycle_offset: 0.0,
+            interior: InteriorColor::Black,
+            stripe_freq: DEFAULT_STRIPE_FREQ,
+            trap: Trap::Point,
+            gamma: 1.0,
+            simd: false,
+            samples: DEFAULT_SAMPLES,
+            seed: 0,
+            adaptive: false,
+            linear_averaging: None,
+            invert: false,
+            alpha_interior: false,
+        }
+    }
+}
+
+impl Params {
+    /// Escape time of the pixel at `point`: for the Mandelbrot set `point`
+    /// is `c` and the orbit starts at `z0`, for a Julia set the orbit starts
+    /// at `point`.
+    pub fn escape(&self, point: Complex64) -> Option<u32> {
+        if self.fractal == Fractal::Newton {
+            return self.newton(point).map(|(_, i)| i);
+        }
+        let (z0, c) = self.start(point);
+        let (fractal, d, r) = (self.fractal, self.power, self.bailout);
+        match self.precision {
+            Precision::F32 => fractal.escape_time(d, r, to_f32(z0), to_f32(c), self.limit),
+            Precision::F64 => fractal.escape_time(d, r, z0, c, self.limit),
+        }
+    }
+
+    /// Like `escape`, but return the continuous escape value.
+    pub fn smooth_escape(&self, point: Complex64) -> Option<f64> {
+        let (z0, c) = self.start(point);
+        let (fractal, d, r) = (self.fractal, self.power, self.bailout);
+        match self.precision {
+            Precision::F32 => fractal.smooth_escape_time(d, r, to_f32(z0), to_f32(c), self.limit),
+            Precision::F64 => fractal.smooth_escape_time(d, r, z0, c, self.limit),
+        }
+    }
+
+    /// The root Newton's method converges to from `point` and the iteration
+    /// at which it got there, as in `newton_basin`.
+    pub fn newton(&self, point: Complex64) -> Option<(usize, u32)> {
+        match self.precision {
+            Precision::F32 => newton_basin(to_f32(point), self.limit),
+            Precision::F64 => newton_basin(point, self.limit),
+        }
+    }
+
+    /// The starting point and constant of the orbit for the pixel at `point`.
+    fn start(&self, point: Complex64) -> (Complex64, Complex64) {
+        match self.julia {
+            Some(c) => (point, c),
+            None => (self.z0, point),
+        }
+    }
+
+    /// The orbit of the pixel at `point`: where its iteration starts and
+    /// each point after it, until it escapes or reaches the limit.
+    pub fn orbit(&self, point: Complex64) -> Vec<Complex64> {
+        let (z0, c) = match self.fractal {
+            Fractal::Newton => (point, point),
+            _ => self.start(point),
+        };
+        let (power, bailout) = (self.power, self.bailout);
+        self.fractal.full_orbit(power, bailout, z0, c, self.limit)
+    }
+
+    /// How an image of `region` was rendered with these parameters, as
+    /// keywords and texts for `WriteOptions::text`. Corners are written so
+    /// that `parse_complex` reads them back exactly.
+    pub fn metadata(&self, region: &Region) -> Vec<(String, String)> {
+        let (bounds, (upper_l, lower_r)) = (region.bounds, region.corners());
+        let corner = |z: Complex64| format!("{:?},{:?}", z.re, z.im);
+        let palette = self.palette.map_or("none", Palette::name);
+        [
+            (
+                "Software",
+                format!("mandelbrot {}", env!("CARGO_PKG_VERSION")),
+            ),
+            ("Bounds", format!("{}x{}", bounds.0, bounds.1)),
+            ("UpperLeft", corner(upper_l)),
+            ("LowerRight", corner(lower_r)),
+            ("Iterations", self.limit.to_string()),
+            ("Fractal", self.fractal.name().to_string()),
+            ("Palette", palette.to_string()),
+        ]
+        .into_iter()
+        .chain(region.flip_y.then(|| ("FlipY", "true".to_string())))
+        .map(|(keyword, text)| (keyword.to_string(), text))
+        .collect()
+    }
+
+    /// A description of the render of `region` with these parameters, which
+    /// took `elapsed` and came out with the escape statistics `stats`.
+    pub fn metadata_json(&self, region: &Region, stats: &Stats, elapsed: Duration) -> Json {
+        let point = |z: Complex64| Json::object([("re", z.re.into()), ("im", z.im.into())]);
+        let (upper_l, lower_r) = region.corners();
+        let escaped = stats.escaped() > 0;
+        let stats = Json::object([
+            ("pixels", (stats.pixels as f64).into()),
+            ("interior", (stats.interior as f64).into()),
+            ("escaped", (stats.escaped() as f64).into()),
+            ("min", escaped.then_some(stats.min).into()),
+            ("max", escaped.then_some(stats.max).into()),
+            ("mean", stats.mean().into()),
+        ]);
+        Json::object([
+            (
+                "software",
+                concat!("mandelbrot ", env!("CARGO_PKG_VERSION")).into(),
+            ),
+            ("width", (region.bounds.0 as f64).into()),
+            ("height", (region.bounds.1 as f64).into()),
+            ("upper_left", point(upper_l)),
+            ("lower_right", point(lower_r)),
+            ("flip_y", Json::Bool(region.flip_y)),
+            ("iterations", f64::from(self.limit).into()),
+            ("fractal", self.fractal.name().into()),
+            ("palette", self.palette.map(Palette::name).into()),
+            ("elapsed_seconds", elapsed.as_secs_f64().into()),
+            ("stats", stats),
+        ])
+    }
+
+    /// Number of bytes per pixel in the rendered buffer.
+    pub fn channels(&self) -> usize {
+        self.color_channels() + usize::from(self.alpha_interior)
+    }
+
+    /// Color type of the rendered buffer, for passing to `write_image`.
+    pub fn color_type(&self) -> ColorType {
+        match (self.is_rgb(), self.alpha_interior) {
+            (false, _) => ColorType::L8,
+            (true, false) => ColorType::Rgb8,
+            (true, true) => ColorType::Rgba8,
+        }
+    }
+
+    /// Whether rendering produces RGB rather than grayscale pixels.
+    fn is_rgb(&self) -> bool {
+        self.palette.is_some()
+            || self.color_mode == ColorMode::Angle
+            || self.fractal == Fractal::Newton
+            || self.alpha_interior
+    }
+
+    /// Whether supersamples are averaged in linear light, as
+    /// `linear_averaging` says.
+    fn averages_linear(&self) -> bool {
+        self.linear_averaging.unwrap_or(self.is_rgb())
+    }
+
+    /// Number of samples per pixel holding its color, before any alpha.
+    fn color_channels(&self) -> usize {
+        match self.is_rgb() {
+            false => 1,
+            true => 3,
+        }
+    }
+
+    /// Write the alpha of a pixel, transparent if it's `inside` the set,
+    /// into `out` if it has an alpha channel, and return its color channels.
+    fn split_alpha<'a, P: Sample>(&self, inside: bool, out: &'a mut [P]) -> &'a mut [P] {
+        let (color, alpha) = out.split_at_mut(self.color_channels());
+        match inside {
+            false => alpha.fill(P::from_u64(P::MAX)),
+            true => alpha.fill(P::default()),
+        }
+        color
+    }
+
+    /// The escape value of the pixel at `point`: the iteration count, or the
+    /// continuous escape value when shading smoothly.
+    pub fn escape_value(&self, point: Complex64) -> Option<f64> {
+        if self.fractal == Fractal::Newton {
+            self.escape(point).map(f64::from)
+        } else if self.color_mode == ColorMode::Distance {
+            self.distance(point)
+        } else if self.color_mode == ColorMode::Angle {
+            self.angle(point)
+        } else if self.color_mode == ColorMode::Stripes {
+            self.stripes(point)
+        } else if self.color_mode == ColorMode::OrbitTrap {
+            self.orbit_trap(point)
+        } else if self.smooth {
+            self.smooth_escape(point)
+        } else {
+            self.escape(point).map(f64::from)
+        }
+    }
+
+    /// The estimated distance from the pixel at `point` to the Mandelbrot
+    /// set, or to the Julia set if `julia` is given.
+    pub fn distance(&self, point: Complex64) -> Option<f64> {
+        let limit = self.limit;
+        match (self.julia, self.precision) {
+            (None, Precision::F32) => distance_estimate(to_f32(point), limit),
+            (None, Precision::F64) => distance_estimate(point, limit),
+            (Some(c), Precision::F32) => julia_distance_estimate(to_f32(point), to_f32(c), limit),
+            (Some(c), Precision::F64) => julia_distance_estimate(point, c, limit),
+        }
+    }
+
+    /// The argument of `z` when the orbit of the pixel at `point` escapes.
+    pub fn angle(&self, point: Complex64) -> Option<f64> {
+        let (z0, c) = self.start(point);
+        let (fractal, d, r) = (self.fractal, self.power, self.bailout);
+        match self.precision {
+            Precision::F32 => fractal.escape_angle(d, r, to_f32(z0), to_f32(c), self.limit),
+            Precision::F64 => fractal.escape_angle(d, r, z0, c, self.limit),
+        }
+    }
+
+    /// The stripe average of the orbit of the pixel at `point`.
+    pub fn stripes(&self, point: Complex64) -> Option<f64> {
+        let (z0, c) = self.start(point);
+        let (fractal, d, r, freq) = (self.fractal, self.power, self.bailout, self.stripe_freq);
+        match self.precision {
+            Precision::F32 => fractal.stripe_average(d, r, to_f32(z0), to_f32(c), self.limit, freq),
+            Precision::F64 => fractal.stripe_average(d, r, z0, c, self.limit, freq),
+        }
+    }
+
+    /// Where the orbit of the pixel at `point` falls between 0.0 and 1.0 by
+    /// how close it comes to `trap`: 1.0 for orbits through it, falling off
+    /// as the square root of the distance to none at `bailout`.
+    pub fn orbit_trap(&self, point: Complex64) -> Option<f64> {
+        let (z0, c) = self.start(point);
+        let (fractal, d, r, trap) = (self.fractal, self.power, self.bailout, self.trap);
+        let distance = match self.precision {
+            Precision::F32 => fractal.orbit_trap(d, r, to_f32(z0), to_f32(c), self.limit, trap),
+            Precision::F64 => fractal.orbit_trap(d, r, z0, c, self.limit, trap),
+        }?;
+
+        Some(1.0 - (distance / r).sqrt().min(1.0))
+    }
+
+    /// Where the pixel at `point`, which is inside the set, falls between
+    /// 0.0 and 1.0 under `interior` coloring, or `None` to leave it black.
+    pub fn interior_level(&self, point: Complex64) -> Option<f64> {
+        let (z0, c) = self.start(point);
+        let (fractal, d, r) = (self.fractal, self.power, self.bailout);
+        let summary = match self.interior {
+            InteriorColor::Black => return None,
+            _ => match self.precision {
+                Precision::F32 => fractal.interior_orbit(d, r, to_f32(z0), to_f32(c), self.limit),
+                Precision::F64 => fractal.interior_orbit(d, r, z0, c, self.limit),
+            },
+        }?;
+        let magnitude = match self.interior {
+            InteriorColor::Last => summary.last,
+            _ => summary.mean,
+        };
+
+        Some((magnitude / r).clamp(0.0, 1.0))
+    }
+
+    /// Whether these parameters ask for plain escape counts of the quadratic
+    /// Mandelbrot set in linear shading, the only renders that views too
+    /// deep for `f64` can be rendered with.
+    pub fn plain_escape_counts(&self) -> bool {
+        self.fractal == Fractal::Mandelbrot
+            && self.power == Power::Int(2)
+            && self.julia.is_none()
+            && self.z0 == cmplx!()
+            && !self.smooth
+            && self.color_mode == ColorMode::Linear
+            && self.interior == InteriorColor::Black
+            && self.sample_count() <= 1
+            && self.bailout == BAILOUT
+    }
+
+    /// Whether rendering iterates points `simd::LANES` at a time.
+    fn uses_simd(&self) -> bool {
+        let counts = matches!(self.color_mode, ColorMode::Linear | ColorMode::Histogram);
+        let quadratic = self.fractal == Fractal::Mandelbrot && self.power == Power::Int(2);
+        self.simd
+            && counts
+            && quadratic
+            && self.julia.is_none()
+            && self.z0 == cmplx!()
+            && self.precision == Precision::F64
+            && self.bailout == BAILOUT
+            && !self.smooth
+            && self.sample_count() <= 1
+    }
+
+    /// Whether rendering with these parameters comes out bit for bit the
+    /// same on every platform and build: whether each point is iterated
+    /// one at a time in `f64`, without SIMD, and colored by basic IEEE
+    /// arithmetic alone. Rust never fuses multiplies and adds unless told
+    /// to, so that path is the same sequence of operations everywhere;
+    /// what isn't is the platform's `ln`, `pow` and trigonometry, whose
+    /// last bits differ between math libraries, so smooth shading, the
+    /// color modes built on them, logarithmic scaling, fractional powers,
+    /// interior coloring, gamma, Newton's fading and averaging supersamples
+    /// in linear light don't qualify.
+    ///
+    /// The price is speed: plain escape counts give up iterating several
+    /// points at once under `simd`.
+    pub fn is_reproducible(&self) -> bool {
+        let counts = matches!(self.color_mode, ColorMode::Linear | ColorMode::Histogram);
+        !self.uses_simd()
+            && self.precision == Precision::F64
+            && !self.smooth
+            && counts
+            && self.scale != Scale::Log
+            && !matches!(self.power, Power::Real(_))
+            && self.interior == InteriorColor::Black
+            && self.gamma == 1.0
+            && self.fractal != Fractal::Newton
+            && (self.sample_count() <= 1 || !self.averages_linear())
+    }
+
+    /// The number of passes over the image rendering takes.
+    pub fn passes(&self) -> usize {
+        match self.color_mode {
+            ColorMode::Linear
+            | ColorMode::Distance
+            | ColorMode::Angle
+            | ColorMode::Stripes
+            | ColorMode::OrbitTrap => 1,
+            ColorMode::Histogram => 2,
+        }
+    }
+
+    /// Write the color of escape value `value` into `out`, as `shading`
+    /// says. Ranks and distances are shaded smoothly on the scale of
+    /// escape values, so that points near the set come out dark.
+    fn fill<P: Sample>(&self, value: Option<f64>, shading: Shading, out: &mut [P]) {
+        debug_assert!(value.is_some() || self.interior == InteriorColor::Black);
+        let out = self.split_alpha(value.is_none(), out);
+        let limit = f64::from(self.limit);
+        let smooth = Params {
+            smooth: true,
+            ..*self
+        };
+        let (params, value) = match shading {
+            Shading::Angle => {
+                let color = value.map_or([0.0; 3], |angle| palette::hue(angle / TAU));
+                out.copy_from_slice(&color.map(P::from_8bit));
+                return;
+            }
+            Shading::Level => {
+                match (value, self.palette) {
+                    (Some(level), _) => self.fill_level(level, out),
+                    (None, None) => out.fill(self.gray(0)),
+                    (None, Some(_)) => out.fill(P::default()),
+                }
+                return;
+            }
+            Shading::Linear => match (self.cycle_period, self.scale) {
+                (Some(period), _) => {
+                    let cycle = |v: f64| (v + self.cycle_offset).rem_euclid(period) / period;
+                    (smooth, value.map(|v| cycle(v) * limit))
+                }
+                (None, Scale::Linear) => (*self, value),
+                (None, scale) => (smooth, value.map(|v| scale.apply(v, limit))),
+            },
+            Shading::Ranked(equalizer) => (smooth, value.map(|v| equalizer.equalize(v) * limit)),
+            Shading::Distance(pixel) => {
+                let near = |d: f64| limit * (1.0 - (d / pixel).clamp(0.0, 1.0).powf(0.25));
+                (smooth, value.map(near))
+            }
+        };
+        match params.palette {
+            None => out.fill(params.shade(value)),
+            Some(palette) => out.copy_from_slice(&params.color(value, palette)),
+        }
+    }
+
+    /// Write the color at position `level` from 0.0 to 1.0, in gray or
+    /// along the palette, into `out`.
+    fn fill_level<P: Sample>(&self, level: f64, out: &mut [P]) {
+        match self.palette {
+            None => out.fill(self.gray((level * P::MAX as f64).round() as u64)),
+            Some(palette) => {
+                let color = palette.level(self.palette_level(level));
+                out.copy_from_slice(&color.map(P::from_8bit))
+            }
+        }
+    }
+
+    /// The gray `value`, at most `P::MAX`, or its opposite under `invert`.
+    fn gray<P: Sample>(&self, value: u64) -> P {
+        match self.invert {
+            false => P::from_u64(value),
+            true => P::from_u64(P::MAX - value),
+        }
+    }
+
+    /// Where `level` falls along the palette, which `invert` reverses.
+    fn palette_level(&self, level: f64) -> f64 {
+        match self.invert {
+            false => level,
+            true => 1.0 - level,
+        }
+    }
+
+    /// Write the color of the sample at `point` with escape value `value`
+    /// into `out`, as `shading` says for escaping points and `interior`
+    /// says for the others.
+    fn fill_sample<P: Sample>(
+        &self,
+        point: Complex64,
+        value: Option<f64>,
+        shading: Shading,
+        out: &mut [P],
+    ) {
+        match value.map_or_else(|| self.interior_level(point), |_| None) {
+            Some(level) => self.fill_level(level, self.split_alpha(true, out)),
+            None => self.fill(value, shading, out),
+        }
+        self.apply_gamma(out);
+    }
+
+    /// Apply `gamma` to each channel of `out`.
+    fn apply_gamma<P: Sample>(&self, out: &mut [P]) {
+        if self.gamma != 1.0 {
+            let max = P::MAX as f64;
+            for channel in out {
+                let v = (channel.to_f64() / max).powf(self.gamma.recip());
+                *channel = P::from_u64((v * max).round() as u64);
+            }
+        }
+    }
+
+    /// Write the color of the sample at `point` into `out`, as `shading`
+    /// says, and return its escape value. Newton's method colors each
+    /// point by its root instead, in red, green or blue, darker the longer
+    /// it takes to converge, and returns the iterations that took.
+    fn paint_sample<P: Sample>(
+        &self,
+        point: Complex64,
+        shading: Shading,
+        out: &mut [P],
+    ) -> Option<f64> {
+        if self.fractal != Fractal::Newton {
+            let value = self.escape_value(point);
+            self.fill_sample(point, value, shading, out);
+            return value;
+        }
+
+        let basin = self.newton(point);
+        let color = match basin {
+            Some((root, i)) => {
+                let fade = NEWTON_FADE.powf(f64::from(i));
+                palette::hue(root as f64 / 3.0).map(|channel| channel * fade)
+            }
+            None => [0.0; 3],
+        };
+        let color_out = self.split_alpha(basin.is_none(), out);
+        color_out.copy_from_slice(&color.map(P::from_8bit));
+        self.apply_gamma(out);
+        basin.map(|(_, i)| f64::from(i))
+    }
+
+    /// The number of points each pixel is sampled at.
+    fn sample_count(&self) -> u32 {
+        match self.jitter {
+            true => self.samples.max(1),
+            false => self.grid_size().pow(2),
+        }
+    }
+
+    /// The number of points along each axis of the supersampling grid.
+    fn grid_size(&self) -> u32 {
+        self.supersample.clamp(1, MAX_SUPERSAMPLE)
+    }
+
+    /// Whether only pixels on edges are supersampled.
+    fn adaptive_grid(&self) -> bool {
+        self.adaptive && !self.jitter && self.supersample > 1
+    }
+
+    /// The `sample_count()` points spread over the pixel at `point`, whose
+    /// extent on the plane is `pixel_size`: `supersample²` on a grid
+    /// starting with `point` itself, or under `jitter` at random, from a
+    /// generator seeded by `seed` and `point` so that each pixel gets the
+    /// same ones however the image is split up.
+    fn samples(&self, point: Complex64, pixel_size: Complex64) -> impl Iterator<Item = Complex64> {
+        let (n, jitter) = (self.grid_size(), self.jitter);
+        let mut random =
+            SplitMix(self.seed ^ point.re.to_bits().rotate_left(32) ^ point.im.to_bits());
+        (0..self.sample_count()).map(move |i| {
+            let (fx, fy) = match jitter {
+                true => (random.unit(), random.unit()),
+                false => (
+                    f64::from(i % n) / f64::from(n),
+                    f64::from(i / n) / f64::from(n),
+                ),
+            };
+            point + cmplx!(pixel_size.re * fx, pixel_size.im * fy)
+        })
+    }
+
+    /// Write the pixel at `point`, whose extent on the plane is
+    /// `pixel_size`, into `out`, which holds `channels()` samples, and return
+    /// its escape value. With supersampling this averages the colors of the
+    /// `supersample²` points spread over the pixel and returns the mean
+    /// escape value of the samples that escaped. Histogram coloring ranks
+    /// values by `equalizer`, which it requires.
+    pub fn paint_supersampled<P: Sample>(
+        &self,
+        point: Complex64,
+        pixel_size: Complex64,
+        equalizer: Option<&Equalizer>,
+        out: &mut [P],
+    ) -> Option<f64> {
+        let shading = self.shading(pixel_size, equalizer);
+        if self.sample_count() <= 1 {
+            return self.paint_sample(point, shading, out);
+        }
+
+        // Only the color channels are light; alpha is averaged as it is.
+        let max = P::MAX as f64;
+        let linear = match self.averages_linear() {
+            true => self.color_channels(),
+            false => 0,
+        };
+        let mut sum = [0.0; MAX_CHANNELS];
+        let mut sample = [P::default(); MAX_CHANNELS];
+        let mut escaped = Stats::default();
+        for point in self.samples(point, pixel_size) {
+            let sample = &mut sample[..out.len()];
+            escaped.record(self.paint_sample(point, shading, sample));
+            for (i, (total, &channel)) in sum.iter_mut().zip(sample.iter()).enumerate() {
+                *total += match i < linear {
+                    true => palette::to_linear(channel.to_f64() / max),
+                    false => channel.to_f64(),
+                };
+            }
+        }
+        let count = f64::from(self.sample_count());
+        for (i, (channel, total)) in out.iter_mut().zip(sum).enumerate() {
+            let mean = match i < linear {
+                true => palette::from_linear(total / count) * max,
+                false => total / count,
+            };
+            *channel = P::from_u64(mean.round() as u64);
+        }
+
+        escaped.mean()
+    }
+
+    /// How escape values of pixels of size `pixel_size` are turned into
+    /// colors. Histogram coloring ranks values by `equalizer`, which it
+    /// requires.
+    fn shading<'a>(&self, pixel_size: Complex64, equalizer: Option<&'a Equalizer>) -> Shading<'a> {
+        match (self.color_mode, equalizer) {
+            (ColorMode::Distance, _) => Shading::Distance(pixel_size.re.abs()),
+            (ColorMode::Histogram, Some(equalizer)) => Shading::Ranked(equalizer),
+            (ColorMode::Histogram, None) => panic!("histogram coloring without a histogram"),
+            (ColorMode::Angle, _) => Shading::Angle,
+            (ColorMode::Stripes | ColorMode::OrbitTrap, _) => Shading::Level,
+            (ColorMode::Linear, _) => Shading::Linear,
+        }
+    }
+
+    /// The color for escape value `value` under `palette`. Points inside the
+    /// set are black, under `invert` too.
+    pub fn color<P: Sample>(&self, value: Option<f64>, palette: Palette) -> [P; 3] {
+        match value {
+            None => [P::default(); 3],
+            Some(value) => palette
+                .level(self.palette_level(value / f64::from(self.limit)))
+                .map(P::from_8bit),
+        }
+    }
+
+    /// The grayscale value for escape value `value`. For `u8` this is
+    /// `brightness` or `smooth_brightness`, other depths scale the same way.
+    /// Under `invert` it's the opposite gray.
+    pub fn shade<P: Sample>(&self, value: Option<f64>) -> P {
+        let (max, limit) = (P::MAX, u64::from(self.limit));
+        match value {
+            None => self.gray(0),
+            Some(value) if self.smooth => {
+                let scaled = (value * max as f64 / limit as f64).clamp(0.0, max as f64);
+                self.gray(max - scaled.round() as u64)
+            }
+            Some(count) => self.gray(max - (count as u64).min(limit) * max / limit),
+        }
+    }
+}
+
+/// Round `z` to single precision.
+fn to_f32(z: Complex64) -> Complex32 {
+    Complex32::new(z.re as f32, z.im as f32)
+}
+
+/// Why `parse_pair` or `parse_complex` rejected a string.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// The separator between the two halves is missing.
+    MissingSeparator,
+    /// The left half, or the real part of a complex number, is invalid.
+    InvalidLeft,
+    /// The right half, or the imaginary part of a complex number, is invalid.
+    InvalidRight,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::MissingSeparator => write!(f, "missing separator"),
+            ParseError::InvalidLeft => write!(f, "invalid left half"),
+            ParseError::InvalidRight => write!(f, "invalid right half"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parse the string `s` as a coordinate pair like `"800x600"` or `"1.0, 0.5"`.
+/// Whitespace around either half is ignored.
+pub fn parse_pair<T: FromStr>(s: &str, separator: char) -> Result<(T, T), ParseError> {
+    let idx = s.find(separator).ok_or(ParseError::MissingSeparator)?;
+    let left = T::from_str(s[..idx].trim()).map_err(|_| ParseError::InvalidLeft)?;
+    let right = T::from_str(s[idx + 1..].trim()).map_err(|_| ParseError::InvalidRight)?;
+
+    Ok((left, right))
+}
+
+/// Parse image dimensions like `"800x600"`, or a single number like
+/// `"800"` for a square image.
+pub fn parse_dimensions(s: &str) -> Result<(usize, usize), ParseError> {
+    match parse_pair(s, 'x') {
+        Err(ParseError::MissingSeparator) => {
+            let side = usize::from_str(s.trim()).map_err(|_| ParseError::InvalidLeft)?;
+            Ok((side, side))
+        }
+        pair => pair,
+    }
+}
+
+/// Parse a complex number, either as a pair of floating-point numbers
+/// seperated by a comma like `"-1.20,0.35"`, or written as `a+bi` like
+/// `"-1.20+0.35i"`, `"0.5-i"`, `"3i"` or `"2"`. Whitespace around the
+/// number, or around either half of a pair, is ignored.
+pub fn parse_complex(s: &str) -> Result<Complex64, ParseError> {
+    let s = s.trim();
+    if s.contains(',') {
+        return parse_pair::<f64>(s, ',').map(|(re, im)| cmplx!(re, im));
+    }
+
+    let body = match s.strip_suffix('i') {
+        Some(body) => body,
+        None => {
+            let re = f64::from_str(s).map_err(|_| ParseError::InvalidLeft)?;
+            return Ok(cmplx!(re, 0.0));
+        }
+    };
+
+    // The imaginary part starts at the last sign that isn't part of an
+    // exponent; without one the number is purely imaginary.
+    let is_split = |&(idx, ch): &(usize, char)| {
+        (ch == '+' || ch == '-') && idx > 0 && !body[..idx].ends_with(['e', 'E'])
+    };
+    let split = body.char_indices().rev().find(is_split).map(|(idx, _)| idx);
+    let (re, im) = match split {
+        Some(idx) => {
+            let re = f64::from_str(&body[..idx]).map_err(|_| ParseError::InvalidLeft)?;
+            (re, &body[idx..])
+        }
+        None => (0.0, body),
+    };
+    let im = match im {
+        "" | "+" => 1.0,
+        "-" => -1.0,
+        _ => f64::from_str(im).map_err(|_| ParseError::InvalidRight)?,
+    };
+
+    Ok(cmplx!(re, im))
+}
+
+/// Parse a complex number in polar form like `"0.7885,1.2"`: its modulus
+/// and then its argument in radians, or in degrees if it ends in `deg` or
+/// `°` like `"0.7885,45deg"`. Whitespace around either half is ignored.
+pub fn parse_polar(s: &str) -> Result<Complex64, ParseError> {
+    let (r, theta) = s.split_once(',').ok_or(ParseError::MissingSeparator)?;
+    let r = f64::from_str(r.trim()).map_err(|_| ParseError::InvalidLeft)?;
+    let theta = theta.trim();
+    let degrees = theta.strip_suffix("deg").or(theta.strip_suffix('°'));
+    let theta = match degrees {
+        Some(degrees) => f64::from_str(degrees.trim()).map(f64::to_radians),
+        None => f64::from_str(theta),
+    };
+
+    Ok(Complex64::from_polar(
+        r,
+        theta.map_err(|_| ParseError::InvalidRight)?,
+    ))
+}
+
+/// Split a pair of corners like `"-2,-1.5,1,1.5"` at the second comma,
+/// into one `"re,im"` string for each corner.
+pub fn split_corners(s: &str) -> Result<(&str, &str), ParseError> {
+    let (idx, _) = s
+        .match_indices(',')
+        .nth(1)
+        .ok_or(ParseError::MissingSeparator)?;
+    Ok((&s[..idx], &s[idx + 1..]))
+}
+
+/// Parse a pair of corners like `"-2,-1.5,1,1.5"`, four finite numbers
+/// separated by commas: the real and imaginary parts of one corner, then
+/// of the other. The left half is the first corner. Whitespace around
+/// any of the numbers is ignored.
+pub fn parse_corners(s: &str) -> Result<(Complex64, Complex64), ParseError> {
+    let (first, second) = split_corners(s)?;
+    let corner = |s: &str, err: ParseError| {
+        let (re, im): (f64, f64) = parse_pair(s, ',').map_err(|_| err)?;
+        match re.is_finite() && im.is_finite() {
+            true => Ok(cmplx!(re, im)),
+            false => Err(err),
+        }
+    };
+
+    Ok((
+        corner(first, ParseError::InvalidLeft)?,
+        corner(second, ParseError::InvalidRight)?,
+    ))
+}
+
+/// Given the row and column of a pixel in the output image,
+/// return the corresponding point on the complex plane.
+pub fn pixel_to_point(
+    bounds: (usize, usize),
+    pixel: (usize, usize),
+    upper_l: Complex64,
+    lower_r: Complex64,
+) -> Complex64 {
+    let (w, h) = (lower_r.re - upper_l.re, upper_l.im - lower_r.im);
+    let re = upper_l.re + (((pixel.0 as f64) * w) / bounds.0 as f64);
+    let im = upper_l.im - (((pixel.1 as f64) * h) / bounds.1 as f64);
+
+    cmplx!(re, im)
+}
+
+/// Return the upper left and lower right corners of a view of the complex
+/// plane centered on `center`, for an image of size `bounds`. `zoom` is
+/// half the width of the view along the real axis; the height follows from
+/// the aspect ratio of `bounds`.
+pub fn corners_from_center(
+    center: Complex64,
+    zoom: f64,
+    bounds: (usize, usize),
+) -> (Complex64, Complex64) {
+    let half_height = zoom * bounds.1 as f64 / bounds.0 as f64;
+    let upper_l = cmplx!(center.re - zoom, center.im + half_height);
+    let lower_r = cmplx!(center.re + zoom, center.im - half_height);
+
+    (upper_l, lower_r)
+}
+
+/// Put the corners of the view between `upper_l` and `lower_r` in order,
+/// swapping the real or imaginary parts of the two if they're reversed.
+/// Also returns whether anything was swapped. The render functions happily
+/// take reversed corners and flip the image, so this is for callers that
+/// want to catch them.
+pub fn order_corners(upper_l: Complex64, lower_r: Complex64) -> ((Complex64, Complex64), bool) {
+    let (left, right) = (upper_l.re.min(lower_r.re), upper_l.re.max(lower_r.re));
+    let (top, bottom) = (upper_l.im.max(lower_r.im), upper_l.im.min(lower_r.im));
+    let swapped = upper_l.re > lower_r.re || upper_l.im < lower_r.im;
+
+    ((cmplx!(left, top), cmplx!(right, bottom)), swapped)
+}
+
+/// The zoom of each of `frames` frames going from `start` to `end`, for
+/// `corners_from_center`. The zoom changes by the same factor from each
+/// frame to the next, so the view shrinks at a steady speed.
+pub fn zoom_sequence(start: f64, end: f64, frames: usize) -> Vec<f64> {
+    if frames <= 1 {
+        return vec![start; frames];
+    }
+
+    let ratio = end / start;
+    let mut zooms: Vec<f64> = (0..frames)
+        .map(|i| start * ratio.powf(i as f64 / (frames - 1) as f64))
+        .collect();
+    zooms[frames - 1] = end;
+    zooms
+}
+
+/// Adjust the imaginary extent of the view between `upper_l` and `lower_r`
+/// so that a pixel covers the same distance along both axes in an image of
+/// size `bounds`. The real extent and the center of the view are kept.
+pub fn preserve_aspect(
+    bounds: (usize, usize),
+    upper_l: Complex64,
+    lower_r: Complex64,
+) -> (Complex64, Complex64) {
+    let center = (upper_l + lower_r) / 2.0;
+    let zoom = (lower_r.re - upper_l.re) / 2.0;
+
+    corners_from_center(center, zoom, bounds)
+}
+
+/// Map an escape count to a grayscale value, scaling `count` into the
+/// 0–255 range according to `limit`, rather than truncating it to a byte.
+/// With the default limit of 255 this is simply `255 - count`. Counts
+/// beyond the limit are as dark as the limit itself.
+pub fn brightness(count: u32, limit: u32) -> u8 {
+    let count = u64::from(count.min(limit));
+    255 - (count * 255 / u64::from(limit)) as u8
+}
+
+/// Map a continuous escape value to a grayscale value, the counterpart of
+/// `brightness` for smooth shading.
+pub fn smooth_brightness(value: f64, limit: u32) -> u8 {
+    let scaled = (value * 255.0 / f64::from(limit)).clamp(0.0, 255.0);
+    255 - scaled.round() as u8
+}
+
+/// Render the image of `region` into `pixels`, which holds
+/// `params.channels()` samples per pixel, and return statistics about the
+/// escape values.
+pub fn render<P: Sample>(pixels: &mut [P], region: &Region, params: &Params) -> Stats {
+    if params.fractal == Fractal::Buddhabrot {
+        return render_buddhabrot(pixels, region, params, 1, None);
+    }
+    let bounds = region.bounds;
+    assert!(pixels.len() == bounds.0 * bounds.1 * params.channels());
+    let equalizer = equalizer(params, || {
+        tally_rows(region, 0..bounds.0, 0..bounds.1, params, None)
+    });
+    render_rows(
+        pixels,
+        region,
+        0..bounds.0,
+        0,
+        params,
+        equalizer.as_ref(),
+        None,
+    )
+}
+
+/// The equalizer histogram coloring ranks escape values with, made from
+/// the histogram `tally` counts, or `None` under the other color modes,
+/// which don't need a first pass.
+fn equalizer(params: &Params, tally: impl FnOnce() -> Histogram) -> Option<Equalizer> {
+    match params.color_mode {
+        ColorMode::Linear
+        | ColorMode::Distance
+        | ColorMode::Angle
+        | ColorMode::Stripes
+        | ColorMode::OrbitTrap => None,
+        ColorMode::Histogram => Some(tally().equalizer()),
+    }
+}
+
+/// Count the escape values of every sample in columns `cols` of `rows` of
+/// the image of `region` in a histogram, the first pass of histogram
+/// coloring. Each finished row is counted in `progress`, if given.
+fn tally_rows(
+    region: &Region,
+    cols: Range<usize>,
+    rows: Range<usize>,
+    params: &Params,
+    progress: Option<&Progress>,
+) -> Histogram {
+    let (start, top, band) = (Instant::now(), rows.start, rows.len());
+    let size = region.pixel_size();
+    // Adaptive supersampling colors most pixels by their first sample.
+    let samples = match params.adaptive_grid() {
+        true => 1,
+        false => params.sample_count() as usize,
+    };
+    let mut histogram = Histogram::new(params.limit);
+    for row in rows {
+        if progress.is_some_and(Progress::is_cancelled) {
+            break;
+        }
+        for col in cols.clone() {
+            let point = region.pixel_to_point((col, row));
+            for sample in params.samples(point, size).take(samples) {
+                histogram.record(params.escape_value(sample));
+            }
+        }
+        if let Some(progress) = progress {
+            progress.add_rows(1);
+        }
+    }
+    if let Some(progress) = progress {
+        progress.add_band(worker_index(), top, band, start.elapsed());
+    }
+
+    histogram
+}
+
+/// Render columns `cols` of the rows of the image held in `pixels`, the
+/// first of which is row `top` of the image of `region`. Coordinates are
+/// always computed from the full region, so any split into rows or columns
+/// gives identical results.
+/// Pixels are colored by rank in `equalizer` if given, and each finished
+/// row is counted in `progress`, if given. Once `progress` is cancelled the
+/// remaining rows are left as they are.
+fn render_rows<P: Sample>(
+    pixels: &mut [P],
+    region: &Region,
+    cols: Range<usize>,
+    top: usize,
+    params: &Params,
+    equalizer: Option<&Equalizer>,
+    progress: Option<&Progress>,
+) -> Stats {
+    let start = Instant::now();
+    let channels = params.channels();
+    let size = region.pixel_size();
+    let row_len = cols.len() * channels;
+    let mut stats = Stats::default();
+    let mut corners = Vec::new();
+    for (i, line) in pixels.chunks_mut(row_len.max(1)).enumerate() {
+        if progress.is_some_and(Progress::is_cancelled) {
+            break;
+        }
+        if params.adaptive_grid() {
+            let row = top + i;
+            let cols = cols.clone();
+            let line_stats =
+                render_row_adaptive(line, region, cols, row, params, equalizer, &mut corners);
+            stats = stats.merge(line_stats);
+        } else if params.uses_simd() {
+            let line_stats = render_row_simd(line, region, cols.start, top + i, params, equalizer);
+            stats = stats.merge(line_stats);
+        } else {
+            for (col, pixel) in cols.clone().zip(line.chunks_mut(channels)) {
+                let point = region.pixel_to_point((col, top + i));
+                stats.record(params.paint_supersampled(point, size, equalizer, pixel));
+            }
+        }
+        if let Some(progress) = progress {
+            progress.add_rows(1);
+        }
+    }
+    if let Some(progress) = progress {
+        let rows = pixels.len() / row_len.max(1);
+        progress.add_band(worker_index(), top, rows, start.elapsed());
+    }
+
+    stats
+}
+
+/// The color and escape value of a sample at a corner of a pixel.
+type Corner<P> = ([P; MAX_CHANNELS], Option<f64>);
+
+/// Render the pixels of `line`, columns `cols` of row `row` of the image
+/// of `region`, like `render_rows`, supersampling only the pixels on an
+/// edge. `corners` holds the samples at the corners of those columns along
+/// the last rows of corners sampled, by their row in the unflipped image,
+/// and is updated to those of this row so that the next can share them.
+fn render_row_adaptive<P: Sample>(
+    line: &mut [P],
+    region: &Region,
+    cols: Range<usize>,
+    row: usize,
+    params: &Params,
+    equalizer: Option<&Equalizer>,
+    corners: &mut Vec<(usize, Vec<Corner<P>>)>,
+) -> Stats {
+    let (channels, size) = (params.channels(), region.pixel_size());
+    let shading = params.shading(size, equalizer);
+    // The corners along the top of the pixel's area on the plane, where
+    // its point is, and along the bottom.
+    let k = if region.flip_y {
+        region.bounds.1 - 1 - row
+    } else {
+        row
+    };
+    for k in [k, k + 1] {
+        if !corners.iter().any(|&(other, _)| other == k) {
+            corners.push((k, corner_samples(region, cols.clone(), k, params, shading)));
+        }
+    }
+    corners.retain(|&(other, _)| other == k || other == k + 1);
+    let along = |k: usize| &corners.iter().find(|&&(other, _)| other == k).unwrap().1;
+    let (top, bottom) = (along(k), along(k + 1));
+
+    let mut stats = Stats::default();
+    for ((i, col), pixel) in cols.enumerate().zip(line.chunks_mut(channels)) {
+        let samples = [&top[i], &top[i + 1], &bottom[i], &bottom[i + 1]];
+        if is_edge(samples, channels) {
+            let point = region.pixel_to_point((col, row));
+            stats.record(params.paint_supersampled(point, size, equalizer, pixel));
+        } else {
+            let (color, value) = top[i];
+            pixel.copy_from_slice(&color[..channels]);
+            stats.record(value);
+        }
+    }
+
+    stats
+}
+
+/// The samples at the corners of the pixels in columns `cols` along row
+/// `k` of corners of the unflipped image of `region`, the top of its row
+/// `k`, from left to right.
+fn corner_samples<P: Sample>(
+    region: &Region,
+    cols: Range<usize>,
+    k: usize,
+    params: &Params,
+    shading: Shading,
+) -> Vec<Corner<P>> {
+    let (u_l, l_r) = region.corners();
+    let samples = (cols.start..=cols.end).map(|col| {
+        let point = pixel_to_point(region.bounds, (col, k), u_l, l_r);
+        let mut color = [P::default(); MAX_CHANNELS];
+        let value = params.paint_sample(point, shading, &mut color[..params.channels()]);
+        (color, value)
+    });
+    samples.collect()
+}
+
+/// Whether the pixel with the corner samples `samples` is on an edge: if
+/// any of the first `channels` channels of their colors differ by more
+/// than `ADAPTIVE_CONTRAST`.
+fn is_edge<P: Sample>(samples: [&Corner<P>; 4], channels: usize) -> bool {
+    let contrast = ADAPTIVE_CONTRAST * P::MAX as f64;
+    (0..channels).any(|channel| {
+        let values = samples.map(|(color, _)| color[channel].to_f64());
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        max - min > contrast
+    })
+}
+
+/// Render the pixels of `line`, starting at column `left` of row `row` of
+/// the image of `region`, like `render_rows`, with the escape counts of
+/// `simd::LANES` pixels at a time computed by `simd::escape_times`.
+fn render_row_simd<P: Sample>(
+    line: &mut [P],
+    region: &Region,
+    left: usize,
+    row: usize,
+    params: &Params,
+    equalizer: Option<&Equalizer>,
+) -> Stats {
+    let channels = params.channels();
+    let shading = params.shading(region.pixel_size(), equalizer);
+    let mut stats = Stats::default();
+    for (i, pixels) in line.chunks_mut(channels * simd::LANES).enumerate() {
+        let col = |lane| left + i * simd::LANES + lane;
+        let points = std::array::from_fn(|lane| region.pixel_to_point((col(lane), row)));
+        let counts = simd::escape_times(points, params.limit);
+        for ((pixel, point), count) in pixels.chunks_mut(channels).zip(points).zip(counts) {
+            let value = count.map(f64::from);
+            params.fill_sample(point, value, shading, pixel);
+            stats.record(value);
+        }
+    }
+
+    stats
+}
+
+/// Render the image on `threads` threads like `render`. `threads` is
+/// clamped to between one and the number of rows.
+pub fn render_parallel<P: Sample>(
+    pixels: &mut [P],
+    region: &Region,
+    params: &Params,
+    threads: usize,
+) -> Stats {
+    render_parallel_inner(pixels, region, params, threads, None, None)
+}
+
+/// Like `render_parallel`, counting finished rows in `progress` so another
+/// thread can report on the render while it runs. Rows are counted once
+/// for each of `params.passes()`. If `progress` is cancelled, the rows not
+/// yet started are left as they are in `pixels`.
+pub fn render_parallel_with_progress<P: Sample>(
+    pixels: &mut [P],
+    region: &Region,
+    params: &Params,
+    threads: usize,
+    progress: &Progress,
+) -> Stats {
+    render_parallel_inner(pixels, region, params, threads, Some(progress), None)
+}
+
+/// Like `render_parallel`, calling `on_row` with the index and samples of
+/// each row as soon as it's painted, so that the image can be sent on
+/// while the rest of it renders. Rows are finished on whichever thread
+/// renders them, so they come in no particular order, but `on_row` is only
+/// ever called for one at a time. Buddhabrot images are only painted once
+/// every orbit is traced, after which their rows come all at once.
+pub fn render_parallel_streaming<P: Sample, F>(
+    pixels: &mut [P],
+    region: &Region,
+    params: &Params,
+    threads: usize,
+    on_row: F,
+) -> Stats
+where
+    F: FnMut(usize, &[P]) + Send,
+{
+    let on_row = Mutex::new(on_row);
+    render_parallel_inner(pixels, region, params, threads, None, Some(&on_row))
+}
+
+/// The function `render_parallel_streaming` hands each finished row to,
+/// shared between the render threads.
+type RowSink<'a, P> = Mutex<dyn FnMut(usize, &[P]) + Send + 'a>;
+
+/// Render the image on `threads` threads in one or two passes, handing
+/// each row to `on_row`, if given, once it's painted.
+fn render_parallel_inner<P: Sample>(
+    pixels: &mut [P],
+    region: &Region,
+    params: &Params,
+    threads: usize,
+    progress: Option<&Progress>,
+    on_row: Option<&RowSink<'_, P>>,
+) -> Stats {
+    let row_len = region.bounds.0 * params.channels();
+    let report = |band: &[P], top: usize| {
+        if let Some(on_row) = on_row {
+            let mut on_row = on_row.lock().unwrap();
+            for (i, row) in band.chunks(row_len.max(1)).enumerate() {
+                on_row(top + i, row);
+            }
+        }
+    };
+    if params.fractal == Fractal::Buddhabrot {
+        let stats = render_buddhabrot(pixels, region, params, threads, progress);
+        report(pixels, 0);
+        return stats;
+    }
+    let bounds = region.bounds;
+    assert!(pixels.len() == bounds.0 * bounds.1 * params.channels());
+
+    let equalizer = equalizer(params, || {
+        let tally = |rows| tally_rows(region, 0..bounds.0, rows, params, progress);
+        let empty = || Histogram::new(params.limit);
+        on_rows(0..bounds.1, threads, tally, empty, Histogram::merge)
+    });
+    let equalizer = equalizer.as_ref();
+
+    let paint = |band: &mut [P], top: usize| match on_row {
+        None => render_rows(band, region, 0..bounds.0, top, params, equalizer, progress),
+        // With one thread the band is the whole image, so it's painted a
+        // row at a time to hand each on as it's done.
+        Some(_) => {
+            let mut stats = Stats::default();
+            for (i, row) in band.chunks_mut(row_len.max(1)).enumerate() {
+                let painted = render_rows(
+                    row,
+                    region,
+                    0..bounds.0,
+                    top + i,
+                    params,
+                    equalizer,
+                    progress,
+                );
+                report(row, top + i);
+                stats = stats.merge(painted);
+            }
+            stats
+        }
+    };
+    in_rows(
+        pixels,
+        row_len,
+        threads,
+        paint,
+        Stats::default,
+        Stats::merge,
+    )
+}
+
+/// A rectangle of columns `cols` of rows `rows` of an image, rendered by
+/// `render_tiled` into a buffer of its own.
+struct Tile<P> {
+    /// Where the tile comes in the image, counting row by row.
+    index: usize,
+    cols: Range<usize>,
+    rows: Range<usize>,
+    pixels: Vec<P>,
+    /// The statistics of the tile's pixels, once they're all rendered.
+    stats: Option<Stats>,
+}
+
+impl<P: Sample> Tile<P> {
+    /// The tiles of `size` pixels square covering an image of size
+    /// `bounds`, row by row, those along the right and bottom edges cut
+    /// short to fit. Their buffers start out empty.
+    fn split(bounds: (usize, usize), size: usize) -> Vec<Tile<P>> {
+        let mut tiles = Vec::new();
+        for top in (0..bounds.1).step_by(size) {
+            for left in (0..bounds.0).step_by(size) {
+                tiles.push(Tile {
+                    index: tiles.len(),
+                    cols: left..(left + size).min(bounds.0),
+                    rows: top..(top + size).min(bounds.1),
+                    pixels: Vec::new(),
+                    stats: None,
+                });
+            }
+        }
+        tiles
+    }
+
+    /// The rows of this tile in `image`, whose rows are `width` pixels of
+    /// `channels` samples each.
+    fn lines<'a>(
+        &'a self,
+        image: &'a mut [P],
+        width: usize,
+        channels: usize,
+    ) -> impl Iterator<Item = &'a mut [P]> {
+        let cols = self.cols.start * channels..self.cols.end * channels;
+        let image = &mut image[self.rows.start * width * channels..];
+        let lines = image.chunks_mut(width * channels).take(self.rows.len());
+        lines.map(move |line| &mut line[cols.clone()])
+    }
+}
+
+/// The number of rows `render_tiled_with_progress` counts in each pass
+/// over an image of size `bounds` in tiles of `tile_size` pixels square:
+/// every row once for each column of tiles.
+pub fn tiled_rows(bounds: (usize, usize), tile_size: usize) -> usize {
+    bounds.1 * bounds.0.div_ceil(tile_size.max(1))
+}
+
+/// Render the image on `threads` threads like `render_parallel`, but in
+/// square tiles of `tile_size` pixels rather than whole rows, which keeps
+/// each thread's work close together on the plane. The output is the same.
+pub fn render_tiled<P: Sample>(
+    pixels: &mut [P],
+    region: &Region,
+    params: &Params,
+    threads: usize,
+    tile_size: usize,
+) -> Stats {
+    render_tiled_inner(pixels, region, params, threads, tile_size, None)
+}
+
+/// Like `render_tiled`, counting the finished rows of each tile in
+/// `progress` for each of `params.passes()`, `tiled_rows` in all per pass.
+/// If `progress` is cancelled, the rows not yet started are left as they
+/// are in `pixels`.
+pub fn render_tiled_with_progress<P: Sample>(
+    pixels: &mut [P],
+    region: &Region,
+    params: &Params,
+    threads: usize,
+    tile_size: usize,
+    progress: &Progress,
+) -> Stats {
+    render_tiled_inner(pixels, region, params, threads, tile_size, Some(progress))
+}
+
+/// Render the image in tiles on `threads` threads in one or two passes.
+/// The Buddhabrot has no tiles, and is rendered as by `render_parallel`.
+fn render_tiled_inner<P: Sample>(
+    pixels: &mut [P],
+    region: &Region,
+    params: &Params,
+    threads: usize,
+    tile_size: usize,
+    progress: Option<&Progress>,
+) -> Stats {
+    if params.fractal == Fractal::Buddhabrot {
+        return render_buddhabrot(pixels, region, params, threads, progress);
+    }
+    let mut state = TiledState::new(region.bounds, tile_size);
+    let save = |_: &[P], _: &TiledState| Ok::<(), Infallible>(());
+    match render_tiles(
+        pixels,
+        region,
+        params,
+        threads,
+        &mut state,
+        progress,
+        usize::MAX,
+        save,
+    ) {
+        Ok(stats) => stats,
+        Err(never) => match never {},
+    }
+}
+
+/// How many tiles per thread `render_tiled_resumable` renders between
+/// calls of its `save`.
+const TILES_PER_SAVE: usize = 8;
+
+/// Which tiles of a render in tiles are finished, in the order the image
+/// is split into them, row by row, and the escape statistics of their
+/// pixels, for `render_tiled_resumable` to pick up from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TiledState {
+    /// The side of the tiles, in pixels.
+    pub tile_size: usize,
+    pub done: Vec<bool>,
+    pub stats: Stats,
+}
+
+impl TiledState {
+    /// A render of an image of size `bounds` in tiles of `tile_size`
+    /// pixels square, none of them finished.
+    pub fn new(bounds: (usize, usize), tile_size: usize) -> TiledState {
+        let tile_size = tile_size.max(1);
+        let tiles = bounds.0.div_ceil(tile_size) * bounds.1.div_ceil(tile_size);
+        TiledState {
+            tile_size,
+            done: vec![false; tiles],
+            stats: Stats::default(),
+        }
+    }
+
+    /// Whether every tile is finished.
+    pub fn is_finished(&self) -> bool {
+        self.done.iter().all(|&done| done)
+    }
+}
+
+/// Like `render_tiled_with_progress`, but only render the tiles that
+/// `state` doesn't have finished into `pixels`, which holds the others
+/// already, keeping `state` up to date. Every few tiles per thread, the
+/// image and state so far are handed to `save`, so that a render can be
+/// picked up again after it's stopped; rendering stops at the first error
+/// from `save`, which is returned. The rows of the finished tiles are
+/// counted in `progress`, if given, at the start. Once it's cancelled, the
+/// tiles under way are left unfinished. The Buddhabrot can't be rendered
+/// like this.
+pub fn render_tiled_resumable<P: Sample, E>(
+    pixels: &mut [P],
+    region: &Region,
+    params: &Params,
+    threads: usize,
+    state: &mut TiledState,
+    progress: Option<&Progress>,
+    save: impl FnMut(&[P], &TiledState) -> Result<(), E>,
+) -> Result<Stats, E> {
+    assert!(params.fractal != Fractal::Buddhabrot);
+    let batch = threads.max(1) * TILES_PER_SAVE;
+    render_tiles(
+        pixels, region, params, threads, state, progress, batch, save,
+    )
+}
+
+/// Render the tiles `state` doesn't have finished in groups of `batch`,
+/// calling `save` after each.
+#[allow(clippy::too_many_arguments)]
+fn render_tiles<P: Sample, E>(
+    pixels: &mut [P],
+    region: &Region,
+    params: &Params,
+    threads: usize,
+    state: &mut TiledState,
+    progress: Option<&Progress>,
+    batch: usize,
+    mut save: impl FnMut(&[P], &TiledState) -> Result<(), E>,
+) -> Result<Stats, E> {
+    let (bounds, channels) = (region.bounds, params.channels());
+    assert!(pixels.len() == bounds.0 * bounds.1 * channels);
+    let mut tiles = Tile::split(bounds, state.tile_size);
+    assert!(tiles.len() == state.done.len());
+
+    let equalizer = equalizer(params, || {
+        let tally = |tiles: &mut [Tile<P>], _| {
+            let histograms = tiles.iter().map(|tile| {
+                let (cols, rows) = (tile.cols.clone(), tile.rows.clone());
+                tally_rows(region, cols, rows, params, progress)
+            });
+            histograms.fold(Histogram::new(params.limit), Histogram::merge)
+        };
+        let empty = || Histogram::new(params.limit);
+        in_rows(&mut tiles, 1, threads, tally, empty, Histogram::merge)
+    });
+    let equalizer = equalizer.as_ref();
+
+    tiles.retain(|tile| !state.done[tile.index]);
+    if let Some(progress) = progress {
+        let all: usize = Tile::<P>::split(bounds, state.tile_size)
+            .iter()
+            .filter(|tile| state.done[tile.index])
+            .map(|tile| tile.rows.len())
+            .sum();
+        progress.add_rows(all);
+    }
+    for group in tiles.chunks_mut(batch.max(1)) {
+        if progress.is_some_and(Progress::is_cancelled) {
+            break;
+        }
+        // Tiles start out as what's in the image, so that a cancelled
+        // render leaves the rest of it as it was.
+        for tile in group.iter_mut() {
+            let mut buffer = Vec::with_capacity(tile.cols.len() * tile.rows.len() * channels);
+            tile.lines(pixels, bounds.0, channels)
+                .for_each(|line| buffer.extend_from_slice(line));
+            tile.pixels = buffer;
+        }
+        let paint = |tiles: &mut [Tile<P>], _| {
+            for tile in tiles {
+                let (cols, top) = (tile.cols.clone(), tile.rows.start);
+                let stats = render_rows(
+                    &mut tile.pixels,
+                    region,
+                    cols,
+                    top,
+                    params,
+                    equalizer,
+                    progress,
+                );
+                let cancelled = progress.is_some_and(Progress::is_cancelled);
+                tile.stats = (!cancelled).then_some(stats);
+            }
+        };
+        in_rows(group, 1, threads, paint, || (), |(), ()| ());
+        for tile in group.iter_mut() {
+            let rendered = std::mem::take(&mut tile.pixels);
+            let lines = tile.lines(pixels, bounds.0, channels);
+            for (line, rendered) in lines.zip(rendered.chunks(tile.cols.len() * channels)) {
+                line.copy_from_slice(rendered);
+            }
+            if let Some(stats) = tile.stats {
+                state.done[tile.index] = true;
+                state.stats = state.stats.merge(stats);
+            }
+        }
+        save(pixels, state)?;
+    }
+
+    Ok(state.stats)
+}
+
+/// Render the image of `region` like `render_parallel`, but only
+/// `strip_rows` rows at a time, handing each finished strip to `write`
+/// before starting the next. Only one strip is ever held in memory, so this
+/// can render images too large for `render_parallel`; histogram coloring
+/// counts the whole image in a first pass of strips. Finished rows are
+/// counted in `progress`, if given, and once it's cancelled the remaining
+/// rows are written black. Rendering stops at the first error from `write`,
+/// which is returned. The Buddhabrot, which needs the whole image at once,
+/// can't be rendered in strips.
+pub fn render_strips<P: Sample, E>(
+    region: &Region,
+    params: &Params,
+    strip_rows: usize,
+    threads: usize,
+    progress: Option<&Progress>,
+    mut write: impl FnMut(&[P]) -> Result<(), E>,
+) -> Result<Stats, E> {
+    assert!(params.fractal != Fractal::Buddhabrot);
+    let bounds = region.bounds;
+    let row_len = bounds.0 * params.channels();
+    let strip_rows = strip_rows.clamp(1, bounds.1.max(1));
+    let mut strip = vec![P::default(); strip_rows * row_len];
+    let strips = || (0..bounds.1).step_by(strip_rows);
+    let band = |top: usize| (bounds.1 - top).min(strip_rows) * row_len;
+
+    let equalizer = equalizer(params, || {
+        let tally = |rows| tally_rows(region, 0..bounds.0, rows, params, progress);
+        let empty = || Histogram::new(params.limit);
+        strips()
+            .map(|top| {
+                let rows = top..top + band(top) / row_len.max(1);
+                on_rows(rows, threads, tally, empty, Histogram::merge)
+            })
+            .fold(Histogram::new(params.limit), Histogram::merge)
+    });
+    let equalizer = equalizer.as_ref();
+
+    let mut stats = Stats::default();
+    for top in strips() {
+        let paint = |rows: &mut [P], first: usize| {
+            render_rows(
+                rows,
+                region,
+                0..bounds.0,
+                top + first,
+                params,
+                equalizer,
+                progress,
+            )
+        };
+        let strip = &mut strip[..band(top)];
+        // Rows skipped after a cancel are written black, not as the rows of
+        // the previous strip.
+        strip.fill(P::default());
+        stats = stats.merge(in_rows(
+            strip,
+            row_len,
+            threads,
+            paint,
+            Stats::default,
+            Stats::merge,
+        ));
+        write(strip)?;
+    }
+
+    Ok(stats)
+}
+
+/// Render only columns `cols` of rows `rows` of the image of `region` into
+/// `pixels`, which holds just those pixels, on `threads` threads like
+/// `render_parallel`. Every pixel comes out as it does in the whole image,
+/// histogram coloring counting the whole image in a first pass, so windows
+/// rendered separately piece together into it. Finished rows are counted
+/// in `progress`, if given: every row of the image in the first pass, and
+/// then the window's. If it's cancelled, the rows not yet started are left
+/// as they are. The Buddhabrot, which needs the whole image at once, can't
+/// be rendered in windows.
+pub fn render_window<P: Sample>(
+    pixels: &mut [P],
+    region: &Region,
+    cols: Range<usize>,
+    rows: Range<usize>,
+    params: &Params,
+    threads: usize,
+    progress: Option<&Progress>,
+) -> Stats {
+    assert!(params.fractal != Fractal::Buddhabrot);
+    let bounds = region.bounds;
+    assert!(cols.end <= bounds.0 && rows.end <= bounds.1);
+    let row_len = cols.len() * params.channels();
+    assert!(pixels.len() == rows.len() * row_len);
+
+    let equalizer = equalizer(params, || {
+        let tally = |rows| tally_rows(region, 0..bounds.0, rows, params, progress);
+        let empty = || Histogram::new(params.limit);
+        on_rows(0..bounds.1, threads, tally, empty, Histogram::merge)
+    });
+    let equalizer = equalizer.as_ref();
+
+    let paint = |band: &mut [P], top: usize| {
+        let top = rows.start + top;
+        render_rows(band, region, cols.clone(), top, params, equalizer, progress)
+    };
+    in_rows(
+        pixels,
+        row_len,
+        threads,
+        paint,
+        Stats::default,
+        Stats::merge,
+    )
+}
+
+/// Call `f` on the rows of `buffer`, `row_len` items each, on a pool of
+/// `threads` threads handing out one row at a time, and combine the results
+/// with `merge`. `f` is given the rows and the index of the first of them.
+/// `threads` is clamped to between one and the number of rows; with a
+/// single thread, `f` is called once on the whole buffer on this thread.
+#[cfg(feature = "rayon")]
+fn in_rows<T, R, F, E>(
+    buffer: &mut [T],
+    row_len: usize,
+    threads: usize,
+    f: F,
+    empty: E,
+    merge: fn(R, R) -> R,
+) -> R
+where
+    T: Send,
+    R: Send,
+    F: Fn(&mut [T], usize) -> R + Sync,
+    E: Fn() -> R + Sync + Send,
+{
+    use rayon::prelude::*;
+
+    let row_len = row_len.max(1);
+    let rows = buffer.len() / row_len;
+    let threads = threads.clamp(1, rows.max(1));
+    if threads == 1 {
+        return f(buffer, 0);
+    }
+    // Without a pool the rows are rendered here, to the same pixels.
+    let Ok(pool) = thread_pool(threads) else {
+        return f(buffer, 0);
+    };
+    pool.install(|| {
+        buffer
+            .par_chunks_mut(row_len)
+            // Rows differ a lot in how long they take, so don't let rayon
+            // hand out runs of them that idle threads can't steal from.
+            .with_max_len(1)
+            .enumerate()
+            .map(|(row, line)| f(line, row))
+            .reduce(&empty, merge)
+    })
+}
+
+/// Call `f` on `rows` as `in_rows` would on the rows of a buffer, for work
+/// that needs only the rows' indices: with one thread once on all of them,
+/// and otherwise on one row at a time.
+#[cfg(feature = "rayon")]
+fn on_rows<R, F, E>(rows: Range<usize>, threads: usize, f: F, empty: E, merge: fn(R, R) -> R) -> R
+where
+    R: Send,
+    F: Fn(Range<usize>) -> R + Sync,
+    E: Fn() -> R + Sync + Send,
+{
+    use rayon::prelude::*;
+
+    let threads = threads.clamp(1, rows.len().max(1));
+    if threads == 1 {
+        return f(rows);
+    }
+    let Ok(pool) = thread_pool(threads) else {
+        return f(rows);
+    };
+    pool.install(|| {
+        rows.into_par_iter()
+            .with_max_len(1)
+            .map(|row| f(row..row + 1))
+            .reduce(&empty, merge)
+    })
+}
+
+/// The pool of `threads` threads for `in_rows` and `on_rows`, built the
+/// first time that many are asked for and kept for every later render, so
+/// that strips and passes don't each start and stop their own threads.
+#[cfg(feature = "rayon")]
+fn thread_pool(threads: usize) -> Result<Arc<rayon::ThreadPool>, rayon::ThreadPoolBuildError> {
+    static POOLS: Mutex<Vec<Arc<rayon::ThreadPool>>> = Mutex::new(Vec::new());
+
+    let mut pools = POOLS.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(pool) = pools
+        .iter()
+        .find(|pool| pool.current_num_threads() == threads)
+    {
+        return Ok(Arc::clone(pool));
+    }
+    let pool = Arc::new(
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()?,
+    );
+    pools.push(Arc::clone(&pool));
+    Ok(pool)
+}
+
+/// The index of the render thread this is running on, from 0 up to the
+/// number of threads `in_rows` was given, or 0 outside of `in_rows`.
+#[cfg(feature = "rayon")]
+fn worker_index() -> usize {
+    rayon::current_thread_index().unwrap_or(0)
+}
+
+#[cfg(not(feature = "rayon"))]
+fn worker_index() -> usize {
+    WORKER.with(Cell::get)
+}
+
+#[cfg(not(feature = "rayon"))]
+thread_local! {
+    /// The index of the `in_rows` thread this is.
+    static WORKER: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Like the rayon version, with `threads` threads taking the next row from
+/// a shared queue as they finish the last, until there are none left. Rows
+/// differ a lot in how long they take, so fixed bands of rows, one per
+/// thread, would leave the threads that got the quick ones idle.
+#[cfg(not(feature = "rayon"))]
+fn in_rows<T, R, F, E>(
+    buffer: &mut [T],
+    row_len: usize,
+    threads: usize,
+    f: F,
+    empty: E,
+    merge: fn(R, R) -> R,
+) -> R
+where
+    T: Send,
+    R: Send,
+    F: Fn(&mut [T], usize) -> R + Sync,
+    E: Fn() -> R + Sync + Send,
+{
+    let row_len = row_len.max(1);
+    let rows = buffer.len() / row_len;
+    let threads = threads.clamp(1, rows.max(1));
+    if threads == 1 {
+        return f(buffer, 0);
+    }
+    let queue = buffer.chunks_mut(row_len).enumerate();
+    in_queue(queue, threads, |(row, line)| f(line, row), empty, merge)
+}
+
+/// Like the rayon version, with the rows handed out as by `in_rows`.
+#[cfg(not(feature = "rayon"))]
+fn on_rows<R, F, E>(rows: Range<usize>, threads: usize, f: F, empty: E, merge: fn(R, R) -> R) -> R
+where
+    R: Send,
+    F: Fn(Range<usize>) -> R + Sync,
+    E: Fn() -> R + Sync + Send,
+{
+    let threads = threads.clamp(1, rows.len().max(1));
+    if threads == 1 {
+        return f(rows);
+    }
+    in_queue(rows, threads, |row| f(row..row + 1), empty, merge)
+}
+
+/// Call `f` on each item of `queue` on `threads` threads, each taking the
+/// next item as it finishes the last, and combine the results with
+/// `merge`.
+#[cfg(not(feature = "rayon"))]
+fn in_queue<I, R, F, E>(queue: I, threads: usize, f: F, empty: E, merge: fn(R, R) -> R) -> R
+where
+    I: Iterator + Send,
+    R: Send,
+    F: Fn(I::Item) -> R + Sync,
+    E: Fn() -> R + Sync + Send,
+{
+    let queue = Mutex::new(queue);
+    let (f, empty, queue) = (&f, &empty, &queue);
+
+    crossbeam::scope(|spawner| {
+        let handles: Vec<_> = (0..threads)
+            .map(|i| {
+                spawner.spawn(move |_: &Scope| {
+                    WORKER.with(|worker| worker.set(i));
+                    let mut result = empty();
+                    loop {
+                        // Take the next item, unlocking the queue before
+                        // working on it.
+                        let next = queue.lock().unwrap().next();
+                        let Some(item) = next else {
+                            return result;
+                        };
+                        result = merge(result, f(item));
+                    }
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .fold(empty(), merge)
+    })
+    .unwrap()
+}
+
+/// The escape count of every pixel of the image of `region`, row by row,
+/// computed on `threads` threads. Points that reach the limit get
+/// `params.limit`, which no escaping point can have. Smooth shading and
+/// supersampling don't apply.
+pub fn escape_counts(region: &Region, params: &Params, threads: usize) -> Vec<u32> {
+    let bounds = region.bounds;
+    let mut counts = vec![0; bounds.0 * bounds.1];
+    let count_rows = |band: &mut [u32], top: usize| {
+        for (i, line) in band.chunks_mut(bounds.0).enumerate() {
+            for (col, count) in line.iter_mut().enumerate() {
+                let point = region.pixel_to_point((col, top + i));
+                *count = params.escape(point).unwrap_or(params.limit);
+            }
+        }
+    };
+    in_rows(
+        &mut counts,
+        bounds.0,
+        threads,
+        count_rows,
+        || (),
+        |(), ()| (),
+    );
+
+    counts
+}
+
+/// The escape value of every pixel of the image of `region`, row by row,
+/// computed on `threads` threads: the continuous value with smooth
+/// shading and the escape count otherwise. Points that reach the limit
+/// never escape, so they get infinity. Supersampling doesn't apply.
+pub fn escape_values(region: &Region, params: &Params, threads: usize) -> Vec<f32> {
+    let bounds = region.bounds;
+    let mut values = vec![0.0; bounds.0 * bounds.1];
+    let value_rows = |band: &mut [f32], top: usize| {
+        for (i, line) in band.chunks_mut(bounds.0).enumerate() {
+            for (col, value) in line.iter_mut().enumerate() {
+                let point = region.pixel_to_point((col, top + i));
+                *value = params
+                    .escape_value(point)
+                    .map_or(f32::INFINITY, |v| v as f32);
+            }
+        }
+    };
+    in_rows(
+        &mut values,
+        bounds.0,
+        threads,
+        value_rows,
+        || (),
+        |(), ()| (),
+    );
+
+    values
+}
+
+/// Render the image of `region` into `pixels`, three bytes per pixel, on
+/// `threads` threads like `render_parallel`, but color each sample with
+/// `colorizer` instead of `params.palette`. The colorizer is handed escape
+/// values as `Params::escape_value` gives them, ranked on the scale of the
+/// limit first under histogram coloring; supersampling averages the colors
+/// of each pixel's samples, and `params.gamma` applies as usual. The
+/// inside of the set is whatever the colorizer makes of `None`, whatever
+/// `params.interior` says.
+pub fn render_colorized(
+    pixels: &mut [u8],
+    region: &Region,
+    params: &Params,
+    colorizer: &dyn Colorizer,
+    threads: usize,
+) -> Stats {
+    let bounds = region.bounds;
+    assert!(pixels.len() == bounds.0 * bounds.1 * 3);
+    let row_len = bounds.0 * 3;
+
+    let equalizer = equalizer(params, || {
+        let tally = |rows| tally_rows(region, 0..bounds.0, rows, params, None);
+        let empty = || Histogram::new(params.limit);
+        on_rows(0..bounds.1, threads, tally, empty, Histogram::merge)
+    });
+    let limit = f64::from(params.limit);
+    let rank = |value: f64| match &equalizer {
+        Some(equalizer) => equalizer.equalize(value) * limit,
+        None => value,
+    };
+    let size = region.pixel_size();
+    let count = f64::from(params.sample_count());
+    // Colorizers always make RGB, so their samples are averaged in linear
+    // light unless that's turned off.
+    let linear = count > 1.0 && params.linear_averaging.unwrap_or(true);
+
+    let paint = |band: &mut [u8], top: usize| {
+        let mut stats = Stats::default();
+        for (i, line) in band.chunks_mut(row_len.max(1)).enumerate() {
+            for (col, pixel) in line.chunks_mut(3).enumerate() {
+                let point = region.pixel_to_point((col, top + i));
+                let mut sum = [0.0; 3];
+                let mut escaped = Stats::default();
+                for point in params.samples(point, size) {
+                    let value = params.escape_value(point);
+                    escaped.record(value);
+                    let mut sample = colorizer.color(value.map(rank), params.limit);
+                    params.apply_gamma(&mut sample);
+                    for (total, channel) in sum.iter_mut().zip(sample) {
+                        *total += match linear {
+                            true => palette::to_linear(f64::from(channel) / 255.0),
+                            false => f64::from(channel),
+                        };
+                    }
+                }
+                for (channel, total) in pixel.iter_mut().zip(sum) {
+                    let mean = match linear {
+                        true => palette::from_linear(total / count) * 255.0,
+                        false => total / count,
+                    };
+                    *channel = mean.round() as u8;
+                }
+                stats.record(escaped.mean());
+            }
+        }
+        stats
+    };
+    in_rows(
+        pixels,
+        row_len,
+        threads,
+        paint,
+        Stats::default,
+        Stats::merge,
+    )
+}
+
+/// Allocate a buffer for the image of `region`, render into it on
+/// `default_threads()` threads and return it. The result can be passed to
+/// `write_image` along with `params.color_type()`.
+pub fn render_to_vec(region: &Region, params: &Params) -> Vec<u8> {
+    let (width, height) = region.bounds;
+    let mut pixels = vec![0; width * height * params.channels()];
+    render_parallel(&mut pixels, region, params, default_threads());
+    pixels
+}
+
+/// The FNV-1a hash of the reference image `validate` renders, as it comes
+/// out on the builds this crate is developed on.
+const VALIDATION_HASH: u64 = 0x9f5f_5e8c_208f_9cac;
+
+/// Render a small reference image, smoothly shaded along a palette and
+/// supersampled in 16 bits, and check that it hashes to the value baked
+/// in here, to catch builds whose floating point differs from the builds
+/// this crate is developed on.
+///
+/// A mismatch doesn't mean the build renders wrongly, only that it may not
+/// render bit for bit like others, as when the compiler fuses multiplies
+/// and adds or the platform's `ln` rounds differently, so the images of
+/// one machine can't be relied on to match another's exactly.
+pub fn validate() -> bool {
+    let region = Region::new((32, 24), cmplx!(-2.0, 1.2), cmplx!(1.0, -1.2));
+    let params = Params {
+        smooth: true,
+        palette: Some(Palette::Fire),
+        supersample: 2,
+        ..Params::default()
+    };
+    let mut pixels = vec![0u16; 32 * 24 * params.channels()];
+    render(&mut pixels, &region, &params);
+
+    let bytes = pixels.iter().flat_map(|sample| sample.to_le_bytes());
+    let hash = bytes.fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    hash == VALIDATION_HASH
+}
+
+#[cfg(test)]
+mod test {
+    use super::zoom_sequence as zs;
+    use super::Params;
+    use super::ParseError::{InvalidLeft, InvalidRight, MissingSeparator};
+    use super::Precision;
+    use super::PHOENIX_P;
+    use super::{brightness, corners_from_center as cfc, parse_complex as pc, parse_pair as pp};
+    use super::{corner_samples, in_rows, is_edge, render_strips, worker_index};
+    use super::{escape_counts, render_parallel_with_progress, ColorMode, InteriorColor, Progress};
+    use super::{escape_time, render, render_parallel, render_tiled, Complex64, Fractal, Palette};
+    use super::{order_corners as oc, pixel_to_point as ptp, preserve_aspect as pa, Region};
+    use super::{parse_corners as pcs, parse_dimensions as pd, Range, Sample, Scale};
+    use super::{parse_polar, validate, Shading, MAX_SUPERSAMPLE, TAU};
+
+    #[test]
+    fn parse_pair() {
+        assert_eq!(pp::<i32>("", ','), Err(MissingSeparator));
+        assert_eq!(pp::<u32>("10", 'x'), Err(MissingSeparator));
+        assert_eq!(pp::<u32>("10x20", ','), Err(MissingSeparator));
+        assert_eq!(pp::<f64>("400.0x", 'x'), Err(InvalidRight));
+        assert_eq!(pp::<u32>("10,20", ','), Ok((10u32, 20u32)));
+        assert_eq!(pp::<u32>("400x600", 'x'), Ok((400u32, 600u32)));
+        assert_eq!(pp::<f64>("400.0x600.5", 'x'), Ok((400.0f64, 600.5f64)));
+        assert_eq!(pp::<f64>("1.0, 0.5", ','), Ok((1.0, 0.5)));
+        assert_eq!(pp::<u32>(" 400 x 600 ", 'x'), Ok((400, 600)));
+        assert_eq!(pp::<f64>("-1.20 , 0.35", ','), Ok((-1.20, 0.35)));
+        assert_eq!(pp::<u32>("4 00x600", 'x'), Err(InvalidLeft));
+        assert_eq!(pp::<f64>("1.0, ", ','), Err(InvalidRight));
+    }
+
+    #[test]
+    fn validates() {
+        assert!(validate());
+    }
+
+    #[test]
+    fn parse_dimensions() {
+        assert_eq!(pd("800x600"), Ok((800, 600)));
+        assert_eq!(pd("800"), Ok((800, 800)));
+        assert_eq!(pd(" 64 "), Ok((64, 64)));
+        assert_eq!(pd("800x"), Err(InvalidRight));
+        assert_eq!(pd("eight"), Err(InvalidLeft));
+        assert_eq!(pd(""), Err(InvalidLeft));
+    }
+
+    #[test]
+    fn parse_corners() {
+        let corners = (cmplx!(-2.0, -1.5), cmplx!(1.0, 1.5));
+        assert_eq!(pcs("-2,-1.5,1,1.5"), Ok(corners));
+        assert_eq!(pcs(" -2 , -1.5 , 1 , 1.5 "), Ok(corners));
+        assert_eq!(pcs("-2,-1.5"), Err(MissingSeparator));
+        assert_eq!(pcs("-2,-1.5,1"), Err(InvalidRight));
+        assert_eq!(pcs("-2,-1.5,1,1.5,0"), Err(InvalidRight));
+        assert_eq!(pcs("-2,nan,1,1.5"), Err(InvalidLeft));
+        assert_eq!(pcs("-2,-1.5,inf,1.5"), Err(InvalidRight));
+        assert_eq!(pcs("-2,x,1,1.5"), Err(InvalidLeft));
+    }
+
+    #[test]
+    fn parse_complex() {
+        assert_eq!(pc("1.25,-0.0625"), Ok(cmplx!(1.25, -0.0625)));
+        assert_eq!(pc("0.0,0.0"), Ok(cmplx!()));
+        assert_eq!(pc(",-1.0256"), Err(InvalidLeft));
+        assert_eq!(pc("-1.20 , 0.35"), Ok(cmplx!(-1.20, 0.35)));
+        assert_eq!(pc(" -1.20+0.35i "), Ok(cmplx!(-1.20, 0.35)));
+        assert_eq!(pc("1.0 0.5"), Err(InvalidLeft));
+    }
+
+    #[test]
+    fn polar() {
+        assert_eq!(parse_polar("1.0,0"), pc("1.0,0.0"));
+        assert_eq!(parse_polar(" 2 , 0deg "), Ok(cmplx!(2.0, 0.0)));
+        let c = parse_polar("0.5,3.5").unwrap();
+        assert!((c.norm() - 0.5).abs() < 1e-15 && (c.arg() - (3.5 - TAU)).abs() < 1e-15);
+        let (radians, degrees) = (
+            parse_polar("1,1.25").unwrap(),
+            parse_polar("1,-90°").unwrap(),
+        );
+        assert!((radians - Complex64::from_polar(1.0, 1.25)).norm() < 1e-15);
+        assert!((degrees - cmplx!(0.0, -1.0)).norm() < 1e-15);
+        assert_eq!(parse_polar("1"), Err(MissingSeparator));
+        assert_eq!(parse_polar("r,0"), Err(InvalidLeft));
+        assert_eq!(parse_polar("1,90 degrees"), Err(InvalidRight));
+    }
+
+    #[test]
+    fn parse_complex_algebraic() {
+        assert_eq!(pc("1.25-0.0625i"), Ok(cmplx!(1.25, -0.0625)));
+        assert_eq!(pc("-1.20+0.35i"), Ok(cmplx!(-1.20, 0.35)));
+        assert_eq!(pc("i"), Ok(cmplx!(0.0, 1.0)));
+        assert_eq!(pc("-i"), Ok(cmplx!(0.0, -1.0)));
+        assert_eq!(pc("2-i"), Ok(cmplx!(2.0, -1.0)));
+        assert_eq!(pc("3i"), Ok(cmplx!(0.0, 3.0)));
+        assert_eq!(pc("3"), Ok(cmplx!(3.0, 0.0)));
+        assert_eq!(pc("1e-3+2.5e+1i"), Ok(cmplx!(0.001, 25.0)));
+        assert_eq!(pc("1+2"), Err(InvalidLeft));
+        assert_eq!(pc("1+-2i"), Err(InvalidLeft));
+        assert_eq!(pc("x+2i"), Err(InvalidLeft));
+        assert_eq!(pc("1+2ei"), Err(InvalidRight));
+        assert_eq!(pc("1,2i"), Err(InvalidRight));
+        assert_eq!(pc(""), Err(InvalidLeft));
+    }
+
+    #[test]
+    fn pixel_to_point() {
+        assert_eq!(
+            ptp((100, 100), (25, 75), cmplx!(-1.0, 1.0), cmplx!(1.0, -1.0)),
+            cmplx!(-0.5, -0.5)
+        );
+    }
+
+    /// A xorshift generator, for test cases that are random but the same
+    /// on every run.
+    struct Xorshift(u64);
+
+    impl Xorshift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /// A float drawn evenly from `range`.
+        fn float(&mut self, range: Range<f64>) -> f64 {
+            let unit = (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+            range.start + unit * (range.end - range.start)
+        }
+
+        /// An integer drawn evenly from `range`, near enough.
+        fn int(&mut self, range: Range<usize>) -> usize {
+            range.start + (self.next() % (range.end - range.start) as u64) as usize
+        }
+    }
+
+    #[test]
+    fn pixel_to_point_properties() {
+        let mut rng = Xorshift(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..500 {
+            let bounds = (rng.int(1..3000), rng.int(1..3000));
+            let center = cmplx!(rng.float(-2.0..2.0), rng.float(-2.0..2.0));
+            let half = cmplx!(rng.float(1e-6..2.0), rng.float(1e-6..2.0));
+            let (u_l, l_r) = (center - half.conj(), center + half.conj());
+            let case = format!("{:?} {:?} {:?}", bounds, u_l, l_r);
+
+            assert_eq!(ptp(bounds, (0, 0), u_l, l_r), u_l, "{}", case);
+            // The far corner can be off by a few units in the last place of
+            // coordinates below 4, from rounding the view's width and height.
+            let corner = ptp(bounds, bounds, u_l, l_r);
+            let tolerance = 16.0 * f64::EPSILON;
+            assert!(
+                (corner.re - l_r.re).abs() <= tolerance,
+                "{} {:?}",
+                case,
+                corner
+            );
+            assert!(
+                (corner.im - l_r.im).abs() <= tolerance,
+                "{} {:?}",
+                case,
+                corner
+            );
+
+            let row = rng.int(0..bounds.1 + 1);
+            for col in 0..bounds.0 {
+                let (a, b) = (
+                    ptp(bounds, (col, row), u_l, l_r),
+                    ptp(bounds, (col + 1, row), u_l, l_r),
+                );
+                assert!(a.re < b.re && a.im == b.im, "{} {:?} {:?}", case, a, b);
+            }
+            let col = rng.int(0..bounds.0 + 1);
+            for row in 0..bounds.1 {
+                let (a, b) = (
+                    ptp(bounds, (col, row), u_l, l_r),
+                    ptp(bounds, (col, row + 1), u_l, l_r),
+                );
+                assert!(a.im > b.im && a.re == b.re, "{} {:?} {:?}", case, a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn corners_from_center() {
+        assert_eq!(
+            cfc(cmplx!(), 2.0, (400, 200)),
+            (cmplx!(-2.0, 1.0), cmplx!(2.0, -1.0))
+        );
+        assert_eq!(
+            cfc(cmplx!(-0.75, 0.25), 0.5, (100, 100)),
+            (cmplx!(-1.25, 0.75), cmplx!(-0.25, -0.25))
+        );
+
+        let (u_l, l_r) = cfc(cmplx!(-0.5, 0.0), 1.5, (300, 200));
+        assert_eq!(ptp((300, 200), (150, 100), u_l, l_r), cmplx!(-0.5, 0.0));
+    }
+
+    #[test]
+    fn zoom_sequence() {
+        let zooms = zs(2.0, 0.002, 4);
+        assert_eq!(zooms.len(), 4);
+        assert_eq!((zooms[0], zooms[3]), (2.0, 0.002));
+        for pair in zooms.windows(2) {
+            assert!((pair[1] / pair[0] - 0.1).abs() < 1e-12, "{:?}", zooms);
+        }
+        assert_eq!(zs(1.5, 0.1, 1), vec![1.5]);
+        assert!(zs(1.5, 0.1, 0).is_empty());
+    }
+
+    #[test]
+    fn order_corners() {
+        let (u_l, l_r) = (cmplx!(-2.0, 1.0), cmplx!(1.0, -1.0));
+        assert_eq!(oc(u_l, l_r), ((u_l, l_r), false));
+        // Reversed along the real axis, the imaginary axis and both.
+        assert_eq!(oc(cmplx!(1.0, 1.0), cmplx!(-2.0, -1.0)), ((u_l, l_r), true));
+        assert_eq!(oc(cmplx!(-2.0, -1.0), cmplx!(1.0, 1.0)), ((u_l, l_r), true));
+        assert_eq!(oc(l_r, u_l), ((u_l, l_r), true));
+    }
+
+    #[test]
+    fn preserve_aspect() {
+        let bounds = (100, 100);
+        let (u_l, l_r) = pa(bounds, cmplx!(-2.0, 0.5), cmplx!(1.0, -0.5));
+        assert_eq!((u_l, l_r), (cmplx!(-2.0, 1.5), cmplx!(1.0, -1.5)));
+
+        let step_re = ptp(bounds, (1, 0), u_l, l_r).re - ptp(bounds, (0, 0), u_l, l_r).re;
+        let step_im = ptp(bounds, (0, 0), u_l, l_r).im - ptp(bounds, (0, 1), u_l, l_r).im;
+        assert!((step_re - step_im).abs() < 1e-12);
+
+        let (u_l, l_r) = pa((400, 200), cmplx!(-1.0, 1.0), cmplx!(1.0, 0.0));
+        assert_eq!((u_l, l_r), (cmplx!(-1.0, 1.0), cmplx!(1.0, 0.0)));
+    }
+
+    #[test]
+    fn brightness_scales_to_limit() {
+        for count in [0, 1, 100, 254] {
+            assert_eq!(brightness(count, 255), 255 - count as u8);
+        }
+        assert_eq!(brightness(0, 1000), 255);
+        assert_eq!(brightness(500, 1000), 128);
+        assert_eq!(brightness(999, 1000), 1);
+        assert_eq!(brightness(1000, 1000), 0);
+        assert_eq!(brightness(5000, 1000), 0);
+    }
+
+    #[test]
+    fn brightness_never_wraps() {
+        let params = Params {
+            limit: 1000,
+            ..Params::default()
+        };
+        let mut last = (255, u16::MAX);
+        for count in 0..=1200 {
+            let shades = (
+                params.shade::<u8>(Some(f64::from(count))),
+                params.shade::<u16>(Some(f64::from(count))),
+            );
+            assert_eq!(shades.0, brightness(count, 1000));
+            assert!(shades.0 <= last.0 && shades.1 <= last.1, "{}", count);
+            last = shades;
+        }
+        assert_eq!(last, (0, 0));
+
+        // Rendered pixels get darker the longer their points take to escape.
+        let bounds = (60, 40);
+        let region = Region::new(bounds, cmplx!(-1.2, 0.35), cmplx!(-1.0, 0.2));
+        let mut pixels = vec![0u8; bounds.0 * bounds.1];
+        render(&mut pixels, &region, &params);
+        let mut shades: Vec<(u32, u8)> = escape_counts(&region, &params, 1)
+            .into_iter()
+            .zip(pixels)
+            .filter(|&(count, _)| count < params.limit)
+            .collect();
+        shades.sort_unstable();
+        assert!(shades.last().unwrap().0 > 255);
+        assert!(shades.windows(2).all(|w| w[1].1 <= w[0].1));
+    }
+
+    #[test]
+    fn invert_flips_grays_and_palettes() {
+        let bounds = (60, 40);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(1.0, -1.2));
+        let plain = Params::default();
+        let all = [
+            plain,
+            Params {
+                smooth: true,
+                ..plain
+            },
+            Params {
+                color_mode: ColorMode::Histogram,
+                ..plain
+            },
+            Params {
+                color_mode: ColorMode::Stripes,
+                ..plain
+            },
+        ];
+        for params in all {
+            let inverted = Params {
+                invert: true,
+                ..params
+            };
+            let mut pixels = vec![0u8; bounds.0 * bounds.1];
+            let mut flipped = vec![0u8; pixels.len()];
+            render(&mut pixels, &region, &params);
+            render(&mut flipped, &region, &inverted);
+            let expected: Vec<u8> = pixels.iter().map(|&p| 255 - p).collect();
+            assert!(flipped == expected, "{:?}", params);
+            assert_eq!(inverted.shade::<u16>(None), u16::MAX);
+        }
+
+        // Palettes run the other way, and the set stays black.
+        let fire = Params {
+            palette: Some(Palette::Fire),
+            invert: true,
+            ..plain
+        };
+        for count in [0.0, 10.0, 100.0, 255.0] {
+            let level = 1.0 - count / f64::from(fire.limit);
+            let expected = Palette::Fire.level(level).map(u8::from_8bit);
+            assert_eq!(fire.color::<u8>(Some(count), Palette::Fire), expected);
+        }
+        assert_eq!(fire.color::<u8>(None, Palette::Fire), [0; 3]);
+    }
+
+    #[test]
+    fn cycled_colors_shift_with_offset() {
+        let bounds = (60, 40);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(1.0, -1.2));
+        let cycled = Params {
+            palette: Some(Palette::Fire),
+            cycle_period: Some(16.0),
+            ..Params::default()
+        };
+        let shifted = Params {
+            cycle_offset: 5.0,
+            ..cycled
+        };
+        let color = |params: &Params, value| {
+            let mut color = [0u8; 3];
+            params.fill(value, Shading::Linear, &mut color);
+            color
+        };
+        // Colors repeat every period, and the offset moves along it.
+        assert_eq!(color(&cycled, Some(3.0)), color(&cycled, Some(19.0)));
+        assert_eq!(color(&shifted, Some(3.0)), color(&cycled, Some(8.0)));
+        assert_eq!(color(&shifted, Some(14.0)), color(&cycled, Some(3.0)));
+        assert_ne!(color(&cycled, Some(3.0)), color(&cycled, Some(8.0)));
+
+        let counts = escape_counts(&region, &cycled, 1);
+        let (mut a, mut b) = (vec![0u8; counts.len() * 3], vec![0u8; counts.len() * 3]);
+        render(&mut a, &region, &cycled);
+        render(&mut b, &region, &shifted);
+        assert_ne!(a, b);
+        for ((a, b), &count) in a.chunks(3).zip(b.chunks(3)).zip(&counts) {
+            let value = (count < cycled.limit).then_some(f64::from(count));
+            assert_eq!(a, color(&cycled, value));
+            assert_eq!(b, color(&cycled, value.map(|v| v + 5.0)));
+        }
+    }
+
+    #[test]
+    fn parallel_matches_serial() {
+        let bounds = (80, 60);
+        let region = Region::new(bounds, cmplx!(-1.20, 0.35), cmplx!(-1.0, 0.20));
+        let mut serial = vec![0u8; bounds.0 * bounds.1];
+        let mut parallel = vec![0u8; bounds.0 * bounds.1];
+
+        render(&mut serial, &region, &Params::default());
+        render_parallel(&mut parallel, &region, &Params::default(), 8);
+
+        assert_eq!(serial, parallel);
+
+        // Pulling 64x64 tiles or single rows off a queue paints the same
+        // image as one band, however lopsided the work.
+        let bounds = (150, 140);
+        let lopsided = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(1.0, -4.8));
+        let mut band = vec![0u8; bounds.0 * bounds.1];
+        render(&mut band, &lopsided, &Params::default());
+        for threads in [2, 3] {
+            let mut rows = vec![0u8; band.len()];
+            render_parallel(&mut rows, &lopsided, &Params::default(), threads);
+            assert_eq!(rows, band, "threads {}", threads);
+            let mut tiles = vec![0u8; band.len()];
+            render_tiled(&mut tiles, &lopsided, &Params::default(), threads, 64);
+            assert_eq!(tiles, band, "threads {}", threads);
+        }
+    }
+
+    #[test]
+    fn parallel_covers_uneven_bands() {
+        let (u_l, l_r) = (cmplx!(-2.0, 1.0), cmplx!(1.0, -1.0));
+        for bounds in [(40, 61), (40, 7), (40, 3)] {
+            let region = Region::new(bounds, u_l, l_r);
+            let mut serial = vec![0u8; bounds.0 * bounds.1];
+            let mut parallel = vec![0u8; bounds.0 * bounds.1];
+
+            render(&mut serial, &region, &Params::default());
+            render_parallel(&mut parallel, &region, &Params::default(), 8);
+
+            assert_eq!(serial, parallel, "bounds {:?}", bounds);
+            let last_row = &parallel[(bounds.1 - 1) * bounds.0..];
+            assert!(last_row.iter().any(|&p| p != 0), "bounds {:?}", bounds);
+        }
+    }
+
+    #[test]
+    fn parallel_clamps_thread_count() {
+        let bounds = (40, 5);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.0), cmplx!(1.0, -1.0));
+        let mut serial = vec![0u8; bounds.0 * bounds.1];
+        render(&mut serial, &region, &Params::default());
+
+        for threads in [0, 1, 5, 64] {
+            let mut parallel = vec![0u8; bounds.0 * bounds.1];
+            render_parallel(&mut parallel, &region, &Params::default(), threads);
+            assert_eq!(serial, parallel, "threads {}", threads);
+        }
+    }
+
+    #[test]
+    fn in_rows_hands_out_each_row_once() {
+        for threads in [2, 3, 8] {
+            let mut buffer = vec![usize::MAX; 3 * 24];
+            let taken = in_rows(
+                &mut buffer,
+                3,
+                threads,
+                |line, row| {
+                    line.fill(worker_index());
+                    vec![(row, line.len())]
+                },
+                Vec::new,
+                |a, b| [a, b].concat(),
+            );
+            assert!(taken.iter().all(|&(_, len)| len == 3), "{:?}", taken);
+            let mut rows: Vec<usize> = taken.iter().map(|&(row, _)| row).collect();
+            rows.sort();
+            assert_eq!(rows, (0..24).collect::<Vec<_>>(), "threads {}", threads);
+            assert!(
+                buffer.iter().all(|&worker| worker < threads),
+                "{:?}",
+                buffer
+            );
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn thread_pools_are_reused() {
+        let pool = super::thread_pool(3).unwrap();
+        assert_eq!(pool.current_num_threads(), 3);
+        assert!(std::sync::Arc::ptr_eq(
+            &pool,
+            &super::thread_pool(3).unwrap()
+        ));
+        assert!(!std::sync::Arc::ptr_eq(
+            &pool,
+            &super::thread_pool(4).unwrap()
+        ));
+    }
+
+    #[test]
+    fn single_thread_is_serial() {
+        let caller = std::thread::current().id();
+        let mut buffer = vec![0u8; 40 * 5];
+        let calls = in_rows(
+            &mut buffer,
+            40,
+            1,
+            |rows, top| {
+                assert_eq!((rows.len(), top), (40 * 5, 0));
+                assert_eq!(std::thread::current().id(), caller);
+                1
+            },
+            || 0,
+            |a, b| a + b,
+        );
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn julia() {
+        let c = cmplx!(-0.8, 0.156);
+        for z in [cmplx!(0.3, 0.1), cmplx!(-0.25, 0.6), cmplx!(1.2, -0.4)] {
+            assert_eq!(escape_time(z, c, 255), escape_time(-z, c, 255));
+        }
+
+        let bounds = (48, 32);
+        let region = Region::new(bounds, cmplx!(-1.6, 1.0), cmplx!(1.6, -1.0));
+        let params = Params {
+            julia: Some(c),
+            ..Params::default()
+        };
+        let mut pixels = vec![0u8; bounds.0 * bounds.1];
+        render(&mut pixels, &region, &params);
+
+        let mut mandel = vec![0u8; bounds.0 * bounds.1];
+        render(&mut mandel, &region, &Params::default());
+
+        assert!(pixels.contains(&0));
+        assert!(pixels.iter().any(|&p| p != 0));
+        assert_ne!(pixels, mandel);
+    }
+
+    #[test]
+    fn starting_point() {
+        let z0 = cmplx!(0.2, -0.1);
+        let params = Params {
+            z0,
+            ..Params::default()
+        };
+        for c in [cmplx!(-0.75, 0.2), cmplx!(0.3, 0.5), cmplx!(-1.5, 0.01)] {
+            assert_eq!(params.escape(c), escape_time(z0, c, params.limit));
+        }
+        assert!(!params.plain_escape_counts());
+
+        let bounds = (48, 32);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.0), cmplx!(1.0, -1.0));
+        let mut pixels = vec![0u8; bounds.0 * bounds.1];
+        render(&mut pixels, &region, &params);
+        let mut zero = vec![0u8; bounds.0 * bounds.1];
+        render(&mut zero, &region, &Params::default());
+        let changed = pixels.iter().zip(&zero).filter(|(a, b)| a != b).count();
+        assert!(changed > pixels.len() / 20, "{}", changed);
+    }
+
+    #[test]
+    fn smooth_render_has_no_bands() {
+        let bounds = (64, 1);
+        let region = Region::new(bounds, cmplx!(0.4, 0.0), cmplx!(1.0, 0.0));
+        let params = Params {
+            limit: 8,
+            ..Params::default()
+        };
+        let smooth = Params {
+            smooth: true,
+            ..params
+        };
+        let mut banded = vec![0u8; bounds.0];
+        let mut shaded = vec![0u8; bounds.0];
+
+        render(&mut banded, &region, &params);
+        render(&mut shaded, &region, &smooth);
+
+        let distinct = |p: &[u8]| {
+            let mut v = p.to_vec();
+            v.dedup();
+            v.len()
+        };
+        assert!(distinct(&shaded) > distinct(&banded));
+    }
+
+    #[test]
+    fn palette_render() {
+        let bounds = (24, 16);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.0), cmplx!(1.0, -1.0));
+        let params = Params {
+            palette: Some(Palette::Fire),
+            ..Params::default()
+        };
+        let mut pixels = vec![0u8; bounds.0 * bounds.1 * 3];
+        let mut parallel = pixels.clone();
+
+        render(&mut pixels, &region, &params);
+        render_parallel(&mut parallel, &region, &params, 4);
+
+        assert_eq!(pixels, parallel);
+        let corner = escape_time(cmplx!(), region.upper_left, 255).unwrap();
+        assert_eq!(pixels[..3], Palette::Fire.color(f64::from(corner) / 255.0));
+        assert!(pixels.chunks(3).any(|p| p == [0, 0, 0]));
+    }
+
+    /// `escape_time` without any of the shortcuts.
+    fn naive(c: Complex64) -> Option<u32> {
+        let mut z = cmplx!();
+        (0..255).find(|_| {
+            z = z * z + c;
+            z.norm_sqr() > 4.0
+        })
+    }
+
+    #[test]
+    fn periodicity_leaves_render_unchanged() {
+        let bounds = (60, 40);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(1.0, -1.2));
+        let mut pixels = vec![0u8; bounds.0 * bounds.1];
+        render(&mut pixels, &region, &Params::default());
+
+        for row in 0..bounds.1 {
+            for col in 0..bounds.0 {
+                let point = region.pixel_to_point((col, row));
+                let expected = naive(point).map_or(0, |count| 255 - count as u8);
+                assert_eq!(pixels[row * bounds.0 + col], expected, "{}", point);
+            }
+        }
+    }
+
+    #[test]
+    fn f32_render() {
+        let bounds = (40, 30);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(1.0, -1.2));
+        let params = Params {
+            precision: Precision::F32,
+            ..Params::default()
+        };
+        let mut single = vec![0u8; bounds.0 * bounds.1];
+        let mut double = vec![0u8; bounds.0 * bounds.1];
+
+        render(&mut single, &region, &params);
+        render(&mut double, &region, &Params::default());
+
+        let same = single.iter().zip(&double).filter(|(a, b)| a == b).count();
+        assert!(same > single.len() * 9 / 10);
+    }
+
+    #[test]
+    fn render_stats() {
+        let bounds = (30, 20);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(1.0, -1.2));
+        let params = Params::default();
+        let mut pixels = vec![0u8; bounds.0 * bounds.1];
+
+        let stats = render(&mut pixels, &region, &params);
+        let parallel = render_parallel(&mut pixels, &region, &params, 4);
+
+        assert_eq!(stats.pixels, 600);
+        assert_eq!(
+            stats.interior,
+            pixels.iter().filter(|&&p| p == 0).count() as u64
+        );
+        assert_eq!(stats.min, 0.0);
+        assert!(stats.max < 255.0 && stats.mean().unwrap() > 0.0);
+        assert_eq!(
+            (parallel.pixels, parallel.interior),
+            (stats.pixels, stats.interior)
+        );
+        assert_eq!((parallel.min, parallel.max), (stats.min, stats.max));
+    }
+
+    #[test]
+    fn burning_ship() {
+        // The small ship left of the main one, upright because the corners
+        // put the larger imaginary part at the bottom.
+        let bounds = (40, 18);
+        let region = Region::new(bounds, cmplx!(-1.8, -0.08), cmplx!(-1.7, 0.01));
+        let params = Params {
+            fractal: Fractal::BurningShip,
+            ..Params::default()
+        };
+        let mut ship = vec![0u8; bounds.0 * bounds.1];
+        let mut mandel = vec![0u8; bounds.0 * bounds.1];
+
+        render(&mut ship, &region, &params);
+        render(&mut mandel, &region, &Params::default());
+
+        let interior = |pixels: &[u8], row: usize| {
+            let line = &pixels[row * bounds.0..(row + 1) * bounds.0];
+            line.iter().filter(|&&p| p == 0).count()
+        };
+        // A wide hull with masts rising above it.
+        assert!(interior(&ship, 14) > 10);
+        assert!((9..12).any(|row| interior(&ship, row) > 0));
+        assert_eq!((9..12).map(|row| interior(&mandel, row)).sum::<usize>(), 0);
+    }
+
+    #[test]
+    fn celtic_and_heart_differ() {
+        let bounds = (40, 30);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.5), cmplx!(1.0, -1.5));
+        let images: Vec<Vec<u8>> = [Fractal::Mandelbrot, Fractal::Celtic, Fractal::Heart]
+            .into_iter()
+            .map(|fractal| {
+                let params = Params {
+                    fractal,
+                    ..Params::default()
+                };
+                let mut pixels = vec![0u8; bounds.0 * bounds.1];
+                render(&mut pixels, &region, &params);
+                pixels
+            })
+            .collect();
+        assert!(images[0] != images[1]);
+        assert!(images[0] != images[2]);
+        assert!(images[1] != images[2]);
+        // Each still has points inside and points that escape quickly.
+        for image in &images {
+            assert!(image.contains(&0) && image.iter().any(|&p| p > 250));
+        }
+    }
+
+    #[test]
+    fn phoenix_structure() {
+        let bounds = (60, 40);
+        let region = Region::new(bounds, cmplx!(-1.8, 1.2), cmplx!(1.8, -1.2));
+        let phoenix = Params {
+            fractal: Fractal::Phoenix(PHOENIX_P),
+            julia: Some(cmplx!(0.5667, 0.0)),
+            limit: 200,
+            ..Params::default()
+        };
+        let mut pixels = vec![0u8; bounds.0 * bounds.1];
+        render(&mut pixels, &region, &phoenix);
+        let rows: Vec<&[u8]> = pixels.chunks(bounds.0).collect();
+        let interior = |row: &[u8]| row.iter().filter(|&&p| p == 0).count();
+
+        // The set is mirrored in the real axis, on the middle row, which it
+        // never touches, and has two solid lobes above and below it.
+        for (top, bottom) in rows[1..].iter().zip(rows[1..].iter().rev()) {
+            assert_eq!(top, bottom);
+        }
+        assert_eq!(interior(rows[bounds.1 / 2]), 0);
+        let inside: usize = rows[..bounds.1 / 2].iter().map(|row| interior(row)).sum();
+        assert!(inside > 20, "{}", inside);
+
+        // Without the memory term it's the quadratic Julia set of 0.5667,
+        // which is dust, with no inside at all.
+        let dust = Params {
+            fractal: Fractal::Phoenix(cmplx!()),
+            ..phoenix
+        };
+        let mut quadratic = vec![0u8; pixels.len()];
+        render(&mut quadratic, &region, &dust);
+        render(
+            &mut pixels,
+            &region,
+            &Params {
+                fractal: Fractal::Mandelbrot,
+                ..dust
+            },
+        );
+        assert_eq!(quadratic, pixels);
+        assert!(!quadratic.contains(&0));
+    }
+
+    #[test]
+    fn tricorn_symmetry() {
+        let bounds = (60, 60);
+        let region = Region::new(bounds, cmplx!(-2.0, -1.5), cmplx!(1.0, 1.5));
+        let params = Params {
+            fractal: Fractal::Tricorn,
+            ..Params::default()
+        };
+        let mut pixels = vec![0u8; bounds.0 * bounds.1];
+        render(&mut pixels, &region, &params);
+
+        // Rotating a point by a third of a turn leaves its escape count
+        // alone, apart from rounding at a few boundary points.
+        let third = Complex64::from_polar(1.0, std::f64::consts::TAU / 3.0);
+        let mut same = 0;
+        for row in 0..bounds.1 {
+            for col in 0..bounds.0 {
+                let point = region.pixel_to_point((col, row)) * third;
+                let shade: u8 = params.shade(params.escape(point).map(f64::from));
+                if shade == pixels[row * bounds.0 + col] {
+                    same += 1;
+                }
+            }
+        }
+        assert!(
+            same * 100 >= pixels.len() * 97,
+            "{} of {}",
+            same,
+            pixels.len()
+        );
+
+        // Unlike the Mandelbrot set.
+        let mandel = |c: Complex64| Params::default().escape(c);
+        let c = cmplx!(0.3, 0.0);
+        assert_ne!(mandel(c), mandel(c * third));
+        assert_eq!(params.escape(c), params.escape(c * third));
+    }
+
+    #[test]
+    fn progress_counts_rows() {
+        let bounds = (20, 13);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.0), cmplx!(1.0, -1.0));
+        let params = Params::default();
+        let mut serial = vec![0u8; bounds.0 * bounds.1];
+        let mut pixels = serial.clone();
+        let progress = Progress::new(bounds.1);
+
+        render(&mut serial, &region, &params);
+        render_parallel_with_progress(&mut pixels, &region, &params, 4, &progress);
+
+        assert_eq!(pixels, serial);
+        assert_eq!(progress.rows(), bounds.1);
+        assert!(progress.is_done());
+    }
+
+    #[test]
+    fn adaptive_supersampling_of_edges() {
+        let bounds = (48, 24);
+        let region = Region::new(bounds, cmplx!(-1.0, 0.5), cmplx!(1.0, -0.5));
+        let plain = Params::default();
+        let full = Params {
+            supersample: 3,
+            ..plain
+        };
+        let adaptive = Params {
+            adaptive: true,
+            ..full
+        };
+        let shading = plain.shading(region.pixel_size(), None);
+        let edge = |point: Complex64| {
+            let (col, row) = region.point_to_pixel(point).unwrap();
+            let cols = 0..bounds.0;
+            let top = corner_samples::<u8>(&region, cols.clone(), row, &plain, shading);
+            let bottom = corner_samples::<u8>(&region, cols, row + 1, &plain, shading);
+            let samples = [&top[col], &top[col + 1], &bottom[col], &bottom[col + 1]];
+            is_edge(samples, 1)
+        };
+        // Inside the main cardioid and far outside, pixels get one sample,
+        // while on the boundary they get all of them.
+        assert!(!edge(cmplx!(-0.1, 0.05)));
+        assert!(!edge(cmplx!(0.9, 0.45)));
+        assert!(edge(cmplx!(0.25, 0.0)));
+        assert!(edge(cmplx!(-0.75, 0.02)));
+
+        let mut expected = [
+            vec![0u8; bounds.0 * bounds.1],
+            vec![0u8; bounds.0 * bounds.1],
+        ];
+        render(&mut expected[0], &region, &plain);
+        render(&mut expected[1], &region, &full);
+        for flip_y in [false, true] {
+            let region = Region { flip_y, ..region };
+            let [plain, full] = expected.clone().map(|mut pixels| {
+                if flip_y {
+                    pixels.reverse();
+                    pixels.chunks_mut(bounds.0).for_each(<[u8]>::reverse);
+                }
+                pixels
+            });
+            let mut pixels = vec![0u8; bounds.0 * bounds.1];
+            render(&mut pixels, &region, &adaptive);
+            let mut parallel = vec![0u8; pixels.len()];
+            render_parallel(&mut parallel, &region, &adaptive, 3);
+            assert_eq!(parallel, pixels);
+
+            // Each pixel comes out as in one render or the other.
+            assert!((0..pixels.len()).all(|i| pixels[i] == plain[i] || pixels[i] == full[i]));
+            assert!((0..pixels.len()).any(|i| pixels[i] != plain[i]));
+        }
+    }
+
+    #[test]
+    fn supersampling_blends_boundary() {
+        let bounds = (32, 24);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(1.0, -1.2));
+        let plain = Params {
+            limit: 4,
+            ..Params::default()
+        };
+        let sampled = Params {
+            supersample: 3,
+            ..plain
+        };
+        let mut aliased = vec![0u8; bounds.0 * bounds.1];
+        let mut smooth = aliased.clone();
+        render(&mut aliased, &region, &plain);
+        render(&mut smooth, &region, &sampled);
+
+        // Without supersampling only the five shades of counts 0 to 3 and
+        // the interior can appear.
+        let shades: Vec<u8> = (0..4)
+            .map(|count| brightness(count, 4))
+            .chain([0])
+            .collect();
+        assert!(aliased.iter().all(|p| shades.contains(p)));
+        assert!(smooth.iter().any(|p| !shades.contains(p)));
+
+        // One sample per axis is the plain render.
+        let mut once = aliased.clone();
+        let params = Params {
+            supersample: 1,
+            ..plain
+        };
+        render(&mut once, &region, &params);
+        assert_eq!(once, aliased);
+
+        // Grids too fine to count are capped rather than overflowing.
+        let huge = Params {
+            supersample: 70000,
+            ..plain
+        };
+        assert_eq!(huge.sample_count(), MAX_SUPERSAMPLE.pow(2));
+    }
+
+    #[test]
+    fn supersamples_average_in_linear_light() {
+        // Of the 2x2 samples of this pixel, the two at the origin are inside
+        // the set and black, the two at 3 escape at once and are white.
+        let (point, size) = (cmplx!(), cmplx!(6.0, -0.2));
+        let params = Params {
+            palette: Some(Palette::Grayscale),
+            supersample: 2,
+            ..Params::default()
+        };
+        let mut pixel = [0u8; 3];
+        params.paint_supersampled(point, size, None, &mut pixel);
+        assert_eq!(pixel, [188; 3]);
+        let naive = Params {
+            linear_averaging: Some(false),
+            ..params
+        };
+        naive.paint_supersampled(point, size, None, &mut pixel);
+        assert_eq!(pixel, [128; 3]);
+
+        // Grays are only averaged in linear light when asked to.
+        let gray = Params {
+            palette: None,
+            ..params
+        };
+        let mut sample = [0u8];
+        gray.paint_supersampled(point, size, None, &mut sample);
+        assert_eq!(sample, [128]);
+        let gray = Params {
+            linear_averaging: Some(true),
+            ..gray
+        };
+        gray.paint_supersampled(point, size, None, &mut sample);
+        assert_eq!(sample, [188]);
+        assert!(!params.is_reproducible() && naive.is_reproducible());
+    }
+
+    #[test]
+    fn jittered_samples() {
+        let bounds = (32, 24);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(1.0, -1.2));
+        let plain = Params {
+            limit: 4,
+            ..Params::default()
+        };
+        let jittered = Params {
+            jitter: true,
+            samples: 8,
+            seed: 3,
+            ..plain
+        };
+        let mut once = vec![0u8; bounds.0 * bounds.1];
+        render(&mut once, &region, &plain);
+        let mut first = once.clone();
+        render(&mut first, &region, &jittered);
+
+        // The same seed gives the same image, on any number of threads.
+        let mut again = vec![0u8; first.len()];
+        render_parallel(&mut again, &region, &jittered, 3);
+        assert_eq!(again, first);
+        let reseeded = Params {
+            seed: 4,
+            ..jittered
+        };
+        render(&mut again, &region, &reseeded);
+        assert_ne!(again, first);
+
+        // Pixels on boundaries between counts blend, off the grid too.
+        let shades: Vec<u8> = (0..4)
+            .map(|count| brightness(count, 4))
+            .chain([0])
+            .collect();
+        assert!(first.iter().any(|p| !shades.contains(p)));
+        let grid = Params {
+            supersample: 3,
+            ..plain
+        };
+        let mut gridded = vec![0u8; first.len()];
+        render(&mut gridded, &region, &grid);
+        assert_ne!(first, gridded);
+        // Pixels well away from any boundary are as without supersampling.
+        let same = (0..first.len()).filter(|&i| first[i] == once[i]).count();
+        assert!(same * 2 > first.len(), "{}", same);
+    }
+
+    #[test]
+    fn sixteen_bit_render() {
+        let bounds = (40, 30);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(1.0, -1.2));
+        let params = Params {
+            limit: 1000,
+            ..Params::default()
+        };
+        let mut narrow = vec![0u8; bounds.0 * bounds.1];
+        let mut wide = vec![0u16; bounds.0 * bounds.1];
+        render(&mut narrow, &region, &params);
+        render(&mut wide, &region, &params);
+
+        // The same image, at a finer scale.
+        for (&n, &w) in narrow.iter().zip(&wide) {
+            assert_eq!(n == 0, w == 0);
+            assert!(
+                (f64::from(n) - f64::from(w) / 257.0).abs() <= 1.0,
+                "{} {}",
+                n,
+                w
+            );
+        }
+        let distinct = |pixels: &[u16]| {
+            let mut values = pixels.to_vec();
+            values.sort_unstable();
+            values.dedup();
+            values.len()
+        };
+        let narrow: Vec<u16> = narrow.into_iter().map(u16::from).collect();
+        assert!(distinct(&wide) > distinct(&narrow));
+    }
+
+    #[test]
+    fn histogram_spreads_shades() {
+        let bounds = (60, 40);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(1.0, -1.2));
+        let linear = Params::default();
+        let equalized = Params {
+            color_mode: ColorMode::Histogram,
+            ..linear
+        };
+        let mut plain = vec![0u8; bounds.0 * bounds.1];
+        let mut ranked = plain.clone();
+        let mut parallel = plain.clone();
+        render(&mut plain, &region, &linear);
+        let stats = render(&mut ranked, &region, &equalized);
+        render_parallel(&mut parallel, &region, &equalized, 3);
+
+        // Most pixels escape within a few iterations, so linear shading
+        // leaves them nearly white; ranking spreads them over the range.
+        let mean = |pixels: &[u8]| {
+            let escaped: Vec<f64> = pixels
+                .iter()
+                .filter(|&&p| p != 0)
+                .map(|&p| f64::from(p))
+                .collect();
+            escaped.iter().sum::<f64>() / escaped.len() as f64
+        };
+        assert!(mean(&plain) > 220.0, "{}", mean(&plain));
+        assert!((100.0..160.0).contains(&mean(&ranked)), "{}", mean(&ranked));
+        assert_eq!(
+            plain.iter().filter(|&&p| p == 0).count() as u64,
+            stats.interior
+        );
+        assert_eq!(parallel, ranked);
+    }
+
+    #[test]
+    fn escape_counts_match_escape_time() {
+        let bounds = (17, 9);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(1.0, -1.2));
+        let params = Params {
+            limit: 50,
+            ..Params::default()
+        };
+        let counts = escape_counts(&region, &params, 4);
+
+        assert_eq!(counts.len(), bounds.0 * bounds.1);
+        for (i, &count) in counts.iter().enumerate() {
+            let point = region.pixel_to_point((i % bounds.0, i / bounds.0));
+            assert_eq!(count, escape_time(cmplx!(), point, 50).unwrap_or(50));
+        }
+        assert!(counts.contains(&50));
+    }
+
+    #[test]
+    fn distance_coloring() {
+        let bounds = (60, 40);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(1.0, -1.2));
+        let params = Params {
+            color_mode: ColorMode::Distance,
+            ..Params::default()
+        };
+        let mut pixels = vec![0u8; bounds.0 * bounds.1];
+        let stats = render(&mut pixels, &region, &params);
+
+        // Far from the set is white, darkening towards it.
+        assert_eq!(pixels[0], 255);
+        assert!(pixels.iter().any(|&p| p > 0 && p < 128));
+        assert!(stats.min > 0.0 && stats.max.is_finite());
+        let (near, far) = (
+            params.escape_value(cmplx!(0.3, 0.0)),
+            params.escape_value(cmplx!(1.0, 0.0)),
+        );
+        assert!(near.unwrap() < far.unwrap());
+    }
+
+    #[test]
+    fn interior_coloring() {
+        let bounds = (30, 20);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(1.0, -1.2));
+        let black = Params::default();
+        let mut plain = vec![0u8; bounds.0 * bounds.1];
+        render(&mut plain, &region, &black);
+
+        for interior in [InteriorColor::Last, InteriorColor::Mean] {
+            let params = Params { interior, ..black };
+            let mut pixels = vec![0u8; bounds.0 * bounds.1];
+            render(&mut pixels, &region, &params);
+
+            // Only the interior changes, and not all to black.
+            for (&p, &q) in plain.iter().zip(&pixels) {
+                assert!(p == q || p == 0, "{} {}", p, q);
+            }
+            assert!(pixels.iter().zip(&plain).any(|(&p, &q)| q == 0 && p > 0));
+        }
+    }
+
+    #[test]
+    fn strips_match_whole_render() {
+        let bounds = (16, 75);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(0.5, -1.2));
+        for params in [
+            Params::default(),
+            Params {
+                palette: Some(Palette::Fire),
+                color_mode: ColorMode::Histogram,
+                ..Params::default()
+            },
+        ] {
+            let len = bounds.0 * bounds.1 * params.channels();
+            let mut whole = vec![0u8; len];
+            let stats = render_parallel(&mut whole, &region, &params, 3);
+
+            let mut strips = Vec::new();
+            let mut strip_lens = Vec::new();
+            let progress = Progress::new(bounds.1 * params.passes());
+            let write = |strip: &[u8]| -> Result<(), ()> {
+                strip_lens.push(strip.len());
+                strips.extend_from_slice(strip);
+                Ok(())
+            };
+            let streamed = render_strips(&region, &params, 7, 3, Some(&progress), write);
+
+            assert_eq!(streamed.unwrap().pixels, stats.pixels);
+            assert!(progress.is_done());
+            let row_len = bounds.0 * params.channels();
+            assert_eq!(strip_lens.len(), 11);
+            assert_eq!(strip_lens[10], 5 * row_len);
+            assert_eq!(strips, whole);
+        }
+    }
+
+    #[test]
+    fn angle_coloring() {
+        let bounds = (40, 30);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(0.6, -1.2));
+        let params = Params {
+            color_mode: ColorMode::Angle,
+            ..Params::default()
+        };
+        assert_eq!(params.channels(), 3);
+        let mut pixels = vec![0u8; bounds.0 * bounds.1 * 3];
+        render(&mut pixels, &region, &params);
+
+        // Exterior pixels take many hues, not one shade, and each is fully
+        // saturated: one channel is off and another at full brightness.
+        let exterior: Vec<&[u8]> = pixels.chunks(3).filter(|p| p != &[0, 0, 0]).collect();
+        assert!(exterior.len() > bounds.0 * bounds.1 / 2);
+        let mut hues: Vec<&[u8]> = exterior.clone();
+        hues.sort();
+        hues.dedup();
+        assert!(hues.len() > 50, "{}", hues.len());
+        for p in exterior {
+            assert!(p.contains(&0) && p.contains(&255), "{:?}", p);
+        }
+    }
+
+    #[test]
+    fn stripe_coloring() {
+        let bounds = (40, 30);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(0.6, -1.2));
+        let stripes = |stripe_freq| {
+            let params = Params {
+                color_mode: ColorMode::Stripes,
+                stripe_freq,
+                ..Params::default()
+            };
+            let mut pixels = vec![0u8; bounds.0 * bounds.1];
+            render(&mut pixels, &region, &params);
+            pixels
+        };
+        let mut plain = vec![0u8; bounds.0 * bounds.1];
+        render(&mut plain, &region, &Params::default());
+
+        let (five, two) = (stripes(5.0), stripes(2.0));
+        assert_ne!(five, two);
+        for pixels in [five, two] {
+            let mut shades = pixels.clone();
+            shades.sort();
+            shades.dedup();
+            assert!(shades.len() > 50, "{}", shades.len());
+            // The interior is black as usual.
+            for (&p, &q) in pixels.iter().zip(&plain) {
+                assert!(q != 0 || p == 0);
+            }
+        }
+    }
+
+    #[test]
+    fn gamma_brightens_midtones() {
+        let bounds = (40, 30);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(0.6, -1.2));
+        for palette in [None, Some(Palette::Fire)] {
+            let render_with = |gamma| {
+                let params = Params {
+                    palette,
+                    gamma,
+                    ..Params::default()
+                };
+                let mut pixels = vec![0u8; bounds.0 * bounds.1 * params.channels()];
+                render(&mut pixels, &region, &params);
+                pixels
+            };
+            let (linear, bright) = (render_with(1.0), render_with(2.2));
+
+            for (&p, &q) in linear.iter().zip(&bright) {
+                match p {
+                    0 | 255 => assert_eq!(p, q),
+                    _ => assert!(q > p, "{} {}", p, q),
+                }
+            }
+            let mid = linear
+                .iter()
+                .position(|&p| (100..156).contains(&p))
+                .unwrap();
+            let expected = 255.0 * (f64::from(linear[mid]) / 255.0).powf(1.0 / 2.2);
+            assert_eq!(f64::from(bright[mid]), expected.round());
+        }
+    }
+
+    #[test]
+    fn log_scale_spreads_small_counts() {
+        let limit = 255.0;
+        for scale in [Scale::Linear, Scale::Log, Scale::Sqrt] {
+            assert_eq!(scale.apply(0.0, limit), 0.0);
+            assert!((scale.apply(limit, limit) - limit).abs() < 1e-9);
+        }
+        // log(1 + 5) / log(1 + 255) of the way to the limit.
+        assert_eq!(Scale::Log.apply(5.0, limit).round(), 82.0);
+
+        // Most of the full view escapes within a few iterations, which the
+        // linear scale leaves nearly white.
+        let bounds = (40, 30);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(0.6, -1.2));
+        let render_with = |scale| {
+            let params = Params {
+                scale,
+                ..Params::default()
+            };
+            let mut pixels = vec![0u8; bounds.0 * bounds.1];
+            render(&mut pixels, &region, &params);
+            pixels
+        };
+        let linear = render_with(Scale::Linear);
+        let mean = |pixels: &[u8]| pixels.iter().map(|&p| f64::from(p)).sum::<f64>() / 1200.0;
+        for scale in [Scale::Log, Scale::Sqrt] {
+            let scaled = render_with(scale);
+            for (&p, &q) in linear.iter().zip(&scaled) {
+                match p {
+                    0 | 255 => assert_eq!(p, q),
+                    _ => assert!(q <= p, "{:?} {} {}", scale, p, q),
+                }
+            }
+            assert!(mean(&scaled) < mean(&linear) - 10.0, "{:?}", scale);
+        }
+    }
+
+    #[test]
+    fn simd_matches_scalar() {
+        // A width that doesn't divide into lanes.
+        let bounds = (43, 30);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(0.6, -1.2));
+        for params in [
+            Params::default(),
+            Params {
+                limit: 1000,
+                palette: Some(Palette::Ocean),
+                color_mode: ColorMode::Histogram,
+                ..Params::default()
+            },
+            Params {
+                interior: InteriorColor::Mean,
+                ..Params::default()
+            },
+        ] {
+            let simd = Params {
+                simd: true,
+                ..params
+            };
+            assert!(simd.uses_simd());
+            let len = bounds.0 * bounds.1 * params.channels();
+            let (mut scalar, mut lanes) = (vec![0u8; len], vec![0u8; len]);
+
+            let stats = render(&mut scalar, &region, &params);
+            assert_eq!(render_parallel(&mut lanes, &region, &simd, 3), stats);
+            assert_eq!(lanes, scalar);
+        }
+    }
+
+    #[test]
+    fn newton_colors_roots() {
+        let params = Params {
+            fractal: Fractal::Newton,
+            ..Params::default()
+        };
+        assert_eq!(params.channels(), 3);
+        let mut pixel = [0u8; 3];
+        for (root, &(re, im)) in super::NEWTON_ROOTS.iter().enumerate() {
+            let point = cmplx!(re, im) * 1.01;
+            let (found, i) = params.newton(point).unwrap();
+            assert_eq!(found, root);
+            params.paint_sample(point, params.shading(cmplx!(), None), &mut pixel);
+            // The root's own channel is the brightest.
+            assert!(
+                pixel[root] > 0 && pixel.iter().all(|&c| c <= pixel[root]),
+                "{:?}",
+                pixel
+            );
+            assert_eq!(params.escape(point), Some(i));
+        }
+        // The derivative vanishes at the origin, so the method never
+        // converges there, and the point is painted black.
+        assert_eq!(params.escape(cmplx!()), None);
+        params.paint_sample(cmplx!(), params.shading(cmplx!(), None), &mut pixel);
+        assert_eq!(pixel, [0; 3]);
+    }
+}
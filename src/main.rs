@@ -1,170 +1,2030 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::io::IsTerminal;
+use std::ops::Range;
+use std::path::Path;
+use std::process::ExitCode;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crossbeam::thread::Scope;
-use image::codecs::png::PngEncoder;
-use image::{ColorType, ImageEncoder};
-use num_complex::Complex64;
-use std::fs::File;
-use std::io::Write;
+use mandelbrot::{
+    corners_from_center, default_threads, downsample, draw_contours, draw_grid, draw_orbit,
+    draw_scale_bar, escape_counts, escape_values, order_corners, parse_complex, parse_corners,
+    parse_dimensions, parse_pair, parse_polar, preserve_aspect, read_checkpoint,
+    render_parallel_with_progress, render_precise_with_progress, render_strips,
+    render_tiled_resumable, render_tiled_with_progress, render_window, simd, split_corners,
+    tiled_rows, validate, write_checkpoint, write_exr, write_image_with, write_raw, zoom_sequence,
+    BandTime, ColorMode, Complex64, Dither, Ditherer, Filter, Format, Fractal, GifFrames,
+    InteriorColor, Palette, Params, ParseError, PngStrips, Power, PreciseRegion, Precision, Preset,
+    Progress, Region, Sample, Scale, Stats, TiffCompression, TiledState, Trap, WriteOptions,
+    BAILOUT, DEFAULT_LIMIT, DEFAULT_SAMPLES, DEFAULT_STRIPE_FREQ, MAX_SUPERSAMPLE, STDOUT,
+};
+#[cfg(feature = "deep-zoom")]
+use mandelbrot::{render_deep_with_progress, DeepView};
 
-macro_rules! cmplx {
-    () => {
-        Complex64::new(0.0, 0.0)
-    };
+#[cfg(feature = "gui")]
+mod viewer;
 
-    ($re: expr) => {
-        Complex64::new($re, $re)
-    };
+const MSG1: &str =
+    "USAGE: mandelbrot <file> <pixels> <upper_left> <lower_right> [OPTIONS]\n       \
+                    mandelbrot <file> <pixels> --center RE,IM --zoom HALF_WIDTH [OPTIONS]\n       \
+                    mandelbrot <file> <pixels> --preset NAME [OPTIONS]\n       \
+                    mandelbrot <dir> <pixels> --center RE,IM --zoom-sequence START,END,FRAMES \
+                    [OPTIONS]\n       \
+                    mandelbrot --manifest FILE";
+const MSG2: &str = "mandel.png 4000x3000 -1.20,0.35 -1,0.20\n\
+                    The output format is chosen by the extension: png, jpg, bmp, ppm, webp,\n\
+                    tiff or ff (farbfeld), or exr for the escape value of each pixel as a float\n\
+                    instead of colors.\n\
+                    A <file> of - writes a PNG image to standard output.\n\
+                    <pixels> of a single number, like 800, means a square image.";
+const OPTIONS: &str = "\
+OPTIONS:
+    --iterations N              Iteration limit [default: 255, or the preset's]
+    --threads N                 Render threads, 0 for one per core [default: 0]
+    --tile-size N               Hand out square tiles of N pixels to the threads,
+                                rather than rows
+    --checkpoint FILE           Render in tiles, saving the image so far to FILE
+                                every so often and when stopped [default tile
+                                size: 64]
+    --resume FILE               Pick up the render saved to the checkpoint FILE,
+                                in its tiles, and go on saving to it
+    --tile X0,Y0,X1,Y1          Render and write only the pixels of columns X0
+                                up to X1 and rows Y0 up to Y1 of the image, so
+                                that renders of parts can be pieced together
+    --center RE,IM              Center of the view, instead of the corners
+    --zoom HALF_WIDTH           Half the width of the view, with --center
+    --region RE0,IM0,RE1,IM1    Two opposite corners of the view, in either
+                                order, instead of <upper_left> <lower_right>
+    --preset NAME               A well-known view: full, seahorse-valley,
+                                elephant-valley or triple-spiral; corners
+                                given as well take its place
+    --zoom-sequence START,END,FRAMES
+                                Write FRAMES numbered PNGs to <dir>, zooming
+                                from START to END at a steady speed; assemble
+                                them with e.g. ffmpeg -i <dir>/frame_%04d.png,
+                                or give a .gif file instead of <dir> to write
+                                an animated GIF in the colors of the palette
+    --frame-delay MS            Time each GIF frame is shown for [default: 40]
+    --deep-zoom                 Render the --center and --zoom view by
+                                perturbation, for zooms finer than 1e-13;
+                                plain escape counts of the Mandelbrot set only
+    --preserve-aspect           Fit the view's height to the aspect ratio of the image
+    --flip-y                    Point the imaginary axis down the image, mirroring it
+    --interactive               Show the view in a window instead: click to zoom
+                                in, right-click to zoom out, arrow keys to pan,
+                                s to write <file>, q to quit (gui feature, X11)
+    --fractal NAME              mandelbrot, burning-ship, tricorn, celtic,
+                                heart, phoenix, newton for the basins of
+                                z^3 - 1, or buddhabrot for where the orbits
+                                of escaping points go
+    --phoenix-p RE,IM           The p of the phoenix's z^2 + c + p*z', where z'
+                                is the point before z [default: -0.5,0]
+    --samples N                 Points sampled per pixel for the buddhabrot,
+                                more for a smoother image [default: 20], or
+                                placed at random in each pixel of the others
+                                and averaged, instead of --supersample's grid
+    --seed N                    Seed of those random points and of the random
+                                palette [default: 0]
+    --power D                   Exponent of the map [default: 2]
+    --julia RE,IM               Render the Julia set for this constant
+    --julia-polar R,THETA       Or for the constant R*e^(i*THETA), THETA being
+                                in radians, or in degrees if it ends in deg
+    --z0 RE,IM                  Start the orbits of the Mandelbrot family here
+                                instead of at zero [default: 0,0]
+    --bailout R                 Escape radius [default: 2]
+    --precision f32|f64         Float type to iterate in [default: f64]
+    --precision-bits N          Iterate in fixed point with N bits of fraction
+                                instead, slowly but exactly however deep the
+                                view, reading the corners or --center to as
+                                many bits; plain escape counts only
+    --smooth                    Shade with continuous escape values
+    --palette NAME              grayscale, fire or ocean, for RGB output, or
+                                random for a gradient drawn from --seed
+    --palette-file FILE         Instead of --palette, a gradient with one stop
+                                per line: position from 0 to 1, then red,
+                                green and blue from 0 to 255
+    --color-mode MODE           linear, histogram, distance, angle for RGB hues
+                                by the angle of z on escape, stripes for the
+                                stripe average of the orbit, or orbit-trap for
+                                how close it comes to --trap [default: linear]
+    --scale SCALE               linear, log or sqrt: how escape counts map to
+                                shades with --color-mode linear, log and sqrt
+                                bringing out small counts [default: linear]
+    --cycle-period P            Run through the grays or the palette every P
+                                iterations, rather than once up to the limit
+    --cycle-offset O            Start the cycle O iterations along it, to cycle
+                                the colors from frame to frame [default: 0]
+    --stripe-freq N             Stripes around the origin with --color-mode
+                                stripes [default: 5]
+    --trap SHAPE                point for the origin or line for the real axis,
+                                with --color-mode orbit-trap [default: point]
+    --interior-color MODE       black, last or mean: color the inside of the
+                                set by |z| at the end of the orbit or by its
+                                mean over the orbit [default: black]
+    --gamma G                   Write each channel v from 0 to 1 as v^(1/G),
+                                so that above 1 brightens midtones [default: 1]
+    --invert                    Swap black and white, making the set white, or
+                                run the palette the other way
+    --alpha-interior            Write RGBA with the inside of the set
+                                transparent (PNG, TIFF and farbfeld only)
+    --supersample N             Samples per pixel along each axis, up to 256
+                                [default: 1]
+    --anti-alias-adaptive       Only supersample pixels on edges, where colors
+                                change sharply [default --supersample: 3]
+    --antialias-gamma-correct on|off
+                                Average supersamples in linear light, so edges
+                                don't darken [default: on for RGB output]
+    --render-scale S            Render S times wider and taller, then shrink
+                                the image back down [default: 1]
+    --downsample-filter NAME    box, triangle or lanczos, how --render-scale
+                                shrinks the image [default: box]
+    --simd                      Iterate four points at once with AVX, for
+                                plain escape counts of the Mandelbrot set
+    --bit-reproducible          Render bit for bit as on any other platform,
+                                one point at a time in f64 with no --simd,
+                                refusing what goes through the math library
+    --depth 8|16                Bits per channel, 16 only for PNG, TIFF and
+                                farbfeld [default: 8]
+    --dither MODE               ordered or floyd-steinberg, to round 16-bit
+                                colors down to 8 bits without banding
+    --quality N                 JPEG or WebP quality from 1 to 100 [default: 90]
+    --lossless                  Keep every bit of WebP pixels, ignoring --quality
+    --tiff-compression MODE     none, lzw or deflate [default: lzw]
+    --raw-output FILE           Also write the escape counts to FILE, as CSV
+                                if it ends in .csv and as binary otherwise
+    --metadata-json FILE        Also describe the render and its escape
+                                statistics in FILE, as JSON
+    --strip-rows N              Render and write N rows at a time, so that
+                                only they are held in memory (PNG only)
+    --max-memory MB             Render in strips, as many rows at a time as fit
+                                in MB megabytes, if the image doesn't fit whole
+    --preview                   First write a quick render at 1/8 of the size
+                                to <file> with -preview before its extension
+    --preview-only              Write only the preview
+    --seed-orbit RE,IM          Draw the orbit of the point RE,IM over the image,
+                                as dots joined by lines
+    --grid STEP                 Draw faint lines at multiples of STEP along
+                                both axes, and the axes themselves more strongly
+    --contours STEP             Darken the lines outside the set where the smooth
+                                escape value passes a multiple of STEP
+    --scale-bar                 Draw a bar of a round length on the plane in the
+                                lower left corner, labeled with that length
+    --stats                     Print escape statistics when done
+    --profile-bands             Print how long each render thread spent on its
+                                bands of rows, to see how evenly work is spread
+    --quiet                     Don't report progress
+    --manifest FILE             Render one job per line of FILE, each line
+                                holding the arguments of one invocation
+    --validate                  Check that a small reference image renders bit
+                                for bit as expected, and exit
+    -h, --help                  Print this help
+    -V, --version               Print the version";
 
-    ($re: expr, $im: expr) => {
-        Complex64::new($re, $im)
-    };
+/// How many times smaller than the image a preview is along each axis.
+const PREVIEW_SCALE: usize = 8;
+
+/// Set once Ctrl-C is pressed, after which renders stop where they are.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// How often the progress line on stderr is updated.
+const REPORT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The bytes in each megabyte of `--max-memory`.
+const MEGABYTE: f64 = 1024.0 * 1024.0;
+
+/// How often a checkpointed render is saved, at the most.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The side of the tiles of checkpointed renders, unless one is given.
+const CHECKPOINT_TILE_SIZE: usize = 64;
+
+/// Everything that can make the command line tool fail.
+#[derive(Debug)]
+enum CliError {
+    /// The arguments don't match the usage; carries the program name.
+    Usage(String),
+    /// An option was given without its value; carries the option.
+    MissingValue(String),
+    /// An option isn't one we know; carries the option.
+    Unknown(String),
+    /// An argument could not be parsed; names what was being parsed.
+    Parse(&'static str),
+    /// A pair or complex number could not be parsed; names what was being
+    /// parsed and its two halves.
+    Pair(&'static str, [&'static str; 2], ParseError),
+    /// The image dimensions are zero or too large to allocate.
+    Dimensions(usize, usize),
+    /// The image would be written to standard output, which is a terminal.
+    Terminal,
+    /// Writing the output image failed.
+    Io(image::ImageError),
+    /// Reading the named file failed.
+    Read(String, std::io::Error),
+    /// Writing the named file, other than the image, failed.
+    Write(String, std::io::Error),
+    /// Some jobs of a manifest failed; carries how many, out of how many.
+    Jobs(usize, usize),
+    /// Ctrl-C stopped the render, after which what was done got written.
+    Interrupted,
+    /// The reference image of `--validate` rendered differently.
+    Mismatch,
+    /// There's no X display to open the `--interactive` window on.
+    #[cfg(feature = "gui")]
+    Display,
 }
 
-fn main() {
-    const THREADS: u8 = 8;
-    const MSG1: &'static str = "USAGE: mandelbrot <file> <pixels> <upper_left> <lower_right>";
-    const MSG2: &'static str = "mandel.png 4000x3000 -1.20,0.35 -1,0.20";
+impl CliError {
+    /// The process exit status reported for this error.
+    fn exit_code(&self) -> u8 {
+        match self {
+            CliError::Usage(_) | CliError::MissingValue(_) | CliError::Unknown(_) => 2,
+            CliError::Terminal => 2,
+            CliError::Parse(_) | CliError::Pair(..) | CliError::Dimensions(..) => 3,
+            CliError::Io(_) | CliError::Read(..) | CliError::Write(..) => 4,
+            CliError::Jobs(..) => 5,
+            CliError::Mismatch => 6,
+            CliError::Interrupted => 130,
+            #[cfg(feature = "gui")]
+            CliError::Display => 4,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::Usage(pname) => {
+                write!(f, "{}\n{}\nEXAMPLE: {} {}", MSG1, OPTIONS, pname, MSG2)
+            }
+            CliError::MissingValue(name) => write!(f, "Option {} needs a value; see --help", name),
+            CliError::Unknown(name) => write!(f, "Unrecognized option {}; see --help", name),
+            CliError::Parse(what) => write!(f, "Error parsing {}", what),
+            CliError::Pair(what, halves, err) => match err {
+                ParseError::MissingSeparator => write!(f, "Error parsing {}: {}", what, err),
+                ParseError::InvalidLeft => write!(f, "Error parsing the {} of {}", halves[0], what),
+                ParseError::InvalidRight => {
+                    write!(f, "Error parsing the {} of {}", halves[1], what)
+                }
+            },
+            CliError::Dimensions(w, h) if *w == 0 || *h == 0 => {
+                write!(f, "Image dimensions {}x{} are empty", w, h)
+            }
+            CliError::Dimensions(w, h) => write!(f, "Image dimensions {}x{} are too large", w, h),
+            CliError::Terminal => write!(f, "Not writing an image to a terminal; redirect it"),
+            CliError::Io(err) => write!(f, "Error writing image: {}", err),
+            CliError::Read(file, err) => write!(f, "Error reading {}: {}", file, err),
+            CliError::Write(file, err) => write!(f, "Error writing {}: {}", file, err),
+            CliError::Jobs(failed, total) => write!(f, "{} of {} jobs failed", failed, total),
+            CliError::Interrupted => write!(f, "Interrupted; wrote the rows rendered so far"),
+            CliError::Mismatch => write!(
+                f,
+                "The reference image rendered differently; this build may not render images \
+                 bit for bit like others"
+            ),
+            #[cfg(feature = "gui")]
+            CliError::Display => write!(f, "Can't open a window without a 24-bit X display"),
+        }
+    }
+}
+
+impl std::error::Error for CliError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CliError::Io(err) => Some(err),
+            CliError::Read(_, err) | CliError::Write(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
 
-    let args: Vec<String> = std::env::args().collect();
+impl From<image::ImageError> for CliError {
+    fn from(err: image::ImageError) -> Self {
+        CliError::Io(err)
+    }
+}
 
-    if args.len() != 5 {
-        let pname: &str = &args[0][..];
-        writeln!(std::io::stderr(), "{}\nEXAMPLE: {} {}", MSG1, pname, MSG2).unwrap();
-        std::process::exit(1);
+fn main() -> ExitCode {
+    catch_interrupts();
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::from(err.exit_code())
+        }
     }
+}
 
-    let bounds = parse_pair(&args[2], 'x').expect("Error parsing image dimensions");
-    let u_l = parse_complex(&args[3]).expect("Error parsing upper left corner point");
-    let l_r = parse_complex(&args[4]).expect("Error parsing lower right corner point");
-    let mut pixels = vec![0; bounds.0 * bounds.1];
+/// Make Ctrl-C set `INTERRUPTED` rather than kill the process, the first
+/// time it's pressed.
+#[cfg(unix)]
+fn catch_interrupts() {
+    extern "C" fn on_interrupt(_: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::Relaxed);
+        // SAFETY: signal() may be called from a signal handler.
+        unsafe { libc::signal(libc::SIGINT, libc::SIG_DFL) };
+    }
+    let handler = on_interrupt as extern "C" fn(libc::c_int);
+    // SAFETY: the handler only stores to an atomic and calls signal(),
+    // both of which are safe in a signal handler.
+    unsafe { libc::signal(libc::SIGINT, handler as libc::sighandler_t) };
+}
 
-    render(&mut pixels, bounds, u_l, l_r);
-    write_image(&args[1], &pixels, bounds).expect("Error writing PNG file");
+/// Elsewhere Ctrl-C kills the process as usual.
+#[cfg(not(unix))]
+fn catch_interrupts() {}
 
-    let rows_per_band = bounds.1 / usize::from(THREADS + 1 as u8);
+/// Whether Ctrl-C has been pressed.
+fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
 
-    {
-        let bands: Vec<&mut [u8]> = pixels.chunks_mut(rows_per_band * bounds.0).collect();
-
-        crossbeam::scope(|spawner| {
-            for (i, band) in bands.into_iter().enumerate() {
-                let top = rows_per_band * i;
-                let height = band.len() / bounds.0;
-                let band_bounds = (bounds.0, height);
-                let band_upper_left = pixel_to_point(bounds, (0, top), u_l, l_r);
-                let band_lower_right = pixel_to_point(bounds, (bounds.0, top + height), u_l, l_r);
-                spawner.spawn(move |_: &Scope| {
-                    render(band, band_bounds, band_upper_left, band_lower_right);
-                });
-            }
-        })
-        .unwrap();
+fn run() -> Result<(), CliError> {
+    let mut args: Vec<String> = std::env::args().collect();
+    if take_flag(&mut args, "--help") || take_flag(&mut args, "-h") {
+        println!("Render the Mandelbrot set and its relatives to an image file.\n");
+        println!("{}\n{}\nEXAMPLE: {} {}", MSG1, OPTIONS, args[0], MSG2);
+        return Ok(());
+    }
+    if take_flag(&mut args, "--version") || take_flag(&mut args, "-V") {
+        println!("mandelbrot {}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
     }
+    if take_flag(&mut args, "--validate") {
+        if !validate() {
+            return Err(CliError::Mismatch);
+        }
+        println!("The reference image rendered as expected");
+        return Ok(());
+    }
+    if let Some(manifest) = take_option(&mut args, "--manifest")? {
+        if args.len() != 1 {
+            return Err(CliError::Usage(args[0].clone()));
+        }
+        return run_manifest(&args[0], &manifest);
+    }
+
+    run_job(args)
 }
 
-fn escape_time(c: Complex64, limit: u32) -> Option<u32> {
-    let mut z = cmplx!();
-    for i in 0..limit {
-        z = z * z + c;
-        if z.norm_sqr() > 4.0 {
-            return Some(i);
+/// Render every job listed in the file `manifest` in turn, then report how
+/// each one went. A failed job doesn't stop the ones after it, but Ctrl-C
+/// does.
+fn run_manifest(pname: &str, manifest: &str) -> Result<(), CliError> {
+    let text = std::fs::read_to_string(manifest)
+        .map_err(|err| CliError::Read(manifest.to_string(), err))?;
+    let jobs = manifest_jobs(pname, &text);
+    let mut results = Vec::new();
+    for args in &jobs {
+        results.push(run_job(args.clone()));
+        if interrupted() {
+            break;
         }
     }
 
-    None
+    let mut failed = 0;
+    for (args, result) in jobs.iter().zip(&results) {
+        let output = args.get(1).map_or("", String::as_str);
+        match result {
+            Ok(()) => eprintln!("{}: ok", output),
+            // The full usage text would drown out the other jobs.
+            Err(CliError::Usage(_)) => {
+                eprintln!("{}: wrong number of arguments; see --help", output);
+                failed += 1;
+            }
+            Err(err) => {
+                eprintln!("{}: {}", output, err);
+                failed += 1;
+            }
+        }
+    }
+
+    match failed {
+        _ if interrupted() => Err(CliError::Interrupted),
+        0 => Ok(()),
+        _ => Err(CliError::Jobs(failed, jobs.len())),
+    }
 }
 
-/// Parse the string `s` as a coordinate pair like `"800x600"` or `"1.0, 0.5"`.
-fn parse_pair<T: FromStr>(s: &str, separator: char) -> Option<(T, T)> {
-    match s.find(separator) {
+/// The arguments of each job in the text of a manifest: one job per line,
+/// split on whitespace, with `pname` in front. Blank lines and lines
+/// starting with `#` are skipped.
+fn manifest_jobs(pname: &str, text: &str) -> Vec<Vec<String>> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let words = line.split_whitespace().map(String::from);
+            std::iter::once(pname.to_string()).chain(words).collect()
+        })
+        .collect()
+}
+
+/// Render the single job described by the command line `args`.
+fn run_job(mut args: Vec<String>) -> Result<(), CliError> {
+    let preset = match take_option(&mut args, "--preset")? {
+        Some(name) => Some(Preset::from_name(&name).ok_or(CliError::Parse("preset name"))?),
         None => None,
-        Some(idx) => match (T::from_str(&s[0..idx]), T::from_str(&s[idx + 1..])) {
-            (Ok(left), Ok(right)) => Some((left, right)),
-            _ => None,
-        },
+    };
+    let limit = parse_option(&mut args, "--iterations", "iteration limit")?;
+    let limit = limit.unwrap_or(preset.map_or(DEFAULT_LIMIT, Preset::limit));
+    if limit == 0 {
+        return Err(CliError::Parse("iteration limit"));
+    }
+    let threads = parse_option(&mut args, "--threads", "thread count")?.unwrap_or(0);
+    let threads = if threads == 0 {
+        default_threads()
+    } else {
+        threads
+    };
+    let tile_size = parse_option(&mut args, "--tile-size", "tile size")?;
+    if tile_size == Some(0) {
+        return Err(CliError::Parse("tile size"));
+    }
+    let checkpoint = take_option(&mut args, "--checkpoint")?;
+    let resume = take_option(&mut args, "--resume")?;
+    let checkpoints = checkpoint.is_some() || resume.is_some();
+    let tile = take_option(&mut args, "--tile")?;
+    let julia = match take_option(&mut args, "--julia")? {
+        Some(c) => Some(parse_complex(&c).map_err(complex_error("the Julia constant"))?),
+        None => None,
+    };
+    let julia =
+        match take_option(&mut args, "--julia-polar")? {
+            Some(_) if julia.is_some() => return Err(CliError::Usage(args[0].clone())),
+            Some(c) => Some(parse_polar(&c).map_err(|err| {
+                CliError::Pair("the Julia constant", ["modulus", "argument"], err)
+            })?),
+            None => julia,
+        };
+    let z0 = match take_option(&mut args, "--z0")? {
+        Some(_) if julia.is_some() => {
+            return Err(CliError::Parse(
+                "--z0, which doesn't apply to Julia sets, whose orbits start at each pixel",
+            ))
+        }
+        Some(z) => Some(parse_complex(&z).map_err(complex_error("the starting point"))?),
+        None => None,
+    };
+    let smooth = take_flag(&mut args, "--smooth");
+    let simd = take_flag(&mut args, "--simd");
+    if simd && !simd::is_available() {
+        eprintln!("Note: this CPU lacks AVX, so --simd iterates one point at a time");
+    }
+    let bit_reproducible = take_flag(&mut args, "--bit-reproducible");
+    let seed = parse_option(&mut args, "--seed", "seed")?.unwrap_or(0);
+    let palette = take_option(&mut args, "--palette")?;
+    let random_palette = palette.as_deref() == Some("random");
+    let palette = match palette {
+        Some(name) if name == "random" => Some(Palette::random(seed)),
+        Some(name) => Some(Palette::from_name(&name).ok_or(CliError::Parse("palette name"))?),
+        None => None,
+    };
+    let palette = match take_option(&mut args, "--palette-file")? {
+        Some(_) if palette.is_some() => return Err(CliError::Usage(args[0].clone())),
+        Some(file) => {
+            let text = std::fs::read_to_string(&file).map_err(|err| CliError::Read(file, err))?;
+            Some(Palette::parse(&text).ok_or(CliError::Parse("palette file"))?)
+        }
+        None => palette,
+    };
+    let mut options = WriteOptions::default();
+    if let Some(quality) = parse_option(&mut args, "--quality", "quality")? {
+        if !(1..=100).contains(&quality) {
+            return Err(CliError::Parse("quality, which runs from 1 to 100"));
+        }
+        options.quality = quality;
+    }
+    options.lossless = take_flag(&mut args, "--lossless");
+    if let Some(name) = take_option(&mut args, "--tiff-compression")? {
+        options.tiff_compression =
+            TiffCompression::from_name(&name).ok_or(CliError::Parse("TIFF compression"))?;
+    }
+    let center_text = take_option(&mut args, "--center")?;
+    let center = match &center_text {
+        Some(c) => Some(parse_complex(c).map_err(complex_error("the center point"))?),
+        None => None,
+    };
+    let zoom: Option<f64> = parse_option(&mut args, "--zoom", "zoom")?;
+    if zoom.is_some_and(|zoom| !(zoom > 0.0 && zoom.is_finite())) {
+        return Err(CliError::Parse("zoom"));
+    }
+    let region_text = take_option(&mut args, "--region")?;
+    let corners =
+        match &region_text {
+            Some(s) if s.split(',').count() != 4 => {
+                return Err(CliError::Parse(
+                    "region, which needs four numbers: RE0,IM0,RE1,IM1",
+                ))
+            }
+            Some(s) => Some(parse_corners(s).map_err(|err| {
+                CliError::Pair("the region", ["first corner", "second corner"], err)
+            })?),
+            None => None,
+        };
+    let sequence = match take_option(&mut args, "--zoom-sequence")? {
+        Some(s) => Some(parse_sequence(&s).ok_or(CliError::Parse("zoom sequence"))?),
+        None => None,
+    };
+    let frame_delay = parse_option(&mut args, "--frame-delay", "frame delay")?.unwrap_or(40);
+    let frame_delay = Duration::from_millis(frame_delay);
+    #[cfg(feature = "deep-zoom")]
+    let deep_zoom = take_flag(&mut args, "--deep-zoom");
+    #[cfg(not(feature = "deep-zoom"))]
+    let deep_zoom = false;
+    let precision_bits = parse_option(&mut args, "--precision-bits", "precision bits")?;
+    if precision_bits == Some(0) {
+        return Err(CliError::Parse("precision bits"));
+    }
+    let keep_aspect = take_flag(&mut args, "--preserve-aspect");
+    #[cfg(feature = "gui")]
+    let interactive = take_flag(&mut args, "--interactive");
+    #[cfg(feature = "gui")]
+    if interactive && (sequence.is_some() || deep_zoom || precision_bits.is_some()) {
+        return Err(CliError::Parse(
+            "--interactive, which can't be used with --zoom-sequence, --deep-zoom or \
+             --precision-bits",
+        ));
+    }
+    let flip_y = take_flag(&mut args, "--flip-y");
+    let precision = match take_option(&mut args, "--precision")? {
+        Some(name) => Precision::from_name(&name).ok_or(CliError::Parse("precision"))?,
+        None => Precision::F64,
+    };
+    let print_stats = take_flag(&mut args, "--stats");
+    let preview_only = take_flag(&mut args, "--preview-only");
+    let preview = take_flag(&mut args, "--preview") || preview_only;
+    let raw_output = take_option(&mut args, "--raw-output")?;
+    let metadata_json = take_option(&mut args, "--metadata-json")?;
+    let quiet = take_flag(&mut args, "--quiet");
+    let profile_bands = take_flag(&mut args, "--profile-bands");
+    let seed_orbit = match take_option(&mut args, "--seed-orbit")? {
+        Some(point) => Some(parse_complex(&point).map_err(complex_error("the orbit's point"))?),
+        None => None,
+    };
+    let grid = parse_option(&mut args, "--grid", "grid step")?;
+    let contours = parse_option(&mut args, "--contours", "contour step")?;
+    if contours.is_some_and(|step: f64| !(step > 0.0 && step.is_finite())) {
+        return Err(CliError::Parse("contour step"));
+    }
+    let scale_bar = take_flag(&mut args, "--scale-bar");
+    if grid.is_some_and(|step: f64| !(step > 0.0 && step.is_finite())) {
+        return Err(CliError::Parse("grid step"));
+    }
+    let strip_rows = parse_option(&mut args, "--strip-rows", "strip height")?;
+    if strip_rows == Some(0) {
+        return Err(CliError::Parse("strip height"));
+    }
+    let max_memory = parse_option(&mut args, "--max-memory", "memory budget")?;
+    if max_memory.is_some_and(|mb: f64| !(mb > 0.0 && mb.is_finite())) {
+        return Err(CliError::Parse("memory budget"));
+    }
+    let supersample = parse_option(&mut args, "--supersample", "supersampling factor")?;
+    let adaptive = take_flag(&mut args, "--anti-alias-adaptive");
+    let sample_grid = supersample.is_some() || adaptive;
+    let supersample = supersample.unwrap_or(if adaptive { 3 } else { 1 });
+    if !(1..=MAX_SUPERSAMPLE).contains(&supersample) {
+        return Err(CliError::Parse("supersampling factor"));
+    }
+    let render_scale = parse_option(&mut args, "--render-scale", "render scale")?.unwrap_or(1);
+    if render_scale == 0 {
+        return Err(CliError::Parse("render scale"));
+    }
+    let downsample_filter = match take_option(&mut args, "--downsample-filter")? {
+        Some(_) if render_scale == 1 => {
+            return Err(CliError::Parse(
+                "--downsample-filter, which only applies to --render-scale",
+            ))
+        }
+        Some(name) => Filter::from_name(&name).ok_or(CliError::Parse("downsample filter"))?,
+        None => Filter::Box,
+    };
+    let linear_averaging = match take_option(&mut args, "--antialias-gamma-correct")?.as_deref() {
+        Some("on") => Some(true),
+        Some("off") => Some(false),
+        Some(_) => {
+            return Err(CliError::Parse(
+                "--antialias-gamma-correct, which is on or off",
+            ))
+        }
+        None => None,
+    };
+    let color_mode = match take_option(&mut args, "--color-mode")? {
+        Some(name) => ColorMode::from_name(&name).ok_or(CliError::Parse("color mode"))?,
+        None => ColorMode::Linear,
+    };
+    let scale = match take_option(&mut args, "--scale")? {
+        Some(name) => Scale::from_name(&name).ok_or(CliError::Parse("scale"))?,
+        None => Scale::Linear,
+    };
+    let cycle_period = parse_option(&mut args, "--cycle-period", "cycle period")?;
+    if cycle_period.is_some_and(|period: f64| !(period > 0.0 && period.is_finite())) {
+        return Err(CliError::Parse("cycle period"));
+    }
+    let cycle_offset = parse_option(&mut args, "--cycle-offset", "cycle offset")?;
+    if cycle_offset.is_some_and(|offset: f64| !offset.is_finite()) {
+        return Err(CliError::Parse("cycle offset"));
+    }
+    if cycle_offset.is_some() && cycle_period.is_none() {
+        return Err(CliError::Parse(
+            "--cycle-offset, which only applies with --cycle-period",
+        ));
+    }
+    let gamma: f64 = parse_option(&mut args, "--gamma", "gamma")?.unwrap_or(1.0);
+    if !(gamma > 0.0 && gamma.is_finite()) {
+        return Err(CliError::Parse("gamma"));
+    }
+    let invert = take_flag(&mut args, "--invert");
+    let alpha_interior = take_flag(&mut args, "--alpha-interior");
+    let stripe_freq = parse_option(&mut args, "--stripe-freq", "stripe frequency")?;
+    let stripe_freq = stripe_freq.unwrap_or(DEFAULT_STRIPE_FREQ);
+    if !stripe_freq.is_finite() {
+        return Err(CliError::Parse("stripe frequency"));
+    }
+    let trap = match take_option(&mut args, "--trap")? {
+        Some(_) if color_mode != ColorMode::OrbitTrap => {
+            return Err(CliError::Parse(
+                "--trap, which only applies to --color-mode orbit-trap",
+            ))
+        }
+        Some(name) => Trap::from_name(&name).ok_or(CliError::Parse("trap shape"))?,
+        None => Trap::Point,
+    };
+    let interior = match take_option(&mut args, "--interior-color")? {
+        Some(name) => InteriorColor::from_name(&name).ok_or(CliError::Parse("interior color"))?,
+        None => InteriorColor::Black,
+    };
+    let depth = parse_option(&mut args, "--depth", "bit depth")?.unwrap_or(8);
+    if depth != 8 && depth != 16 {
+        return Err(CliError::Parse("bit depth"));
+    }
+    let dither = match take_option(&mut args, "--dither")? {
+        Some(name) => Dither::from_name(&name).ok_or(CliError::Parse("dithering mode"))?,
+        None => Dither::None,
+    };
+    if dither != Dither::None && depth != 8 {
+        return Err(CliError::Parse(
+            "dithering mode, which only applies to 8-bit images",
+        ));
+    }
+    let fractal = match take_option(&mut args, "--fractal")? {
+        Some(name) => Fractal::from_name(&name).ok_or(CliError::Parse("fractal name"))?,
+        None => Fractal::Mandelbrot,
+    };
+    let phoenix_p = match take_option(&mut args, "--phoenix-p")? {
+        Some(p) => Some(parse_complex(&p).map_err(complex_error("the phoenix's p"))?),
+        None => None,
+    };
+    let fractal = match (fractal, phoenix_p) {
+        (Fractal::Phoenix(_), Some(p)) => Fractal::Phoenix(p),
+        (_, Some(_)) => {
+            return Err(CliError::Parse(
+                "--phoenix-p, which only applies to --fractal phoenix",
+            ))
+        }
+        (fractal, None) => fractal,
+    };
+    let power = match take_option(&mut args, "--power")? {
+        Some(d) => Power::parse(&d).ok_or(CliError::Parse("power"))?,
+        None => Power::default(),
+    };
+    if color_mode == ColorMode::Distance
+        && ((fractal, power) != (Fractal::Mandelbrot, Power::Int(2)) || z0.is_some())
+    {
+        return Err(CliError::Parse(
+            "color mode, distance only works for the quadratic map from zero",
+        ));
+    }
+    let newton = fractal == Fractal::Newton;
+    if newton
+        && (julia.is_some()
+            || z0.is_some()
+            || power != Power::Int(2)
+            || color_mode != ColorMode::Linear)
+    {
+        return Err(CliError::Parse(
+            "fractal name, newton has no --julia, --z0, --power or --color-mode",
+        ));
+    }
+    let buddhabrot = fractal == Fractal::Buddhabrot;
+    let escape_shading = smooth || supersample > 1 || interior != InteriorColor::Black;
+    if buddhabrot && (julia.is_some() || color_mode != ColorMode::Linear || escape_shading) {
+        return Err(CliError::Parse(
+            "fractal name, buddhabrot has no --julia, --color-mode, --smooth, --supersample \
+             or --interior-color",
+        ));
+    }
+    let samples = parse_option(&mut args, "--samples", "sample count")?;
+    let jitter = samples.is_some() && !buddhabrot;
+    if jitter && sample_grid {
+        return Err(CliError::Parse(
+            "--samples, which places points at random instead of on the grid of \
+             --supersample or --anti-alias-adaptive",
+        ));
+    }
+    let samples = samples.unwrap_or(DEFAULT_SAMPLES);
+    if samples == 0 {
+        return Err(CliError::Parse("sample count"));
+    }
+    if scale != Scale::Linear && (newton || color_mode != ColorMode::Linear) {
+        return Err(CliError::Parse(
+            "scale, which only applies to --color-mode linear",
+        ));
+    }
+    let cycles = color_mode == ColorMode::Linear && scale == Scale::Linear;
+    if cycle_period.is_some() && (newton || buddhabrot || !cycles) {
+        return Err(CliError::Parse(
+            "--cycle-period, which only applies to escape values under --color-mode linear \
+             and --scale linear, not newton or buddhabrot",
+        ));
+    }
+    if invert && (newton || color_mode == ColorMode::Angle) {
+        return Err(CliError::Parse(
+            "--invert, which has no grays or palette to flip in the hues of newton or \
+             --color-mode angle",
+        ));
+    }
+    let bailout = parse_option(&mut args, "--bailout", "bailout radius")?.unwrap_or(BAILOUT);
+    // Orbits are compared against the square of the radius, which has to
+    // stay finite for anything to escape.
+    if !bailout.is_finite() || bailout < BAILOUT || bailout * bailout == f64::INFINITY {
+        return Err(CliError::Parse("bailout radius"));
     }
-}
 
-/// Parse a pair of floating-point numbers seperated by a comma as a complex number.
-fn parse_complex(s: &str) -> Option<Complex64> {
-    match parse_pair::<f64>(s, ',') {
-        Some((re, im)) => Some(cmplx!(re, im)),
+    // Corner points may start with a minus sign, but never with two.
+    if let Some(option) = args.iter().skip(1).find(|arg| arg.starts_with("--")) {
+        return Err(CliError::Unknown(option.clone()));
+    }
+    // A preset's corners can be given explicitly instead.
+    let expected: &[usize] = match (center.or(corners.map(|c| c.0)), preset) {
+        (Some(_), _) => &[3],
+        (None, Some(_)) => &[3, 5],
+        (None, None) => &[5],
+    };
+    let zooms = zoom.is_some() || sequence.is_some();
+    let sidecars = raw_output.is_some() || metadata_json.is_some();
+    let conflict = sequence.is_some() && (zoom.is_some() || sidecars);
+    let conflict = conflict || (center.is_some() && preset.is_some());
+    let conflict = conflict || (center.is_some() && corners.is_some());
+    let conflict = conflict || (sequence.is_some() && preview);
+    let stdout = args.get(1).is_some_and(|file| file == STDOUT);
+    let conflict = conflict || (stdout && (sequence.is_some() || preview));
+    if !expected.contains(&args.len()) || center.is_some() != zooms || conflict {
+        return Err(CliError::Usage(args[0].clone()));
+    }
+    let gif = sequence.is_some()
+        && Path::new(&args[1])
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("gif"));
+    let png = (sequence.is_some() && !gif) || Format::from_filename(&args[1]) == Some(Format::Png);
+    let format = Format::from_filename(&args[1]);
+    let holds_alpha = png || stdout || matches!(format, Some(Format::Tiff | Format::Farbfeld));
+    if alpha_interior && (!holds_alpha || gif) {
+        return Err(CliError::Parse(
+            "--alpha-interior, which only PNG, TIFF and farbfeld files can hold",
+        ));
+    }
+    if depth == 16
+        && matches!(
+            format,
+            Some(Format::Jpeg | Format::Bmp | Format::Ppm | Format::WebP)
+        )
+    {
+        return Err(CliError::Parse(
+            "bit depth, which must be 8 for JPEG, BMP, PPM and WebP files",
+        ));
+    }
+    if strip_rows.is_some() && stdout {
+        return Err(CliError::Parse("--strip-rows, which only writes to files"));
+    }
+    if strip_rows.is_some() && !png {
+        return Err(CliError::Parse("--strip-rows, which only writes PNG files"));
+    }
+    if stdout && std::io::stdout().is_terminal() {
+        return Err(CliError::Terminal);
+    }
+    if gif && dither != Dither::None {
+        return Err(CliError::Parse(
+            "dithering mode, which GIF animations don't use",
+        ));
+    }
+    if gif && depth != 8 {
+        return Err(CliError::Parse(
+            "bit depth, which must be 8 for GIF animations",
+        ));
+    }
+
+    let bounds = parse_dimensions(&args[2])
+        .map_err(|err| CliError::Pair("the image dimensions", ["width", "height"], err))?;
+    let params = Params {
+        limit,
+        julia,
+        z0: z0.unwrap_or_default(),
+        smooth,
+        palette,
+        precision,
+        fractal,
+        power,
+        bailout,
+        supersample,
+        jitter,
+        color_mode,
+        scale,
+        cycle_period,
+        cycle_offset: cycle_offset.unwrap_or(0.0),
+        interior,
+        stripe_freq,
+        trap,
+        gamma,
+        simd,
+        samples,
+        seed,
+        adaptive,
+        linear_averaging,
+        invert,
+        alpha_interior,
+    };
+    // Without a palette GIF frames are written as grays, so the colors of
+    // newton and --color-mode angle have nowhere to go.
+    if gif && palette.is_none() && params.color_type() != image::ColorType::L8 {
+        return Err(CliError::Parse(
+            "color mode, which needs a palette for GIF animations",
+        ));
+    }
+    if buddhabrot && (strip_rows.is_some() || raw_output.is_some() || is_exr(&args[1])) {
+        return Err(CliError::Parse(
+            "fractal name, buddhabrot has no escape counts for --raw-output or exr files, \
+             and can't be rendered with --strip-rows",
+        ));
+    }
+    let overlay = seed_orbit.is_some() || grid.is_some() || scale_bar || contours.is_some();
+    if overlay && alpha_interior {
+        return Err(CliError::Parse(
+            "--seed-orbit, --grid, --scale-bar and --contours, which draw over opaque images, \
+             not --alpha-interior",
+        ));
+    }
+    if overlay && (strip_rows.is_some() || is_exr(&args[1])) {
+        return Err(CliError::Parse(
+            "--seed-orbit, --grid, --scale-bar and --contours, which draw over whole images, \
+             not strips or exr files",
+        ));
+    }
+    let beyond_f64 = deep_zoom || precision_bits.is_some();
+    if tile_size.is_some() && (buddhabrot || beyond_f64 || strip_rows.is_some()) {
+        return Err(CliError::Parse(
+            "--tile-size, which can't be used with buddhabrot, --deep-zoom, --precision-bits \
+             or --strip-rows",
+        ));
+    }
+    let whole = sequence.is_some() || preview || strip_rows.is_some() || is_exr(&args[1]);
+    if checkpoints && (buddhabrot || beyond_f64 || whole) {
+        return Err(CliError::Parse(
+            "--checkpoint and --resume, which can't be used with buddhabrot, --deep-zoom, \
+             --precision-bits, --zoom-sequence, --preview, --strip-rows or exr files",
+        ));
+    }
+    let tile = match tile {
+        Some(tile) => Some(parse_tile(&tile, bounds).ok_or(CliError::Parse(
+            "tile, which is X0,Y0,X1,Y1 with X0 < X1 and Y0 < Y1 within the image",
+        ))?),
         None => None,
+    };
+    let rendered_whole = overlay || raw_output.is_some() || checkpoints || tile_size.is_some();
+    if tile.is_some() && (buddhabrot || beyond_f64 || whole || rendered_whole) {
+        return Err(CliError::Parse(
+            "--tile, which can't be used with buddhabrot, --deep-zoom, --precision-bits, \
+             --zoom-sequence, --preview, --strip-rows, exr files, --checkpoint, --resume, \
+             --tile-size, --raw-output or the overlays",
+        ));
+    }
+    let unscalable = strip_rows.is_some() || is_exr(&args[1]) || checkpoints || tile.is_some();
+    if render_scale > 1 && unscalable {
+        return Err(CliError::Parse(
+            "--render-scale, which can't be used with --strip-rows, exr files, --checkpoint, \
+             --resume or --tile",
+        ));
+    }
+    if contours.is_some() && (buddhabrot || newton || beyond_f64) {
+        return Err(CliError::Parse(
+            "--contours, which follow the escape values of the f64 renders of escape-time \
+             fractals",
+        ));
+    }
+    if bit_reproducible && (simd || random_palette || !params.is_reproducible()) {
+        return Err(CliError::Parse(
+            "--bit-reproducible, which renders in f64 without --simd and can't be used with \
+             --precision f32, or with what goes through the platform's math library: --smooth, \
+             --color-mode other than linear or histogram, --scale log, a fractional --power, \
+             --interior-color, --gamma, newton, --palette random or supersamples averaged \
+             in linear light, without --antialias-gamma-correct off",
+        ));
+    }
+    if deep_zoom && precision_bits.is_some() {
+        return Err(CliError::Parse(
+            "--deep-zoom, which can't be used with --precision-bits",
+        ));
+    }
+    if deep_zoom || precision_bits.is_some() {
+        if !params.plain_escape_counts() {
+            return Err(CliError::Parse(
+                "--deep-zoom and --precision-bits, which only render plain escape counts of \
+                 the Mandelbrot set",
+            ));
+        }
+        if sequence.is_some() || strip_rows.is_some() || raw_output.is_some() || keep_aspect {
+            return Err(CliError::Parse(
+                "--deep-zoom and --precision-bits, which can't be used with --zoom-sequence, \
+                 --strip-rows, --raw-output or --preserve-aspect",
+            ));
+        }
+        if is_exr(&args[1]) {
+            return Err(CliError::Parse(
+                "--deep-zoom and --precision-bits, which don't write exr files",
+            ));
+        }
+    }
+    #[cfg(feature = "deep-zoom")]
+    let deep = match (deep_zoom, &center_text, zoom) {
+        (false, _, _) => None,
+        (true, Some(center), Some(zoom)) => Some(DeepView::parse(center, zoom, bounds).ok_or(
+            CliError::Parse("center point, which --deep-zoom reads as RE,IM"),
+        )?),
+        (true, _, _) => {
+            return Err(CliError::Parse(
+                "--deep-zoom, which needs --center and --zoom",
+            ))
+        }
+    };
+    let corner_text = match &region_text {
+        Some(s) => split_corners(s).ok(),
+        None => args
+            .get(3)
+            .zip(args.get(4))
+            .map(|(u_l, l_r)| (u_l.as_str(), l_r.as_str())),
+    };
+    let precise = match (precision_bits, &center_text, zoom, corner_text, preset) {
+        (None, ..) => None,
+        (Some(bits), Some(center), Some(zoom), _, _) => Some(
+            PreciseRegion::from_center(bounds, center, zoom, bits).ok_or(CliError::Parse(
+                "center point, which --precision-bits reads as RE,IM",
+            ))?,
+        ),
+        (Some(bits), _, _, Some((first, second)), _) => Some(
+            PreciseRegion::parse(bounds, first, second, bits).ok_or(CliError::Parse(
+                "corners, which --precision-bits reads as RE,IM",
+            ))?,
+        ),
+        (Some(bits), _, _, None, preset) => {
+            let (upper_l, lower_r) = preset.expect("checked by the argument count").corners();
+            let region = Region::new(bounds, upper_l, lower_r);
+            Some(PreciseRegion::from_region(&region, bits))
+        }
+    };
+    // Dithered images are rendered in 16 bits.
+    let rendered_depth = if dither == Dither::None { depth } else { 16 };
+    let pixel_bytes = params.channels() * rendered_depth as usize / 8;
+    let rendered = (bounds.0 * render_scale, bounds.1 * render_scale);
+    check_dimensions(rendered, pixel_bytes)?;
+    let budget = max_memory.filter(|_| strip_rows.is_none());
+    let budget = budget.map(|mb| (mb * MEGABYTE) as usize);
+    let strip_rows = match budget.map(|budget| strips_within(rendered, pixel_bytes, budget)) {
+        Some(Ok(None)) | None => strip_rows,
+        Some(Err(err)) => return Err(err),
+        Some(Ok(Some(rows))) => {
+            let at_once = buddhabrot || overlay || beyond_f64 || checkpoints || tile.is_some();
+            let at_once = at_once || render_scale > 1;
+            if !png || stdout || at_once || tile_size.is_some() {
+                return Err(CliError::Parse(
+                    "--max-memory, which is too little for the whole image, and the image \
+                     can't be rendered in strips as with --strip-rows",
+                ));
+            }
+            Some(rows)
+        }
+    };
+    let settings = Settings {
+        threads,
+        tile_size: tile_size.or(checkpoints.then_some(CHECKPOINT_TILE_SIZE)),
+        checkpoint: checkpoint.or(resume.clone()),
+        resume,
+        quiet,
+        depth,
+        options,
+        strip_rows,
+        tile,
+        dither,
+        render_scale,
+        downsample_filter,
+        #[cfg(feature = "deep-zoom")]
+        deep,
+        precise,
+        profile_bands,
+        seed_orbit: seed_orbit.map(|point| params.orbit(point)),
+        grid,
+        scale_bar,
+        contours,
+    };
+    let view = (center, zoom, sequence, preset, corners);
+    let frames: Vec<(String, (Complex64, Complex64))> = match view {
+        (Some(center), _, Some((start, end, frames)), _, _) => {
+            if !gif {
+                std::fs::create_dir_all(&args[1]).map_err(image::ImageError::IoError)?;
+            }
+            zoom_sequence(start, end, frames)
+                .into_iter()
+                .enumerate()
+                .map(|(i, zoom)| {
+                    let filename = match gif {
+                        true => args[1].clone(),
+                        false => format!("{}/frame_{:04}.png", args[1], i + 1),
+                    };
+                    (filename, corners_from_center(center, zoom, bounds))
+                })
+                .collect()
+        }
+        (Some(center), Some(zoom), _, _, _) => {
+            vec![(args[1].clone(), corners_from_center(center, zoom, bounds))]
+        }
+        // Either corner may come first.
+        (None, _, _, _, Some((first, second))) => {
+            vec![(args[1].clone(), order_corners(first, second).0)]
+        }
+        (None, _, _, Some(preset), None) if args.len() == 3 => {
+            vec![(args[1].clone(), preset.corners())]
+        }
+        _ => {
+            let u_l = parse_complex(&args[3]).map_err(complex_error("the upper left corner"))?;
+            let l_r = parse_complex(&args[4]).map_err(complex_error("the lower right corner"))?;
+            let (corners, swapped) = order_corners(u_l, l_r);
+            if swapped {
+                eprintln!("Note: the corners were reversed, so they have been swapped");
+            }
+            vec![(args[1].clone(), corners)]
+        }
+    };
+
+    let mut animation = match gif {
+        true => Some(GifFrames::create(&args[1], bounds, palette, frame_delay)?),
+        false => None,
+    };
+    for (filename, (u_l, l_r)) in frames {
+        if interrupted() {
+            break;
+        }
+        let (u_l, l_r) = if keep_aspect {
+            preserve_aspect(bounds, u_l, l_r)
+        } else {
+            (u_l, l_r)
+        };
+        let region = Region {
+            flip_y,
+            ..Region::new(bounds, u_l, l_r)
+        };
+        #[cfg(feature = "gui")]
+        if interactive {
+            return viewer::explore(&filename, region, &params);
+        }
+        if preview {
+            let small = (bounds.0 / PREVIEW_SCALE, bounds.1 / PREVIEW_SCALE);
+            let small = Region {
+                bounds: (small.0.max(1), small.1.max(1)),
+                ..region
+            };
+            let preview = preview_filename(&filename);
+            let start = Instant::now();
+            draw(&preview, &small, &params, &settings)?;
+            eprintln!("Wrote {} in {:.2?}", preview, start.elapsed());
+            if preview_only {
+                continue;
+            }
+        }
+        let start = Instant::now();
+        let (stats, encoding) = match &mut animation {
+            Some(animation) => {
+                let (pixels, stats) = render_frame::<u8>(&region, &params, &settings)?;
+                let start = Instant::now();
+                animation
+                    .write_frame(&pixels)
+                    .map_err(|err| CliError::Write(filename, err))?;
+                (stats, start.elapsed())
+            }
+            None => draw(&filename, &region, &params, &settings)?,
+        };
+        let elapsed = start.elapsed();
+        if !settings.quiet {
+            eprintln!(
+                "Rendered {} pixels in {:.2?} ({:.2?} rendering, {:.2?} encoding)",
+                bounds.0 * bounds.1,
+                elapsed,
+                elapsed.saturating_sub(encoding),
+                encoding
+            );
+        }
+        if print_stats {
+            eprintln!("{}", stats);
+        }
+        if let Some(json) = &metadata_json {
+            let text = params.metadata_json(&region, &stats, elapsed).to_string() + "\n";
+            std::fs::write(json, text).map_err(|err| CliError::Write(json.clone(), err))?;
+        }
+        if let Some(raw) = raw_output.as_ref().filter(|_| !interrupted()) {
+            let counts = escape_counts(&region, &params, threads);
+            write_raw(raw, &counts, bounds, params.limit)
+                .map_err(|err| CliError::Write(raw.clone(), err))?;
+        }
+    }
+    if let Some(animation) = animation {
+        animation
+            .finish()
+            .map_err(|err| CliError::Write(args[1].clone(), err))?;
+    }
+
+    match interrupted() {
+        true => Err(CliError::Interrupted),
+        false => Ok(()),
     }
 }
 
-/// Given the row and column of a pixel in the output image,
-/// return the corresponding point on the complex plane.
-fn pixel_to_point(
+/// How the rendered image is produced and stored, apart from its contents.
+struct Settings {
+    threads: usize,
+    /// Render in square tiles of this many pixels, rather than in rows.
+    tile_size: Option<usize>,
+    /// Save the render so far to this checkpoint file every so often.
+    checkpoint: Option<String>,
+    /// Pick the render up from this checkpoint file, in its tiles.
+    resume: Option<String>,
+    quiet: bool,
+    /// Bits per channel, 8 or 16.
+    depth: u32,
+    options: WriteOptions,
+    /// Render and write this many rows at a time, rather than all at once.
+    strip_rows: Option<usize>,
+    /// Render and write only these columns of these rows of the image.
+    tile: Option<(Range<usize>, Range<usize>)>,
+    /// How 8-bit images are rounded down from 16-bit renders, unless it's
+    /// `Dither::None`, when they're rendered in 8 bits directly.
+    dither: Dither,
+    /// Render this many times wider and taller, and shrink the image back
+    /// down with `downsample_filter` before drawing any overlays.
+    render_scale: usize,
+    downsample_filter: Filter,
+    /// Render this view by perturbation instead of the region's corners,
+    /// which have run out of precision.
+    #[cfg(feature = "deep-zoom")]
+    deep: Option<DeepView>,
+    /// Render this view in fixed point instead of the region's corners.
+    precise: Option<PreciseRegion>,
+    /// Print how long each band of rows took to render once it's done.
+    profile_bands: bool,
+    /// The orbit drawn over the image once it's rendered.
+    seed_orbit: Option<Vec<Complex64>>,
+    /// The spacing of the grid drawn over the image, under the orbit.
+    grid: Option<f64>,
+    /// The spacing of the contours drawn over the image, under the grid.
+    contours: Option<f64>,
+    /// Whether to draw a scale bar over everything else.
+    scale_bar: bool,
+}
+
+/// Render the image of `region` and write it to `filename`. Returns the
+/// escape statistics of the image and the part of the time taken that went
+/// to encoding and writing it.
+fn draw(
+    filename: &str,
+    region: &Region,
+    params: &Params,
+    settings: &Settings,
+) -> Result<(Stats, Duration), CliError> {
+    if is_exr(filename) {
+        return draw_exr(filename, region, params, settings);
+    }
+    match (settings.depth, settings.dither) {
+        (8, Dither::None) => draw_samples::<u8>(filename, region, params, settings),
+        _ => draw_samples::<u16>(filename, region, params, settings),
+    }
+}
+
+/// A ditherer for rows `width` pixels wide of samples of type `P`, if
+/// they're wider than the 8 bits they're written in.
+fn ditherer<P: Sample>(width: usize, params: &Params, settings: &Settings) -> Option<Ditherer> {
+    let wider = settings.depth == 8 && P::MAX > u64::from(u8::MAX);
+    wider.then(|| Ditherer::new(settings.dither, width, params.channels()))
+}
+
+/// Whether `filename` names an OpenEXR file, which holds escape values
+/// rather than colors.
+fn is_exr(filename: &str) -> bool {
+    Path::new(filename)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("exr"))
+}
+
+/// Compute the escape values of the image of `region` and write them to
+/// the OpenEXR file `filename`.
+fn draw_exr(
+    filename: &str,
+    region: &Region,
+    params: &Params,
+    settings: &Settings,
+) -> Result<(Stats, Duration), CliError> {
+    let values = escape_values(region, params, settings.threads);
+    let start = Instant::now();
+    write_exr(filename, &values, region.bounds)
+        .map_err(|err| CliError::Write(filename.to_string(), err))?;
+    let encoding = start.elapsed();
+
+    let mut stats = Stats::default();
+    for &value in &values {
+        stats.record(value.is_finite().then_some(f64::from(value)));
+    }
+    Ok((stats, encoding))
+}
+
+/// How many rows at a time an image of size `bounds` with `bytes` bytes per
+/// pixel has to be rendered in to be held in `budget` bytes: `None` if it
+/// fits whole. Fails if not even one row fits.
+fn strips_within(
     bounds: (usize, usize),
-    pixel: (usize, usize),
-    upper_l: Complex64,
-    lower_r: Complex64,
-) -> Complex64 {
-    let (w, h) = (lower_r.re - upper_l.re, upper_l.im - lower_r.im);
-    let re = upper_l.re + (((pixel.0 as f64) * w) / bounds.0 as f64);
-    let im = upper_l.im - (((pixel.1 as f64) * h) / bounds.1 as f64);
-
-    cmplx!(re, im)
-}
-
-fn render(pixels: &mut [u8], bounds: (usize, usize), upper_l: Complex64, lower_r: Complex64) {
-    assert!(pixels.len() == bounds.0 * bounds.1);
-    for row in 0..bounds.1 {
-        for col in 0..bounds.0 {
-            let point = pixel_to_point(bounds, (col, row), upper_l, lower_r);
-            pixels[row * bounds.0 + col] = match escape_time(point, 255) {
-                None => 0,
-                Some(count) => 255 - count as u8,
+    bytes: usize,
+    budget: usize,
+) -> Result<Option<usize>, CliError> {
+    let row = bounds.0 * bytes;
+    if row > budget {
+        return Err(CliError::Parse(
+            "--max-memory, which is too little for even one row of the image",
+        ));
+    }
+
+    Ok((row * bounds.1 > budget).then(|| budget / row.max(1)))
+}
+
+/// Check that an image of size `bounds` with `bytes` bytes per pixel is
+/// neither empty nor too large to allocate and encode.
+fn check_dimensions(bounds: (usize, usize), bytes: usize) -> Result<(), CliError> {
+    let error = CliError::Dimensions(bounds.0, bounds.1);
+    if bounds.0 == 0 || bounds.1 == 0 {
+        return Err(error);
+    }
+    let fits_u32 = u32::try_from(bounds.0).is_ok() && u32::try_from(bounds.1).is_ok();
+    let size = bounds
+        .0
+        .checked_mul(bounds.1)
+        .and_then(|n| n.checked_mul(bytes));
+    match size {
+        Some(size) if fits_u32 && size <= isize::MAX as usize => Ok(()),
+        _ => Err(error),
+    }
+}
+
+/// Like `draw_samples`, rendering `strip_rows` rows at a time and writing
+/// each strip to the PNG file `filename` as soon as it's done.
+fn draw_strips<P: Sample>(
+    filename: &str,
+    region: &Region,
+    params: &Params,
+    settings: &Settings,
+    strip_rows: usize,
+) -> Result<(Stats, Duration), CliError> {
+    let bounds = region.bounds;
+    let mut ditherer = ditherer::<P>(region.bounds.0, params, settings);
+    let color = match ditherer {
+        Some(_) => u8::color_type(params.channels()),
+        None => P::color_type(params.channels()),
+    };
+    let options = WriteOptions {
+        text: params.metadata(region),
+        ..settings.options.clone()
+    };
+    let mut png = PngStrips::create(filename, bounds, color, &options)?;
+    let write_error = |err| CliError::Write(filename.to_string(), err);
+
+    let progress = new_progress(bounds.1 * params.passes(), settings);
+    let mut encoding = Duration::ZERO;
+    let stats = watched(&progress, settings.quiet, || {
+        let threads = settings.threads;
+        let write = |strip: &[P]| {
+            let start = Instant::now();
+            let written = match &mut ditherer {
+                Some(ditherer) => png.write_rows(&ditherer.quantize(strip)),
+                None => png.write_rows(strip),
             };
+            encoding += start.elapsed();
+            written
+        };
+        let stats = render_strips(region, params, strip_rows, threads, Some(&progress), write);
+        if stats.is_err() {
+            // Let the watcher stop.
+            progress.cancel();
         }
+        stats
+    });
+    let stats = stats.map_err(write_error)?;
+    if settings.profile_bands {
+        print_band_times(&progress.band_times());
     }
+    let start = Instant::now();
+    png.finish().map_err(write_error)?;
+
+    Ok((stats, encoding + start.elapsed()))
+}
+
+/// Parse a tile like `"0,0,400,300"` of an image of size `bounds`: the
+/// columns `X0..X1` and rows `Y0..Y1`, neither of them empty, and both
+/// within the image.
+fn parse_tile(s: &str, bounds: (usize, usize)) -> Option<(Range<usize>, Range<usize>)> {
+    let (first, second) = split_corners(s).ok()?;
+    let (x0, y0): (usize, usize) = parse_pair(first, ',').ok()?;
+    let (x1, y1): (usize, usize) = parse_pair(second, ',').ok()?;
+    let valid = x0 < x1 && x1 <= bounds.0 && y0 < y1 && y1 <= bounds.1;
+
+    valid.then_some((x0..x1, y0..y1))
 }
 
-/// Write the buffer `pixels`,
-/// whose dimensions are given by `bounds`, to the file named `filename`.
-fn write_image(
+/// Like `draw_samples`, rendering only the columns `cols` of rows `rows`
+/// of the image of `region` and writing them as an image of their own.
+fn draw_tile<P: Sample>(
     filename: &str,
-    pixels: &[u8],
-    bounds: (usize, usize),
-) -> Result<(), image::ImageError> {
-    let output = File::create(filename)?;
-    let encoder = PngEncoder::new(output);
+    region: &Region,
+    params: &Params,
+    settings: &Settings,
+    (cols, rows): (Range<usize>, Range<usize>),
+) -> Result<(Stats, Duration), CliError> {
+    let channels = params.channels();
+    let size = (cols.len(), rows.len());
+    let mut pixels = vec![P::default(); size.0 * size.1 * channels];
+    let progress = new_progress(region.bounds.1 * (params.passes() - 1) + size.1, settings);
+    let stats = watched(&progress, settings.quiet, || {
+        let threads = settings.threads;
+        let progress = Some(&progress);
+        render_window(&mut pixels, region, cols, rows, params, threads, progress)
+    });
+    if settings.profile_bands {
+        print_band_times(&progress.band_times());
+    }
 
-    encoder.write_image(pixels, bounds.0 as u32, bounds.1 as u32, ColorType::L8)?;
+    let (bytes, color) = match ditherer::<P>(size.0, params, settings) {
+        Some(mut ditherer) => (
+            Cow::Owned(ditherer.quantize(&pixels)),
+            u8::color_type(channels),
+        ),
+        None => (P::as_bytes(&pixels), P::color_type(channels)),
+    };
+    let options = WriteOptions {
+        text: params.metadata(region),
+        ..settings.options.clone()
+    };
+    let start = Instant::now();
+    write_image_with(filename, &bytes, size, color, &options)?;
 
-    Ok(())
+    Ok((stats, start.elapsed()))
+}
+
+/// Parse a zoom sequence like `"2,0.001,120"`: the start and end zoom and
+/// the number of frames, at least one.
+fn parse_sequence(s: &str) -> Option<(f64, f64, usize)> {
+    let mut parts = s.split(',');
+    let start: f64 = parts.next()?.parse().ok()?;
+    let end: f64 = parts.next()?.parse().ok()?;
+    let frames: usize = parts.next()?.parse().ok()?;
+    let valid = start > 0.0 && end > 0.0 && frames > 0;
+
+    match parts.next() {
+        None if valid => Some((start, end, frames)),
+        _ => None,
+    }
+}
+
+/// Where the preview of the image `filename` goes: next to it, with
+/// `-preview` added before the extension.
+fn preview_filename(filename: &str) -> String {
+    let path = Path::new(filename);
+    let stem = path
+        .file_stem()
+        .map_or("".into(), |stem| stem.to_string_lossy());
+    let name = match path.extension() {
+        Some(ext) => format!("{}-preview.{}", stem, ext.to_string_lossy()),
+        None => format!("{}-preview", stem),
+    };
+
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+/// Wrap an error from parsing the complex number `what`.
+fn complex_error(what: &'static str) -> impl Fn(ParseError) -> CliError {
+    move |err| CliError::Pair(what, ["real part", "imaginary part"], err)
+}
+
+/// Like `draw`, with `P` samples per channel.
+fn draw_samples<P: Sample>(
+    filename: &str,
+    region: &Region,
+    params: &Params,
+    settings: &Settings,
+) -> Result<(Stats, Duration), CliError> {
+    if let Some(strip_rows) = settings.strip_rows {
+        return draw_strips::<P>(filename, region, params, settings, strip_rows);
+    }
+    if let Some(tile) = &settings.tile {
+        return draw_tile::<P>(filename, region, params, settings, tile.clone());
+    }
+    let (pixels, stats) = render_frame::<P>(region, params, settings)?;
+
+    let channels = params.channels();
+    let (bytes, color) = match ditherer::<P>(region.bounds.0, params, settings) {
+        Some(mut ditherer) => (
+            Cow::Owned(ditherer.quantize(&pixels)),
+            u8::color_type(channels),
+        ),
+        None => (P::as_bytes(&pixels), P::color_type(channels)),
+    };
+    let options = WriteOptions {
+        text: params.metadata(region),
+        ..settings.options.clone()
+    };
+    let start = Instant::now();
+    write_image_with(filename, &bytes, region.bounds, color, &options)?;
+
+    Ok((stats, start.elapsed()))
+}
+
+/// Render the image of `region` into a new buffer, reporting progress
+/// unless `settings` says to be quiet. Ctrl-C stops the render, leaving
+/// the rows not yet started black. Under `settings.render_scale` the
+/// escape statistics are of the larger image.
+fn render_frame<P: Sample>(
+    region: &Region,
+    params: &Params,
+    settings: &Settings,
+) -> Result<(Vec<P>, Stats), CliError> {
+    // Everything up to the overlays is rendered at the larger size.
+    let (output, scale) = (region, settings.render_scale);
+    let region = &Region {
+        bounds: (region.bounds.0 * scale, region.bounds.1 * scale),
+        ..*region
+    };
+    let bounds = region.bounds;
+    let (mut pixels, mut state) = match &settings.resume {
+        Some(file) => {
+            let (pixels, state) = read_checkpoint(file, region, params)
+                .map_err(|err| CliError::Read(file.clone(), err))?;
+            (pixels, Some(state))
+        }
+        None => {
+            let pixels = vec![P::default(); bounds.0 * bounds.1 * params.channels()];
+            let tile_size = settings.tile_size.unwrap_or(CHECKPOINT_TILE_SIZE);
+            let state = settings.checkpoint.as_ref();
+            (pixels, state.map(|_| TiledState::new(bounds, tile_size)))
+        }
+    };
+    let tile_size = state
+        .as_ref()
+        .map_or(settings.tile_size, |state| Some(state.tile_size));
+
+    let rows = match tile_size {
+        Some(tile_size) => tiled_rows(bounds, tile_size),
+        None => bounds.1,
+    };
+    let progress = new_progress(rows * params.passes(), settings);
+    let stats = watched(&progress, settings.quiet, || {
+        let threads = settings.threads;
+        #[cfg(feature = "deep-zoom")]
+        if let Some(view) = settings.deep {
+            let view = DeepView {
+                bounds,
+                flip_y: region.flip_y,
+                ..view
+            };
+            return Ok(render_deep_with_progress(
+                &mut pixels,
+                &view,
+                params,
+                threads,
+                &progress,
+            ));
+        }
+        if let Some(view) = &settings.precise {
+            let view = PreciseRegion {
+                bounds,
+                flip_y: region.flip_y,
+                ..view.clone()
+            };
+            return Ok(render_precise_with_progress(
+                &mut pixels,
+                &view,
+                params,
+                threads,
+                &progress,
+            ));
+        }
+        if let (Some(state), Some(file)) = (&mut state, &settings.checkpoint) {
+            let pixels = &mut pixels;
+            return render_checkpointed(pixels, region, params, threads, state, file, &progress);
+        }
+        Ok(match tile_size {
+            Some(size) => {
+                render_tiled_with_progress(&mut pixels, region, params, threads, size, &progress)
+            }
+            None => render_parallel_with_progress(&mut pixels, region, params, threads, &progress),
+        })
+    })?;
+    if settings.profile_bands {
+        print_band_times(&progress.band_times());
+    }
+    if scale > 1 {
+        let filter = settings.downsample_filter;
+        pixels = downsample(&pixels, bounds, params.channels(), scale, filter);
+    }
+    let (region, bounds) = (output, output.bounds);
+    if let Some(step) = settings.contours {
+        let smooth = Params {
+            smooth: true,
+            ..*params
+        };
+        let values = escape_values(region, &smooth, settings.threads);
+        draw_contours(&mut pixels, bounds, params.channels(), &values, step);
+    }
+    if let Some(step) = settings.grid {
+        draw_grid(&mut pixels, region, params.channels(), step);
+    }
+    if let Some(orbit) = &settings.seed_orbit {
+        draw_orbit(&mut pixels, region, params.channels(), orbit);
+    }
+    if settings.scale_bar {
+        draw_scale_bar(&mut pixels, region, params.channels());
+    }
+
+    Ok((pixels, stats))
+}
+
+/// Render the tiles of `region` that `state` doesn't have finished into
+/// `pixels` on `threads` threads, saving the image and `state` to the
+/// checkpoint file `filename` every `CHECKPOINT_INTERVAL`, and once the
+/// render is finished or stopped.
+fn render_checkpointed<P: Sample>(
+    pixels: &mut [P],
+    region: &Region,
+    params: &Params,
+    threads: usize,
+    state: &mut TiledState,
+    filename: &str,
+    progress: &Progress,
+) -> Result<Stats, CliError> {
+    let mut saved = Instant::now();
+    let save = |pixels: &[P], state: &TiledState| {
+        let due = saved.elapsed() >= CHECKPOINT_INTERVAL;
+        if !(due || state.is_finished() || progress.is_cancelled()) {
+            return Ok(());
+        }
+        saved = Instant::now();
+        write_checkpoint(filename, region, params, pixels, state)
+    };
+    let stats =
+        render_tiled_resumable(pixels, region, params, threads, state, Some(progress), save);
+    if stats.is_err() {
+        // Let the watcher stop.
+        progress.cancel();
+    }
+    stats.map_err(|err| CliError::Write(filename.to_string(), err))
+}
+
+/// A `Progress` for a render of `rows` rows, recording how long each band
+/// takes if `settings.profile_bands`.
+fn new_progress(rows: usize, settings: &Settings) -> Progress {
+    match settings.profile_bands {
+        true => Progress::with_band_times(rows),
+        false => Progress::new(rows),
+    }
+}
+
+/// Print a table of how many bands of `times` each render thread did and
+/// how long they kept it busy, then the slowest and quickest band, to show
+/// how evenly the work was spread.
+fn print_band_times(times: &[BandTime]) {
+    let threads = times.iter().map(|band| band.thread + 1).max().unwrap_or(0);
+    eprintln!("thread  bands   rows         busy");
+    for thread in 0..threads {
+        let bands = || times.iter().filter(|band| band.thread == thread);
+        let rows: usize = bands().map(|band| band.rows).sum();
+        let busy: Duration = bands().map(|band| band.elapsed).sum();
+        let count = bands().count();
+        eprintln!("{:>6} {:>6} {:>6} {:>12.2?}", thread, count, rows, busy);
+    }
+    let describe = |band: &BandTime| {
+        let rows = band.top..band.top + band.rows;
+        format!("rows {:?} in {:.2?}", rows, band.elapsed)
+    };
+    let slowest = times.iter().max_by_key(|band| band.elapsed);
+    let quickest = times.iter().min_by_key(|band| band.elapsed);
+    if let (Some(slowest), Some(quickest)) = (slowest, quickest) {
+        eprintln!("Slowest band: {}", describe(slowest));
+        eprintln!("Quickest band: {}", describe(quickest));
+    }
+}
+
+/// Run `render`, which tracks its progress in `progress`, while another
+/// thread watches it as `watch` does, and return what it returns.
+fn watched<R>(progress: &Progress, quiet: bool, render: impl FnOnce() -> R) -> R {
+    thread::scope(|scope| {
+        let watcher = scope.spawn(|| watch(progress, quiet));
+        let result = render();
+        // Wake the watcher to see that it's done, rather than adding the
+        // rest of its wait to the render time.
+        watcher.thread().unpark();
+        result
+    })
+}
+
+/// Watch the render tracked by `progress` until it's done, cancelling it
+/// on Ctrl-C. Unless `quiet`, print `progress` to stderr every
+/// `REPORT_INTERVAL`; renders that finish within the first interval print
+/// nothing.
+fn watch(progress: &Progress, quiet: bool) {
+    let mut last = Instant::now();
+    let mut printed = false;
+    while !progress.is_done() {
+        thread::park_timeout(Duration::from_millis(50));
+        if interrupted() {
+            progress.cancel();
+        }
+        if !quiet && last.elapsed() >= REPORT_INTERVAL {
+            eprint!("\r{}   ", progress);
+            last = Instant::now();
+            printed = true;
+        }
+    }
+    if printed {
+        eprintln!("\r{}   ", progress);
+    }
+}
+
+/// Remove the option `name` and the value following it from `args`,
+/// returning the value if the option was present.
+fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, CliError> {
+    let idx = match args.iter().position(|arg| arg == name) {
+        Some(idx) => idx,
+        None => return Ok(None),
+    };
+
+    args.remove(idx);
+    if idx < args.len() {
+        Ok(Some(args.remove(idx)))
+    } else {
+        Err(CliError::MissingValue(name.to_string()))
+    }
+}
+
+/// Remove the option `name` and its value from `args` like `take_option`,
+/// parsing the value; `what` names the value in the error message.
+fn parse_option<T: FromStr>(
+    args: &mut Vec<String>,
+    name: &str,
+    what: &'static str,
+) -> Result<Option<T>, CliError> {
+    match take_option(args, name)? {
+        Some(value) => T::from_str(&value)
+            .map(Some)
+            .map_err(|_| CliError::Parse(what)),
+        None => Ok(None),
+    }
+}
+
+/// Remove the flag `name` from `args`, returning whether it was present.
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    match args.iter().position(|arg| arg == name) {
+        Some(idx) => {
+            args.remove(idx);
+            true
+        }
+        None => false,
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{parse_complex as pc, parse_pair as pp, pixel_to_point as ptp};
-    use crate::Complex64;
+    use super::{check_dimensions, run_job, strips_within, CliError, ParseError};
+    use super::{complex_error, manifest_jobs, parse_sequence, preview_filename};
+    use super::{take_flag as tf, take_option as to};
+    use mandelbrot::{downsample, Filter};
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    /// A file named for the test in the temporary directory, removed when
+    /// this is dropped, even if the test fails.
+    struct TempFile(String);
+
+    impl TempFile {
+        fn new(name: &str) -> TempFile {
+            let path = std::env::temp_dir().join(format!("mandelbrot-main-test-{}", name));
+            TempFile(path.to_string_lossy().into_owned())
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    /// Run `mandelbrot` with the command line `line` after the file name,
+    /// writing to the temporary file `name`, and read back the image.
+    fn run_to_image(name: &str, line: &[&str]) -> image::RgbImage {
+        let file = TempFile::new(name);
+        run_job(args(&[&["mandelbrot", &file.0][..], line].concat())).unwrap();
+        image::open(&file.0).unwrap().into_rgb8()
+    }
+
+    #[test]
+    fn take_option() {
+        let mut list = args(&["mandelbrot", "--iterations", "500", "out.png"]);
+        assert_eq!(to(&mut list, "--iterations").unwrap(), Some("500".into()));
+        assert_eq!(list, args(&["mandelbrot", "out.png"]));
+        assert_eq!(to(&mut list, "--iterations").unwrap(), None);
+
+        let mut list = args(&["mandelbrot", "--iterations"]);
+        assert!(matches!(
+            to(&mut list, "--iterations"),
+            Err(CliError::MissingValue(name)) if name == "--iterations"
+        ));
+    }
+
+    #[test]
+    fn exit_codes() {
+        let io = image::ImageError::IoError(std::io::ErrorKind::NotFound.into());
+        assert_eq!(CliError::Usage("mandelbrot".into()).exit_code(), 2);
+        assert_eq!(CliError::Unknown("--colour".into()).exit_code(), 2);
+        assert_eq!(CliError::Parse("image dimensions").exit_code(), 3);
+        assert_eq!(CliError::from(io).exit_code(), 4);
+    }
+
+    #[test]
+    fn dimensions() {
+        assert!(check_dimensions((800, 600), 3).is_ok());
+        for (bounds, message) in [
+            ((0, 600), "Image dimensions 0x600 are empty"),
+            ((600, 0), "Image dimensions 600x0 are empty"),
+            (
+                (99999999999, 99999999999),
+                "Image dimensions 99999999999x99999999999 are too large",
+            ),
+        ] {
+            let err = check_dimensions(bounds, 1).unwrap_err();
+            assert_eq!(err.to_string(), message);
+            assert_eq!(err.exit_code(), 3);
+        }
+    }
 
     #[test]
-    fn parse_pair() {
-        assert_eq!(pp::<i32>("", ','), None);
-        assert_eq!(pp::<u32>("10", 'x'), None);
-        assert_eq!(pp::<u32>("10x20", ','), None);
-        assert_eq!(pp::<f64>("400.0x", 'x'), None);
-        assert_eq!(pp::<u32>("10,20", ','), Some((10u32, 20u32)));
-        assert_eq!(pp::<u32>("400x600", 'x'), Some((400u32, 600u32)));
-        assert_eq!(pp::<f64>("400.0x600.5", 'x'), Some((400.0f64, 600.5f64)));
+    fn preview_names() {
+        assert_eq!(preview_filename("mandel.png"), "mandel-preview.png");
+        assert_eq!(
+            preview_filename("out/deep.zoom.jpg"),
+            "out/deep.zoom-preview.jpg"
+        );
+        assert_eq!(preview_filename("image"), "image-preview");
+    }
+
+    #[test]
+    fn pair_errors() {
+        let err = complex_error("the lower right corner")(ParseError::InvalidRight);
+        assert_eq!(err.exit_code(), 3);
+        let message = "Error parsing the imaginary part of the lower right corner";
+        assert_eq!(err.to_string(), message);
+        let err = CliError::Pair(
+            "the image dimensions",
+            ["width", "height"],
+            ParseError::MissingSeparator,
+        );
+        assert_eq!(
+            err.to_string(),
+            "Error parsing the image dimensions: missing separator"
+        );
     }
 
     #[test]
-    fn parse_complex() {
-        assert_eq!(pc("1.25,-0.0625"), Some(cmplx!(1.25, -0.0625)));
-        assert_eq!(pc("0.0,0.0"), Some(cmplx!()));
-        assert_eq!(pc(",-1.0256"), None);
+    fn take_flag() {
+        let mut list = args(&["mandelbrot", "--smooth", "out.png"]);
+        assert!(tf(&mut list, "--smooth"));
+        assert_eq!(list, args(&["mandelbrot", "out.png"]));
+        assert!(!tf(&mut list, "--smooth"));
     }
 
     #[test]
-    fn pixel_to_point() {
+    fn manifest() {
+        let text =
+            "# frames\n\na.png 40x30 -2,1 1,-1\n  b.png 40x30 --center -0.5,0 --zoom 1.5  \n";
+        let jobs = manifest_jobs("mandelbrot", text);
+
+        assert_eq!(jobs.len(), 2);
         assert_eq!(
-            ptp((100, 100), (25, 75), cmplx!(-1.0, 1.0), cmplx!(1.0, -1.0)),
-            cmplx!(-0.5, -0.5)
+            jobs[0],
+            args(&["mandelbrot", "a.png", "40x30", "-2,1", "1,-1"])
         );
+        assert_eq!(
+            jobs[1][1..],
+            args(&["b.png", "40x30", "--center", "-0.5,0", "--zoom", "1.5"])
+        );
+    }
+
+    #[test]
+    fn max_memory() {
+        assert_eq!(strips_within((1000, 800), 3, 3_000_000).unwrap(), None);
+        assert_eq!(strips_within((1000, 800), 3, 30_000).unwrap(), Some(10));
+        let err = strips_within((1000, 800), 3, 2999).unwrap_err();
+        assert_eq!(err.exit_code(), 3);
+
+        // A budget too small for the whole image renders it in strips, to
+        // the same pixels.
+        let line = ["300x200", "-2,1.2", "1,-1.2", "--quiet"];
+        let line = [
+            &line[..],
+            &["--color-mode", "histogram", "--palette", "fire"],
+        ]
+        .concat();
+        let expected = run_to_image("max-memory-whole.png", &line);
+        // 180 000 bytes for the whole image, 900 for each row.
+        assert_eq!(strips_within((300, 200), 3, 9000).unwrap(), Some(10));
+        let strips = [&line[..], &["--max-memory", "0.009"]].concat();
+        assert_eq!(run_to_image("max-memory-strips.png", &strips), expected);
+
+        let file = TempFile::new("max-memory-rejected.png");
+        let line = [
+            "mandelbrot",
+            &file.0,
+            "300x200",
+            "-2,1.2",
+            "1,-1.2",
+            "--max-memory",
+        ];
+        let err = run_job(args(&[&line[..], &["0.0002"]].concat())).unwrap_err();
+        assert_eq!(err.exit_code(), 3);
+        let err = run_job(args(&[&line[..], &["0.01", "--grid", "1"]].concat())).unwrap_err();
+        assert_eq!(err.exit_code(), 3);
+    }
+
+    #[test]
+    fn render_scale() {
+        // Rendering twice the size and shrinking it by boxes is the same as
+        // shrinking a render of twice the size by hand.
+        let job = |name: &str, bounds: &str, options: &[&str]| {
+            let line = [
+                bounds,
+                "-2,1.2",
+                "1,-1.2",
+                "--quiet",
+                "--palette",
+                "fire",
+                "--smooth",
+            ];
+            run_to_image(name, &[&line[..], options].concat()).into_raw()
+        };
+        let large = job("render-scale-large.png", "120x80", &[]);
+        let expected = downsample(&large, (120, 80), 3, 2, Filter::Box);
+        let scaled = job("render-scale-box.png", "60x40", &["--render-scale", "2"]);
+        assert_eq!(scaled, expected);
+        let lanczos = ["--render-scale", "2", "--downsample-filter", "lanczos"];
+        assert_ne!(job("render-scale-lanczos.png", "60x40", &lanczos), expected);
+
+        let file = TempFile::new("render-scale-rejected.png");
+        let line = ["mandelbrot", &file.0, "60x40", "-2,1.2", "1,-1.2"];
+        for options in [
+            &["--render-scale", "0"][..],
+            &["--downsample-filter", "box"],
+        ] {
+            let err = run_job(args(&[&line[..], options].concat())).unwrap_err();
+            assert_eq!(err.exit_code(), 3);
+        }
+    }
+
+    #[test]
+    fn depth() {
+        for ext in ["jpg", "bmp", "ppm", "webp"] {
+            let file = TempFile::new(&format!("depth.{}", ext));
+            let line = [
+                "mandelbrot",
+                &file.0,
+                "4x4",
+                "-2,1",
+                "1,-1",
+                "--depth",
+                "16",
+            ];
+            let err = run_job(args(&line)).unwrap_err();
+            let message =
+                "Error parsing bit depth, which must be 8 for JPEG, BMP, PPM and WebP files";
+            assert_eq!(err.to_string(), message, "{}", ext);
+        }
+    }
+
+    #[test]
+    fn quality() {
+        let file = TempFile::new("quality.jpg");
+        for quality in ["0", "101", "255"] {
+            let line = [
+                "mandelbrot",
+                &file.0,
+                "4x4",
+                "-2,1",
+                "1,-1",
+                "--quality",
+                quality,
+            ];
+            let err = run_job(args(&line)).unwrap_err();
+            let message = "Error parsing quality, which runs from 1 to 100";
+            assert_eq!(err.to_string(), message, "{}", quality);
+        }
+    }
+
+    #[test]
+    fn gif_colors() {
+        let file = TempFile::new("gif-colors.gif");
+        let line = ["mandelbrot", &file.0, "16x16", "--center", "0,0"];
+        let line = [&line[..], &["--zoom-sequence", "2,1,2", "--quiet"]].concat();
+        for options in [&["--fractal", "newton"][..], &["--color-mode", "angle"]] {
+            let err = run_job(args(&[&line[..], options].concat())).unwrap_err();
+            let message = "Error parsing color mode, which needs a palette for GIF animations";
+            assert_eq!(err.to_string(), message, "{:?}", options);
+        }
+        let palette = ["--fractal", "newton", "--palette", "fire"];
+        run_job(args(&[&line[..], &palette].concat())).unwrap();
+    }
+
+    #[test]
+    fn stripe_freq() {
+        let file = TempFile::new("stripe-freq.png");
+        let line = [
+            "mandelbrot",
+            &file.0,
+            "4x4",
+            "-2,1",
+            "1,-1",
+            "--color-mode",
+            "stripes",
+        ];
+        for freq in ["nan", "inf", "-inf"] {
+            let options = ["--stripe-freq", freq];
+            let err = run_job(args(&[&line[..], &options].concat())).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "Error parsing stripe frequency",
+                "{}",
+                freq
+            );
+        }
+    }
+
+    #[test]
+    fn iterations() {
+        let file = TempFile::new("iterations.png");
+        let line = [
+            "mandelbrot",
+            &file.0,
+            "32x32",
+            "--center",
+            "-0.5,0",
+            "--zoom",
+            "1",
+        ];
+        let err = run_job(args(&[&line[..], &["--iterations", "0"]].concat())).unwrap_err();
+        assert_eq!(err.to_string(), "Error parsing iteration limit");
+    }
+
+    #[test]
+    fn zoom() {
+        let file = TempFile::new("zoom.png");
+        let line = ["mandelbrot", &file.0, "4x4", "--center", "0,0", "--zoom"];
+        for zoom in ["0", "-1", "nan", "inf"] {
+            let err = run_job(args(&[&line[..], &[zoom]].concat())).unwrap_err();
+            assert_eq!(err.to_string(), "Error parsing zoom", "{}", zoom);
+        }
+    }
+
+    #[test]
+    fn bailout() {
+        let file = TempFile::new("bailout.png");
+        let line = ["mandelbrot", &file.0, "4x4", "-2,1", "1,-1", "--bailout"];
+        for radius in ["1", "nan", "inf", "1e200", "1.4e154"] {
+            let err = run_job(args(&[&line[..], &[radius]].concat())).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "Error parsing bailout radius",
+                "{}",
+                radius
+            );
+        }
+    }
+
+    #[test]
+    fn sequence() {
+        assert_eq!(parse_sequence("2,0.001,120"), Some((2.0, 0.001, 120)));
+        assert_eq!(parse_sequence("2,0.001"), None);
+        assert_eq!(parse_sequence("2,0.001,120,4"), None);
+        assert_eq!(parse_sequence("2,0,10"), None);
+        assert_eq!(parse_sequence("2,1,0"), None);
     }
 }
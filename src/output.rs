@@ -0,0 +1,879 @@
+//! Encoding rendered buffers to image files.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Cursor, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use image::codecs::bmp::BmpEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::pnm::{PnmEncoder, PnmSubtype, SampleEncoding};
+use image::error::{EncodingError, ImageFormatHint, UnsupportedError, UnsupportedErrorKind};
+use image::{ColorType, ImageEncoder, ImageError, ImageFormat};
+use tiff::encoder::colortype::{Gray16, Gray8, RGB16, RGB8, RGBA16, RGBA8};
+use tiff::encoder::compression::{Compression, Deflate, Lzw, Uncompressed};
+use tiff::encoder::TiffEncoder;
+use tiff::TiffError;
+
+use crate::{webp, Palette, Sample};
+
+/// The filename that stands for standard output, which `write_image` writes
+/// PNG files to.
+pub const STDOUT: &str = "-";
+
+/// JPEG and WebP quality used when none is given, on the `image` crate's
+/// 1–100 scale.
+pub const DEFAULT_QUALITY: u8 = 90;
+
+/// The image formats `write_image` can produce.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Png,
+    Jpeg,
+    Bmp,
+    Ppm,
+    WebP,
+    Tiff,
+    Farbfeld,
+}
+
+impl Format {
+    /// The format named by the extension of `filename`, ignoring case.
+    pub fn from_filename(filename: &str) -> Option<Format> {
+        let ext = Path::new(filename).extension()?.to_str()?;
+        match ext.to_ascii_lowercase().as_str() {
+            "png" => Some(Format::Png),
+            "jpg" | "jpeg" => Some(Format::Jpeg),
+            "bmp" => Some(Format::Bmp),
+            "ppm" => Some(Format::Ppm),
+            "webp" => Some(Format::WebP),
+            "tif" | "tiff" => Some(Format::Tiff),
+            "ff" => Some(Format::Farbfeld),
+            _ => None,
+        }
+    }
+}
+
+/// How the pixels of TIFF files are compressed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TiffCompression {
+    None,
+    Lzw,
+    Deflate,
+}
+
+impl TiffCompression {
+    /// Look up a compression by the name used on the command line.
+    pub fn from_name(name: &str) -> Option<TiffCompression> {
+        match name {
+            "none" => Some(TiffCompression::None),
+            "lzw" => Some(TiffCompression::Lzw),
+            "deflate" => Some(TiffCompression::Deflate),
+            _ => None,
+        }
+    }
+}
+
+/// Encoder settings that don't affect the rendered pixels.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// JPEG quality, from 1 to 100, and WebP quality unless `lossless`.
+    pub quality: u8,
+    /// Whether WebP files keep every bit of every pixel.
+    pub lossless: bool,
+    /// How TIFF files are compressed.
+    pub tiff_compression: TiffCompression,
+    /// Keywords and texts stored in PNG files as `tEXt` chunks, for
+    /// example from `Params::metadata`. Other formats leave them out.
+    pub text: Vec<(String, String)>,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            quality: DEFAULT_QUALITY,
+            lossless: false,
+            tiff_compression: TiffCompression::Lzw,
+            text: Vec::new(),
+        }
+    }
+}
+
+/// Write the buffer `pixels`, whose dimensions are given by `bounds` and
+/// whose layout is given by `color`, to the file named `filename`. The
+/// format is chosen from the file extension; a `filename` of `STDOUT`
+/// writes a PNG file to standard output.
+pub fn write_image(
+    filename: &str,
+    pixels: &[u8],
+    bounds: (usize, usize),
+    color: ColorType,
+) -> Result<(), ImageError> {
+    write_image_with(filename, pixels, bounds, color, &WriteOptions::default())
+}
+
+/// Like `write_image`, with explicit encoder settings.
+pub fn write_image_with(
+    filename: &str,
+    pixels: &[u8],
+    bounds: (usize, usize),
+    color: ColorType,
+    options: &WriteOptions,
+) -> Result<(), ImageError> {
+    if filename == STDOUT {
+        // Standard output takes bytes as they are, with no newline
+        // translation on any platform.
+        let stdout = io::stdout();
+        return encode_image(stdout.lock(), Format::Png, pixels, bounds, color, options);
+    }
+    let format = Format::from_filename(filename).ok_or_else(|| unsupported(filename))?;
+    encode_image(
+        File::create(filename)?,
+        format,
+        pixels,
+        bounds,
+        color,
+        options,
+    )
+}
+
+/// Encode the buffer `pixels` like `write_image_with` in the format `format`,
+/// writing the encoded image to `output`.
+pub fn encode_image<W: Write>(
+    output: W,
+    format: Format,
+    pixels: &[u8],
+    bounds: (usize, usize),
+    color: ColorType,
+    options: &WriteOptions,
+) -> Result<(), ImageError> {
+    let (width, height) = (bounds.0 as u32, bounds.1 as u32);
+    let mut output = BufWriter::new(output);
+
+    match format {
+        Format::Png => {
+            let mut writer = png_encoder(&mut output, bounds, color, options)?
+                .write_header()
+                .map_err(io::Error::from)?;
+            let pixels = match color {
+                ColorType::L16 | ColorType::Rgb16 | ColorType::Rgba16 => {
+                    Cow::Owned(ne_to_be(pixels))
+                }
+                _ => Cow::Borrowed(pixels),
+            };
+            writer.write_image_data(&pixels).map_err(io::Error::from)?;
+            writer.finish().map_err(io::Error::from)?;
+        }
+        Format::Jpeg => JpegEncoder::new_with_quality(&mut output, options.quality)
+            .write_image(pixels, width, height, color)?,
+        Format::Bmp => BmpEncoder::new(&mut output).write_image(pixels, width, height, color)?,
+        Format::Ppm => {
+            let encoder = PnmEncoder::new(&mut output)
+                .with_subtype(PnmSubtype::Pixmap(SampleEncoding::Binary));
+            match color {
+                ColorType::L8 => {
+                    let rgb = gray_to_rgb(pixels);
+                    encoder.write_image(&rgb, width, height, ColorType::Rgb8)?
+                }
+                _ => encoder.write_image(pixels, width, height, color)?,
+            }
+        }
+        Format::WebP => {
+            let quality = (!options.lossless).then_some(options.quality);
+            webp::encode(&mut output, pixels, bounds, color, quality)?
+        }
+        Format::Tiff => {
+            // TIFF encoders seek back to fill in offsets, so the file is
+            // put together in memory first.
+            let mut tiff = Cursor::new(Vec::new());
+            match options.tiff_compression {
+                TiffCompression::None => {
+                    encode_tiff(&mut tiff, pixels, bounds, color, Uncompressed)
+                }
+                TiffCompression::Lzw => encode_tiff(&mut tiff, pixels, bounds, color, Lzw),
+                TiffCompression::Deflate => {
+                    encode_tiff(&mut tiff, pixels, bounds, color, Deflate::default())
+                }
+            }?;
+            output.write_all(tiff.get_ref())?;
+        }
+        Format::Farbfeld => encode_farbfeld(&mut output, pixels, bounds, color)?,
+    }
+    output.flush()?;
+
+    Ok(())
+}
+
+/// A PNG file written a strip of rows at a time, so that images too large
+/// to hold in memory can be rendered straight to disk.
+pub struct PngStrips {
+    writer: png::StreamWriter<'static, BufWriter<File>>,
+}
+
+impl PngStrips {
+    /// Create the file `filename` for a PNG image of size `bounds` with
+    /// pixels of type `color`, which must be 8 or 16-bit gray, RGB or RGBA.
+    pub fn create(
+        filename: &str,
+        bounds: (usize, usize),
+        color: ColorType,
+        options: &WriteOptions,
+    ) -> Result<PngStrips, ImageError> {
+        let output = BufWriter::new(File::create(filename)?);
+        let mut encoder = png_encoder(output, bounds, color, options)?;
+        // The stream writer garbles rows when choosing filters adaptively.
+        encoder.set_adaptive_filter(png::AdaptiveFilterType::NonAdaptive);
+        let writer = encoder
+            .write_header()
+            .and_then(png::Writer::into_stream_writer)
+            .map_err(io::Error::from)?;
+
+        Ok(PngStrips { writer })
+    }
+
+    /// Append the whole rows held in `pixels` to the image.
+    pub fn write_rows<P: Sample>(&mut self, pixels: &[P]) -> io::Result<()> {
+        self.writer.write_all(&P::as_png_bytes(pixels))
+    }
+
+    /// Finish the image, which must have had all its rows written.
+    pub fn finish(self) -> io::Result<()> {
+        Ok(self.writer.finish()?)
+    }
+}
+
+/// An animated GIF written a frame at a time. GIF stores at most 256
+/// colors, so frames are reduced to the colors of the palette they were
+/// rendered with: 255 evenly spaced along it plus black for the interior,
+/// or all 256 grays for grayscale frames.
+pub struct GifFrames {
+    encoder: gif::Encoder<BufWriter<File>>,
+    bounds: (u16, u16),
+    /// The colors of the global color table, or `None` for grayscale.
+    colors: Option<Vec<[u8; 3]>>,
+    /// The index of the nearest table color to each color seen so far.
+    nearest: HashMap<[u8; 3], u8>,
+    /// The time each frame is shown for, in hundredths of a second.
+    delay: u16,
+}
+
+impl GifFrames {
+    /// Create the file `filename` for an animation of frames of size
+    /// `bounds`, each shown for `delay`, that loops forever. Frames hold
+    /// RGB pixels colored with `palette`, or gray pixels if it's `None`.
+    pub fn create(
+        filename: &str,
+        bounds: (usize, usize),
+        palette: Option<Palette>,
+        delay: Duration,
+    ) -> Result<GifFrames, ImageError> {
+        let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "too large for a GIF");
+        let width = u16::try_from(bounds.0).map_err(|_| too_large())?;
+        let height = u16::try_from(bounds.1).map_err(|_| too_large())?;
+        let colors = palette.map(|palette| {
+            let along = (0..255).map(|i| palette.color(f64::from(i) / 254.0));
+            std::iter::once([0, 0, 0]).chain(along).collect::<Vec<_>>()
+        });
+        let table: Vec<u8> = match &colors {
+            None => (0..=255).flat_map(|g| [g, g, g]).collect(),
+            Some(colors) => colors.concat(),
+        };
+
+        let output = BufWriter::new(File::create(filename)?);
+        let mut encoder = gif::Encoder::new(output, width, height, &table).map_err(gif_error)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(gif_error)?;
+        let delay = (delay.as_millis() / 10).min(u128::from(u16::MAX)) as u16;
+
+        Ok(GifFrames {
+            encoder,
+            bounds: (width, height),
+            colors,
+            nearest: HashMap::new(),
+            delay,
+        })
+    }
+
+    /// Append the frame held in `pixels`, which must be a whole image, RGB
+    /// if there's a palette and gray if not.
+    pub fn write_frame(&mut self, pixels: &[u8]) -> io::Result<()> {
+        let channels = if self.colors.is_some() { 3 } else { 1 };
+        let (width, height) = (usize::from(self.bounds.0), usize::from(self.bounds.1));
+        assert!(pixels.len() == width * height * channels);
+        let indices = match &self.colors {
+            None => Cow::Borrowed(pixels),
+            Some(colors) => {
+                let nearest = &mut self.nearest;
+                let index = |rgb: &[u8]| {
+                    let rgb = [rgb[0], rgb[1], rgb[2]];
+                    *nearest
+                        .entry(rgb)
+                        .or_insert_with(|| nearest_color(colors, rgb))
+                };
+                Cow::Owned(pixels.chunks(3).map(index).collect())
+            }
+        };
+        let frame = gif::Frame {
+            delay: self.delay,
+            width: self.bounds.0,
+            height: self.bounds.1,
+            buffer: indices,
+            ..gif::Frame::default()
+        };
+
+        self.encoder.write_frame(&frame).map_err(gif_error)
+    }
+
+    /// Finish the animation.
+    pub fn finish(self) -> io::Result<()> {
+        self.encoder.into_inner()?.flush()
+    }
+}
+
+/// The index of the color in `colors` closest to `rgb`.
+fn nearest_color(colors: &[[u8; 3]], rgb: [u8; 3]) -> u8 {
+    let distance = |color: &[u8; 3]| -> i32 {
+        (0..3)
+            .map(|i| (i32::from(color[i]) - i32::from(rgb[i])).pow(2))
+            .sum()
+    };
+    let (index, _) = colors
+        .iter()
+        .enumerate()
+        .min_by_key(|&(_, color)| distance(color))
+        .unwrap();
+
+    index as u8
+}
+
+/// Convert an error from the GIF encoder.
+fn gif_error(err: gif::EncodingError) -> io::Error {
+    match err {
+        gif::EncodingError::Io(err) => err,
+        gif::EncodingError::Format(err) => io::Error::new(io::ErrorKind::InvalidInput, err),
+    }
+}
+
+/// Write the escape values `values` of an image of size `bounds` to the
+/// OpenEXR file named `filename`, as a single float channel `Y` that
+/// viewers show in gray.
+pub fn write_exr(filename: &str, values: &[f32], bounds: (usize, usize)) -> io::Result<()> {
+    use exr::prelude::*;
+
+    let channel = AnyChannel::new("Y", FlatSamples::F32(values.to_vec()));
+    let layer = Layer::new(
+        bounds,
+        LayerAttributes::named("escape"),
+        Encoding::SMALL_FAST_LOSSLESS,
+        AnyChannels::sort(SmallVec::from_elem(channel, 1)),
+    );
+
+    Image::from_layer(layer)
+        .write()
+        .to_file(filename)
+        .map_err(|err| match err {
+            Error::Io(err) => err,
+            err => io::Error::new(io::ErrorKind::InvalidInput, err),
+        })
+}
+
+/// Write the escape counts `counts` of an image of size `bounds`, rendered
+/// with iteration limit `limit`, to the file named `filename` for other
+/// tools to color. A `.csv` file gets one line of comma-separated counts per
+/// row. Any other file gets the width, height and limit followed by the
+/// counts, all as little-endian `u32`s. Either way interior points have the
+/// count `limit`.
+pub fn write_raw(
+    filename: &str,
+    counts: &[u32],
+    bounds: (usize, usize),
+    limit: u32,
+) -> io::Result<()> {
+    let mut output = BufWriter::new(File::create(filename)?);
+    let csv = Path::new(filename)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+
+    if csv {
+        for row in counts.chunks(bounds.0.max(1)) {
+            let line: Vec<String> = row.iter().map(u32::to_string).collect();
+            writeln!(output, "{}", line.join(","))?;
+        }
+    } else {
+        for value in [bounds.0 as u32, bounds.1 as u32, limit]
+            .iter()
+            .chain(counts)
+        {
+            output.write_all(&value.to_le_bytes())?;
+        }
+    }
+
+    output.flush()
+}
+
+/// A PNG encoder for an image of size `bounds` with pixels of type `color`,
+/// compressed like the `image` crate does and carrying the text of
+/// `options`. Fails for color types other than 8 or 16-bit gray, RGB or
+/// RGBA.
+fn png_encoder<W: Write>(
+    output: W,
+    bounds: (usize, usize),
+    color: ColorType,
+    options: &WriteOptions,
+) -> Result<png::Encoder<'static, W>, ImageError> {
+    let (color, depth) = match color {
+        ColorType::L8 => (png::ColorType::Grayscale, png::BitDepth::Eight),
+        ColorType::L16 => (png::ColorType::Grayscale, png::BitDepth::Sixteen),
+        ColorType::Rgb8 => (png::ColorType::Rgb, png::BitDepth::Eight),
+        ColorType::Rgb16 => (png::ColorType::Rgb, png::BitDepth::Sixteen),
+        ColorType::Rgba8 => (png::ColorType::Rgba, png::BitDepth::Eight),
+        ColorType::Rgba16 => (png::ColorType::Rgba, png::BitDepth::Sixteen),
+        _ => {
+            return Err(ImageError::Unsupported(
+                UnsupportedError::from_format_and_kind(
+                    ImageFormatHint::Exact(ImageFormat::Png),
+                    UnsupportedErrorKind::Color(color.into()),
+                ),
+            ))
+        }
+    };
+    let mut encoder = png::Encoder::new(output, bounds.0 as u32, bounds.1 as u32);
+    encoder.set_color(color);
+    encoder.set_depth(depth);
+    encoder.set_compression(png::Compression::Fast);
+    encoder.set_filter(png::FilterType::Sub);
+    encoder.set_adaptive_filter(png::AdaptiveFilterType::Adaptive);
+    for (keyword, text) in &options.text {
+        encoder
+            .add_text_chunk(keyword.clone(), text.clone())
+            .map_err(io::Error::from)?;
+    }
+
+    Ok(encoder)
+}
+
+/// Encode the buffer `pixels` of size `bounds` with pixels of type `color`,
+/// which must be 8 or 16-bit gray, RGB or RGBA, as a TIFF file compressed
+/// with `compression`.
+fn encode_tiff<D: Compression>(
+    output: &mut Cursor<Vec<u8>>,
+    pixels: &[u8],
+    bounds: (usize, usize),
+    color: ColorType,
+    compression: D,
+) -> Result<(), ImageError> {
+    let (width, height) = (bounds.0 as u32, bounds.1 as u32);
+    let words = || -> Vec<u16> {
+        let words = pixels.chunks(2).map(|b| u16::from_ne_bytes([b[0], b[1]]));
+        words.collect()
+    };
+    let mut encoder = TiffEncoder::new(output).map_err(tiff_error)?;
+    match color {
+        ColorType::L8 => {
+            encoder.write_image_with_compression::<Gray8, _>(width, height, compression, pixels)
+        }
+        ColorType::Rgb8 => {
+            encoder.write_image_with_compression::<RGB8, _>(width, height, compression, pixels)
+        }
+        ColorType::L16 => {
+            encoder.write_image_with_compression::<Gray16, _>(width, height, compression, &words())
+        }
+        ColorType::Rgb16 => {
+            encoder.write_image_with_compression::<RGB16, _>(width, height, compression, &words())
+        }
+        ColorType::Rgba8 => {
+            encoder.write_image_with_compression::<RGBA8, _>(width, height, compression, pixels)
+        }
+        ColorType::Rgba16 => {
+            encoder.write_image_with_compression::<RGBA16, _>(width, height, compression, &words())
+        }
+        _ => {
+            return Err(ImageError::Unsupported(
+                UnsupportedError::from_format_and_kind(
+                    ImageFormatHint::Exact(ImageFormat::Tiff),
+                    UnsupportedErrorKind::Color(color.into()),
+                ),
+            ))
+        }
+    }
+    .map_err(tiff_error)
+}
+
+/// Write the buffer `pixels` of size `bounds` with pixels of type `color`,
+/// which must be 8 or 16-bit gray, RGB or RGBA, to `output` as a farbfeld
+/// file: the magic and the big-endian width and height, followed by each
+/// pixel as 16-bit big-endian RGBA, opaque unless it has an alpha.
+fn encode_farbfeld<W: Write>(
+    output: &mut W,
+    pixels: &[u8],
+    bounds: (usize, usize),
+    color: ColorType,
+) -> Result<(), ImageError> {
+    let samples: Vec<u16> = match color {
+        ColorType::L8 | ColorType::Rgb8 | ColorType::Rgba8 => {
+            pixels.iter().map(|&p| u16::from(p) * 257).collect()
+        }
+        ColorType::L16 | ColorType::Rgb16 | ColorType::Rgba16 => pixels
+            .chunks(2)
+            .map(|b| u16::from_ne_bytes([b[0], b[1]]))
+            .collect(),
+        _ => {
+            return Err(ImageError::Unsupported(
+                UnsupportedError::from_format_and_kind(
+                    ImageFormatHint::Name("farbfeld".into()),
+                    UnsupportedErrorKind::Color(color.into()),
+                ),
+            ))
+        }
+    };
+    output.write_all(b"farbfeld")?;
+    output.write_all(&(bounds.0 as u32).to_be_bytes())?;
+    output.write_all(&(bounds.1 as u32).to_be_bytes())?;
+    for pixel in samples.chunks(color.channel_count().into()) {
+        let rgba = match *pixel {
+            [gray] => [gray, gray, gray, u16::MAX],
+            [r, g, b] => [r, g, b, u16::MAX],
+            [r, g, b, a] => [r, g, b, a],
+            _ => unreachable!(),
+        };
+        for sample in rgba {
+            output.write_all(&sample.to_be_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The `image` crate's error for the TIFF encoder's error `err`.
+fn tiff_error(err: TiffError) -> ImageError {
+    match err {
+        TiffError::IoError(err) => ImageError::IoError(err),
+        err => ImageError::Encoding(EncodingError::new(
+            ImageFormatHint::Exact(ImageFormat::Tiff),
+            err,
+        )),
+    }
+}
+
+/// Reorder the native-endian 16-bit samples in `bytes` to big endian.
+fn ne_to_be(bytes: &[u8]) -> Vec<u8> {
+    bytes
+        .chunks(2)
+        .flat_map(|b| u16::from_ne_bytes([b[0], b[1]]).to_be_bytes())
+        .collect()
+}
+
+/// Repeat each grayscale byte of `pixels` three times, for formats that
+/// only store RGB.
+fn gray_to_rgb(pixels: &[u8]) -> Vec<u8> {
+    pixels.iter().flat_map(|&p| [p, p, p]).collect()
+}
+
+/// The error returned for a file name without a supported extension.
+fn unsupported(filename: &str) -> ImageError {
+    let hint = match Path::new(filename).extension() {
+        Some(ext) => ImageFormatHint::PathExtension(ext.into()),
+        None => ImageFormatHint::Unknown,
+    };
+
+    ImageError::Unsupported(UnsupportedError::from_format_and_kind(
+        hint.clone(),
+        UnsupportedErrorKind::Format(hint),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::PngStrips;
+    use super::{encode_image, write_exr, write_image, write_image_with, write_raw, Format};
+    use super::{GifFrames, TiffCompression, WriteOptions};
+    use crate::{cmplx, escape_values, Palette, Params, Region};
+    use image::codecs::gif::GifDecoder;
+    use image::{AnimationDecoder, ColorType};
+    use std::time::Duration;
+
+    #[test]
+    fn from_filename() {
+        assert_eq!(Format::from_filename("mandel.png"), Some(Format::Png));
+        assert_eq!(Format::from_filename("out/mandel.JPG"), Some(Format::Jpeg));
+        assert_eq!(Format::from_filename("mandel.jpeg"), Some(Format::Jpeg));
+        assert_eq!(Format::from_filename("mandel.bmp"), Some(Format::Bmp));
+        assert_eq!(Format::from_filename("mandel.ppm"), Some(Format::Ppm));
+        assert_eq!(Format::from_filename("mandel.WebP"), Some(Format::WebP));
+        assert_eq!(Format::from_filename("mandel.tif"), Some(Format::Tiff));
+        assert_eq!(Format::from_filename("mandel.TIFF"), Some(Format::Tiff));
+        assert_eq!(Format::from_filename("mandel.ff"), Some(Format::Farbfeld));
+        assert_eq!(Format::from_filename("mandel.xcf"), None);
+        assert_eq!(Format::from_filename("mandel"), None);
+    }
+
+    #[test]
+    fn each_format_round_trips() {
+        let dir = std::env::temp_dir();
+        let pixels: Vec<u8> = (0..12 * 8).map(|i| (i * 2) as u8).collect();
+
+        for ext in ["png", "jpg", "bmp", "ppm", "webp", "tiff"] {
+            let path = dir.join(format!("mandelbrot-output-test.{}", ext));
+            let filename = path.to_str().unwrap();
+
+            write_image(filename, &pixels, (12, 8), ColorType::L8).unwrap();
+            let decoded = image::open(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!((decoded.width(), decoded.height()), (12, 8), "{}", ext);
+        }
+    }
+
+    #[test]
+    fn exr_holds_escape_values() {
+        let bounds = (9, 6);
+        let region = Region::new(bounds, cmplx!(-2.0, 1.0), cmplx!(0.5, -1.0));
+        let params = Params {
+            smooth: true,
+            ..Params::default()
+        };
+        let values = escape_values(&region, &params, 2);
+        assert!(values.contains(&f32::INFINITY));
+        let path = std::env::temp_dir().join("mandelbrot-output-test.exr");
+        write_exr(path.to_str().unwrap(), &values, bounds).unwrap();
+
+        let image = exr::prelude::read_first_flat_layer_from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let layer = image.layer_data;
+        assert_eq!((layer.size.0, layer.size.1), bounds);
+        let channel = &layer.channel_data.list[0];
+        assert_eq!(channel.name.to_string(), "Y");
+        match &channel.sample_data {
+            exr::prelude::FlatSamples::F32(samples) => assert_eq!(samples, &values),
+            samples => panic!("{:?}", samples),
+        }
+        let point = region.pixel_to_point((2, 1));
+        assert_eq!(
+            values[bounds.0 + 2],
+            params.escape_value(point).unwrap() as f32
+        );
+    }
+
+    #[test]
+    fn encode_to_memory() {
+        let pixels: Vec<u8> = (0..5 * 3 * 3).map(|i| (i * 5) as u8).collect();
+        let options = WriteOptions::default();
+        let mut buffer = Vec::new();
+        encode_image(
+            &mut buffer,
+            Format::Png,
+            &pixels,
+            (5, 3),
+            ColorType::Rgb8,
+            &options,
+        )
+        .unwrap();
+
+        assert!(buffer.starts_with(b"\x89PNG\r\n\x1a\n"));
+        let decoded = image::load_from_memory(&buffer).unwrap().into_rgb8();
+        assert_eq!(decoded.into_raw(), pixels);
+    }
+
+    #[test]
+    fn bmp_and_ppm_headers() {
+        let pixels: Vec<u8> = (0..7 * 3).map(|i| (i * 10) as u8).collect();
+        let encode = |format, pixels: &[u8], color| {
+            let mut buffer = Vec::new();
+            let options = WriteOptions::default();
+            encode_image(&mut buffer, format, pixels, (7, 3), color, &options).unwrap();
+            buffer
+        };
+
+        let bmp = encode(Format::Bmp, &pixels, ColorType::L8);
+        assert!(bmp.starts_with(b"BM"));
+        let field = |at: usize| i32::from_le_bytes(bmp[at..at + 4].try_into().unwrap());
+        assert_eq!(field(2) as usize, bmp.len());
+        assert_eq!((field(18), field(22).abs()), (7, 3));
+
+        // Gray pixels are written as RGB, since P6 has no gray.
+        let ppm = encode(Format::Ppm, &pixels, ColorType::L8);
+        let header = b"P6\n7 3 255\n";
+        assert!(ppm.starts_with(header), "{:?}", &ppm[..header.len()]);
+        assert_eq!(ppm.len(), header.len() + 7 * 3 * 3);
+        assert_eq!(ppm[header.len() + 3..header.len() + 6], [10; 3]);
+        let rgb: Vec<u8> = (0..7 * 3 * 3).map(|i| i as u8).collect();
+        let ppm = encode(Format::Ppm, &rgb, ColorType::Rgb8);
+        assert_eq!(ppm[header.len()..], rgb[..]);
+    }
+
+    #[test]
+    fn farbfeld() {
+        let encode = |pixels: &[u8], color| {
+            let mut buffer = Vec::new();
+            let options = WriteOptions::default();
+            encode_image(
+                &mut buffer,
+                Format::Farbfeld,
+                pixels,
+                (5, 2),
+                color,
+                &options,
+            )
+            .unwrap();
+            buffer
+        };
+        let pixels: Vec<u8> = (0..5 * 2).map(|i| (i * 20) as u8).collect();
+        let ff = encode(&pixels, ColorType::L8);
+
+        assert_eq!(&ff[..16], b"farbfeld\0\0\0\x05\0\0\0\x02");
+        assert_eq!(ff.len(), 16 + 5 * 2 * 8);
+        // Gray goes to all of red, green and blue, with opaque alpha.
+        assert_eq!(
+            ff[16 + 3 * 8..16 + 4 * 8],
+            [60, 60, 60, 60, 60, 60, 255, 255]
+        );
+
+        let wide: Vec<u16> = (0..5 * 2 * 3).map(|i| i * 2000).collect();
+        let bytes: Vec<u8> = wide.iter().flat_map(|p| p.to_ne_bytes()).collect();
+        let ff = encode(&bytes, ColorType::Rgb16);
+        assert_eq!(ff.len(), 16 + 5 * 2 * 8);
+        let sample = |at: usize| u16::from_be_bytes([ff[16 + at * 2], ff[17 + at * 2]]);
+        assert_eq!([8, 9, 10, 11].map(sample), [12000, 14000, 16000, 65535]);
+    }
+
+    #[test]
+    fn sixteen_bit_png() {
+        let path = std::env::temp_dir().join("mandelbrot-output-test-16.png");
+        let pixels: Vec<u16> = (0..6 * 4).map(|i| i * 2000).collect();
+        let bytes: Vec<u8> = pixels.iter().flat_map(|p| p.to_ne_bytes()).collect();
+
+        write_image(path.to_str().unwrap(), &bytes, (6, 4), ColorType::L16).unwrap();
+        let decoded = image::open(&path).unwrap().into_luma16();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(decoded.into_raw(), pixels);
+    }
+
+    #[test]
+    fn tiff_compressions() {
+        let path = std::env::temp_dir().join("mandelbrot-output-test.tif");
+        let filename = path.to_str().unwrap();
+        let pixels: Vec<u16> = (0..9 * 4 * 3).map(|i| i * 600).collect();
+        let bytes: Vec<u8> = pixels.iter().flat_map(|p| p.to_ne_bytes()).collect();
+
+        for compression in ["none", "lzw", "deflate"] {
+            let options = WriteOptions {
+                tiff_compression: TiffCompression::from_name(compression).unwrap(),
+                ..WriteOptions::default()
+            };
+            write_image_with(filename, &bytes, (9, 4), ColorType::Rgb16, &options).unwrap();
+            let decoded = image::open(&path).unwrap().into_rgb16();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(decoded.dimensions(), (9, 4), "{}", compression);
+            assert_eq!(decoded.get_pixel(5, 2).0, [69, 70, 71].map(|i| i * 600));
+            assert_eq!(decoded.into_raw(), pixels, "{}", compression);
+        }
+        assert_eq!(TiffCompression::from_name("jpeg"), None);
+    }
+
+    #[test]
+    fn png_text() {
+        let path = std::env::temp_dir().join("mandelbrot-output-test-text.png");
+        let options = WriteOptions {
+            text: vec![("Iterations".into(), "1000".into())],
+            ..WriteOptions::default()
+        };
+        for ext in ["png", "jpg"] {
+            let path = path.with_extension(ext);
+            let filename = path.to_str().unwrap();
+            write_image_with(filename, &[1, 2, 3, 4], (2, 2), ColorType::L8, &options).unwrap();
+            let decoded = image::open(&path).unwrap();
+            assert_eq!((decoded.width(), decoded.height()), (2, 2));
+            if ext == "png" {
+                let decoder = png::Decoder::new(std::fs::File::open(&path).unwrap());
+                let reader = decoder.read_info().unwrap();
+                let text = &reader.info().uncompressed_latin1_text;
+                assert_eq!(text.len(), 1);
+                assert_eq!((&*text[0].keyword, &*text[0].text), ("Iterations", "1000"));
+            }
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn png_strips() {
+        let path = std::env::temp_dir().join("mandelbrot-output-test-strips.png");
+        let pixels: Vec<u16> = (0..6 * 5 * 3).map(|i| i * 700).collect();
+
+        let options = WriteOptions::default();
+        let mut png =
+            PngStrips::create(path.to_str().unwrap(), (6, 5), ColorType::Rgb16, &options).unwrap();
+        for strip in pixels.chunks(6 * 2 * 3) {
+            png.write_rows(strip).unwrap();
+        }
+        png.finish().unwrap();
+        let decoded = image::open(&path).unwrap().into_rgb16();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(decoded.into_raw(), pixels);
+    }
+
+    #[test]
+    fn gif_frames() {
+        let path = std::env::temp_dir().join("mandelbrot-output-test.gif");
+        let filename = path.to_str().unwrap();
+        let delay = Duration::from_millis(50);
+        let fire = Palette::Fire;
+        let first: Vec<u8> = [[0, 0, 0], fire.color(0.5), fire.color(1.0), [1, 2, 3]].concat();
+        let second: Vec<u8> = first.iter().rev().copied().collect();
+
+        let mut gif = GifFrames::create(filename, (2, 2), Some(fire), delay).unwrap();
+        gif.write_frame(&first).unwrap();
+        gif.write_frame(&second).unwrap();
+        gif.finish().unwrap();
+        let decoder = GifDecoder::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let frames = decoder.into_frames().collect_frames().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(frames.len(), 2);
+        assert_eq!(Duration::from(frames[0].delay()), delay);
+        let rgba = frames[0].buffer();
+        // Palette colors survive, others go to the nearest one.
+        for (i, color) in [[0, 0, 0], fire.color(0.5), fire.color(1.0), [0, 0, 0]]
+            .iter()
+            .enumerate()
+        {
+            assert_eq!(&rgba.as_raw()[i * 4..i * 4 + 3], color, "{}", i);
+        }
+    }
+
+    #[test]
+    fn raw_counts() {
+        let dir = std::env::temp_dir();
+        let counts = [0, 1, 2, 255, 7, 255];
+
+        let path = dir.join("mandelbrot-output-test.raw");
+        write_raw(path.to_str().unwrap(), &counts, (3, 2), 255).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let words: Vec<u32> = bytes
+            .chunks(4)
+            .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+            .collect();
+        assert_eq!(words, [3, 2, 255, 0, 1, 2, 255, 7, 255]);
+
+        let path = dir.join("mandelbrot-output-test.csv");
+        write_raw(path.to_str().unwrap(), &counts, (3, 2), 255).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(text, "0,1,2\n255,7,255\n");
+    }
+
+    #[test]
+    fn unsupported_extension() {
+        let path = std::env::temp_dir().join("mandelbrot-output-test.xcf");
+        let err = write_image(path.to_str().unwrap(), &[0], (1, 1), ColorType::L8);
+
+        assert!(matches!(err, Err(image::ImageError::Unsupported(_))));
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,500 @@
+//! Drawing on top of rendered images.
+
+use std::ops::Range;
+
+use num_complex::Complex64;
+
+use crate::{Region, Sample};
+
+/// How far gridlines go from each pixel's color towards its opposite.
+const GRID_STRENGTH: f64 = 0.35;
+
+/// How far the axes go from each pixel's color towards its opposite.
+const AXIS_STRENGTH: f64 = 1.0;
+
+/// How much of each pixel's brightness contour lines keep.
+const CONTOUR_SHADE: f64 = 0.3;
+
+/// The widest a scale bar gets, as a fraction of the width of the image.
+const SCALE_BAR_FRACTION: f64 = 0.25;
+
+/// The glyphs of scale bar labels, three pixels wide and five high, each
+/// row a bit per pixel with the leftmost highest.
+const GLYPHS: [(char, [u8; 5]); 13] = [
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b111, 0b001, 0b111, 0b100, 0b111]),
+    ('3', [0b111, 0b001, 0b111, 0b001, 0b111]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b111, 0b001, 0b111]),
+    ('6', [0b111, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b001, 0b001, 0b001]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b111]),
+    ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+    ('e', [0b000, 0b111, 0b111, 0b100, 0b111]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000]),
+];
+
+/// How far the dots marking the points of an orbit reach from the pixel
+/// each point falls in, so that they're three pixels across.
+const DOT_RADIUS: isize = 1;
+
+/// Draw `orbit` over the image of `region` in `pixels`, which holds
+/// `channels` samples per pixel: a white dot at each point, with white
+/// lines joining each point to the next. Whatever falls outside the image
+/// is clipped.
+pub fn draw_orbit<P: Sample>(
+    pixels: &mut [P],
+    region: &Region,
+    channels: usize,
+    orbit: &[Complex64],
+) {
+    let (width, height) = region.bounds;
+    let mut plot = |col: isize, row: isize| {
+        if (0..width as isize).contains(&col) && (0..height as isize).contains(&row) {
+            let at = (row as usize * width + col as usize) * channels;
+            pixels[at..at + channels].fill(P::from_u64(P::MAX));
+        }
+    };
+    let positions: Vec<(f64, f64)> = orbit.iter().map(|&z| region.position(z)).collect();
+    for pair in positions.windows(2) {
+        if let Some((start, end)) = clip(pair[0], pair[1], (width as f64, height as f64)) {
+            // Steps of at most half a pixel leave no gaps.
+            let steps = ((end.0 - start.0).abs().max((end.1 - start.1).abs()) * 2.0).ceil();
+            for i in 0..=steps as usize {
+                let t = if steps == 0.0 { 0.0 } else { i as f64 / steps };
+                let col = start.0 + (end.0 - start.0) * t;
+                let row = start.1 + (end.1 - start.1) * t;
+                plot(col.floor() as isize, row.floor() as isize);
+            }
+        }
+    }
+    for &(col, row) in &positions {
+        // Far off points have no dot on the image, and would overflow the
+        // offsets to the pixels around them.
+        let near = (width + height) as f64;
+        if !(col.abs() <= near && row.abs() <= near) {
+            continue;
+        }
+        let (col, row) = (col.floor() as isize, row.floor() as isize);
+        for dy in -DOT_RADIUS..=DOT_RADIUS {
+            for dx in -DOT_RADIUS..=DOT_RADIUS {
+                plot(col + dx, row + dy);
+            }
+        }
+    }
+}
+
+/// Draw a grid over the image of `region` in `pixels`, which holds
+/// `channels` samples per pixel: faint lines through the pixels at each
+/// multiple of `step` along either axis, and stronger ones along the axes
+/// themselves. Lines are drawn by pushing colors towards their opposites,
+/// so that they show up over both light and dark parts of the image.
+pub fn draw_grid<P: Sample>(pixels: &mut [P], region: &Region, channels: usize, step: f64) {
+    let (width, height) = region.bounds;
+    let size = region.pixel_size();
+    let cols: Vec<f64> = (0..width)
+        .map(|col| {
+            let re = region.pixel_to_point((col, 0)).re;
+            line_strength(re, re + size.re, step)
+        })
+        .collect();
+    let rows = (0..height).map(|row| {
+        let im = region.pixel_to_point((0, row)).im;
+        line_strength(im, im + size.im, step)
+    });
+
+    let max = P::MAX as f64;
+    for (row, line) in rows.zip(pixels.chunks_mut(width * channels)) {
+        for (&col, pixel) in cols.iter().zip(line.chunks_mut(channels)) {
+            let strength = col.max(row);
+            if strength == 0.0 {
+                continue;
+            }
+            for channel in pixel {
+                let value = channel.to_f64();
+                let value = value + (max - 2.0 * value) * strength;
+                *channel = P::from_u64(value.round() as u64);
+            }
+        }
+    }
+}
+
+/// How strongly to draw the grid with spacing `step` through the pixels
+/// covering `from` up to but not including `to` along one axis: not at all
+/// unless they hold a multiple of `step`, and most strongly at zero.
+fn line_strength(from: f64, to: f64, step: f64) -> f64 {
+    let (holds, first) = match from < to {
+        true => (0.0 >= from && 0.0 < to, (from / step).ceil() * step < to),
+        false => (0.0 <= from && 0.0 > to, (from / step).floor() * step > to),
+    };
+    match (holds, first) {
+        (true, _) => AXIS_STRENGTH,
+        (false, true) => GRID_STRENGTH,
+        (false, false) => 0.0,
+    }
+}
+
+/// Darken the pixels of `pixels`, an image of size `bounds` with
+/// `channels` samples per pixel, that lie on contours of the escape values
+/// `values`, as `escape_values` gives them: those whose value is past a
+/// multiple of `step` that one of the four pixels next to them hasn't yet
+/// reached. Points that never escape have no contours.
+pub fn draw_contours<P: Sample>(
+    pixels: &mut [P],
+    bounds: (usize, usize),
+    channels: usize,
+    values: &[f32],
+    step: f64,
+) {
+    let (width, height) = bounds;
+    let band = |value: f32| (f64::from(value) / step).floor();
+    for row in 0..height {
+        for col in 0..width {
+            let value = values[row * width + col];
+            if value.is_infinite() {
+                continue;
+            }
+            let neighbors = [
+                (col > 0).then(|| row * width + col - 1),
+                (col + 1 < width).then(|| row * width + col + 1),
+                (row > 0).then(|| (row - 1) * width + col),
+                (row + 1 < height).then(|| (row + 1) * width + col),
+            ];
+            let contour = neighbors
+                .into_iter()
+                .flatten()
+                .any(|i| band(values[i]) < band(value));
+            if contour {
+                let at = (row * width + col) * channels;
+                for channel in &mut pixels[at..at + channels] {
+                    *channel = P::from_u64((channel.to_f64() * CONTOUR_SHADE).round() as u64);
+                }
+            }
+        }
+    }
+}
+
+/// The length of the scale bar for `region`: the longest of 1, 2 or 5
+/// times a power of ten units of the plane that fits in a quarter of the
+/// width of the view, as its digit and power of ten, and as a number of
+/// pixels.
+pub fn scale_bar_length(region: &Region) -> ((u32, i32), usize) {
+    let pixel = region.pixel_size().re.abs();
+    let widest = pixel * region.bounds.0 as f64 * SCALE_BAR_FRACTION;
+    let mut exponent = widest.log10().floor() as i32;
+    // Make up for rounding in the logarithm.
+    if 10f64.powi(exponent + 1) <= widest {
+        exponent += 1;
+    }
+    let power = 10f64.powi(exponent);
+    let digit = [5, 2, 1]
+        .into_iter()
+        .find(|&digit| f64::from(digit) * power <= widest)
+        .unwrap_or(1);
+    let pixels = (f64::from(digit) * power / pixel).round() as usize;
+    ((digit, exponent), pixels.max(1))
+}
+
+/// Draw a scale bar in the lower left corner of the image of `region` in
+/// `pixels`, which holds `channels` samples per pixel: a white bar as long
+/// as `scale_bar_length` says, labeled with how many units of the plane it
+/// spans, on a black box.
+pub fn draw_scale_bar<P: Sample>(pixels: &mut [P], region: &Region, channels: usize) {
+    let (width, height) = region.bounds;
+    let ((digit, exponent), bar) = scale_bar_length(region);
+    let label = format_length(digit, exponent);
+    // Each pixel of the font is this many pixels across.
+    let unit = 1 + width / 800;
+    let (margin, padding) = (4 * unit, 2 * unit);
+    let label_width = (label.len() * 4 - 1) * unit;
+    let inner = (bar.max(label_width), 9 * unit);
+    let left = margin;
+    let top = height.saturating_sub(margin + inner.1 + 2 * padding);
+
+    let mut fill = |cols: Range<usize>, rows: Range<usize>, value: u64| {
+        for row in rows.start..rows.end.min(height) {
+            for col in cols.start..cols.end.min(width) {
+                let at = (row * width + col) * channels;
+                pixels[at..at + channels].fill(P::from_u64(value));
+            }
+        }
+    };
+    let (right, bottom) = (left + inner.0 + 2 * padding, top + inner.1 + 2 * padding);
+    fill(left..right, top..bottom, 0);
+    let (left, top) = (left + padding, top + padding);
+    for (i, c) in label.chars().enumerate() {
+        let rows = GLYPHS
+            .iter()
+            .find(|glyph| glyph.0 == c)
+            .map_or([0; 5], |glyph| glyph.1);
+        for (y, bits) in rows.into_iter().enumerate() {
+            for x in (0..3).filter(|x| bits & (0b100 >> x) != 0) {
+                let (col, row) = (left + (i * 4 + x) * unit, top + y * unit);
+                fill(col..col + unit, row..row + unit, P::MAX);
+            }
+        }
+    }
+    fill(left..left + bar, top + 7 * unit..top + 9 * unit, P::MAX);
+}
+
+/// `digit` times ten to the power `exponent`, written out in full if it
+/// takes no more than four zeros and in scientific notation otherwise.
+fn format_length(digit: u32, exponent: i32) -> String {
+    match exponent {
+        0..=4 => format!("{}{}", digit, "0".repeat(exponent as usize)),
+        -4..=-1 => format!("0.{}{}", "0".repeat((-exponent - 1) as usize), digit),
+        _ => format!("{}e{}", digit, exponent),
+    }
+}
+
+/// The part of the line from `start` to `end` inside the rectangle from
+/// the origin to `size`, or `None` if it misses it, by Liang and Barsky's
+/// method.
+fn clip(start: (f64, f64), end: (f64, f64), size: (f64, f64)) -> Option<((f64, f64), (f64, f64))> {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let (mut enter, mut leave) = (0.0, 1.0);
+    for (p, q) in [
+        (-dx, start.0),
+        (dx, size.0 - start.0),
+        (-dy, start.1),
+        (dy, size.1 - start.1),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+            continue;
+        }
+        let t = q / p;
+        if p < 0.0 {
+            enter = f64::max(enter, t);
+        } else {
+            leave = f64::min(leave, t);
+        }
+    }
+    if enter > leave {
+        return None;
+    }
+    let at = |t: f64| (start.0 + dx * t, start.1 + dy * t);
+    Some((at(enter), at(leave)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::scale_bar_length;
+    use super::{
+        clip, draw_contours, draw_grid, draw_orbit, draw_scale_bar, format_length, line_strength,
+    };
+    use crate::{cmplx, escape_values, Params, Region};
+
+    #[test]
+    fn clipping() {
+        let size = (10.0, 5.0);
+        assert_eq!(
+            clip((1.0, 1.0), (3.0, 2.0), size),
+            Some(((1.0, 1.0), (3.0, 2.0)))
+        );
+        assert_eq!(
+            clip((5.0, 2.0), (25.0, 2.0), size),
+            Some(((5.0, 2.0), (10.0, 2.0)))
+        );
+        assert_eq!(clip((-10.0, -1.0), (20.0, -1.0), size), None);
+        assert_eq!(
+            clip((-5.0, 0.0), (5.0, 10.0), size),
+            Some(((0.0, 5.0), (0.0, 5.0)))
+        );
+    }
+
+    #[test]
+    fn strengths() {
+        assert_eq!(line_strength(0.6, 0.8, 0.5), 0.0);
+        assert_eq!(line_strength(0.9, 1.1, 1.0), 0.35);
+        assert_eq!(line_strength(1.0, 1.1, 1.0), 0.35);
+        assert_eq!(line_strength(0.9, 1.0, 1.0), 0.0);
+        assert_eq!(line_strength(1.1, 0.9, 1.0), 0.35);
+        assert_eq!(line_strength(1.1, 1.0, 1.0), 0.0);
+        assert_eq!(line_strength(-0.1, 0.1, 1.0), 1.0);
+        assert_eq!(line_strength(0.0, -0.1, 1.0), 1.0);
+        assert_eq!(line_strength(0.1, 0.0, 1.0), 0.0);
+    }
+
+    #[test]
+    fn gridlines() {
+        // An eighth of a unit per pixel, so lines every half unit fall on
+        // every fourth pixel, with the axes in the middle.
+        let bounds = (32, 16);
+        for flip_y in [false, true] {
+            let region = Region {
+                flip_y,
+                ..Region::new(bounds, cmplx!(-2.0, 1.0), cmplx!(2.0, -1.0))
+            };
+            let mut pixels = vec![0u8; bounds.0 * bounds.1];
+            draw_grid(&mut pixels, &region, 1, 0.5);
+
+            let column = |col: usize| -> Vec<u8> {
+                (0..bounds.1)
+                    .map(|row| pixels[row * bounds.0 + col])
+                    .collect()
+            };
+            let row = |row: usize| &pixels[row * bounds.0..(row + 1) * bounds.0];
+            assert!(column(4).iter().all(|&p| p >= 89));
+            assert!(column(16).iter().all(|&p| p == 255));
+            let (grid_rows, axis_row) = match flip_y {
+                false => ([0, 4, 12], 8),
+                true => ([15, 11, 3], 7),
+            };
+            assert!(row(axis_row).iter().all(|&p| p == 255));
+            for col in 0..bounds.0 {
+                let lit: Vec<usize> = (0..bounds.1)
+                    .filter(|&row| pixels[row * bounds.0 + col] > 0)
+                    .collect();
+                match col {
+                    _ if col % 4 == 0 => assert_eq!(lit.len(), bounds.1),
+                    _ => {
+                        let mut expected = grid_rows.to_vec();
+                        expected.push(axis_row);
+                        expected.sort_unstable();
+                        assert_eq!(lit, expected, "{} {}", col, flip_y);
+                    }
+                }
+            }
+            assert_eq!(row(grid_rows[1])[1], 89);
+        }
+
+        // Gridlines darken light pixels.
+        let region = Region::new((4, 4), cmplx!(-0.5, 2.0), cmplx!(1.5, 0.0));
+        let mut pixels = vec![255u8; 16];
+        draw_grid(&mut pixels, &region, 1, 1.0);
+        assert_eq!(pixels[..4], [166, 0, 166, 166]);
+        assert_eq!(pixels[4..8], [255, 0, 255, 166]);
+    }
+
+    #[test]
+    fn contours() {
+        let bounds = (60, 40);
+        let region = Region::new(bounds, cmplx!(-2.5, 1.5), cmplx!(1.5, -1.5));
+        let params = Params {
+            smooth: true,
+            ..Params::default()
+        };
+        let values = escape_values(&region, &params, 2);
+        let mut pixels = vec![200u8; bounds.0 * bounds.1];
+        draw_contours(&mut pixels, bounds, 1, &values, 0.5);
+        assert!(pixels.iter().all(|&p| p == 200 || p == 60));
+
+        // Each contour pixel has a neighbor on the other side of a
+        // multiple of the step, and no other pixel does.
+        let mut contours = 0;
+        for row in 1..bounds.1 - 1 {
+            for col in 1..bounds.0 - 1 {
+                let i = row * bounds.0 + col;
+                let value = f64::from(values[i]);
+                let crossed = [i - 1, i + 1, i - bounds.0, i + bounds.0].iter().any(|&n| {
+                    let threshold = (value / 0.5).floor() * 0.5;
+                    f64::from(values[n]) < threshold
+                });
+                let outside = value.is_finite();
+                assert_eq!(pixels[i] == 60, outside && crossed, "{} {}", col, row);
+                contours += usize::from(pixels[i] == 60);
+            }
+        }
+        assert!(contours > 100, "{}", contours);
+    }
+
+    #[test]
+    fn lengths() {
+        assert_eq!(format_length(1, 0), "1");
+        assert_eq!(format_length(5, 3), "5000");
+        assert_eq!(format_length(2, 5), "2e5");
+        assert_eq!(format_length(2, -1), "0.2");
+        assert_eq!(format_length(5, -4), "0.0005");
+        assert_eq!(format_length(1, -12), "1e-12");
+    }
+
+    #[test]
+    fn scale_bar() {
+        // A quarter of a view four units wide is one unit.
+        let region = Region::new((400, 300), cmplx!(-2.5, 1.5), cmplx!(1.5, -1.5));
+        assert_eq!(scale_bar_length(&region), ((1, 0), 100));
+        let region = Region::new((300, 200), cmplx!(-2.0, 1.0), cmplx!(1.0, -1.0));
+        assert_eq!(scale_bar_length(&region), ((5, -1), 50));
+        let region = Region::new((640, 480), cmplx!(-0.75, 0.1), cmplx!(-0.7499, 0.0999));
+        let ((digit, exponent), bar) = scale_bar_length(&region);
+        assert_eq!((digit, exponent), (2, -5));
+        let distance = f64::from(digit) * 10f64.powi(exponent);
+        let pixel = region.pixel_size().re;
+        assert!((bar as f64 * pixel - distance).abs() <= pixel / 2.0);
+
+        let region = Region::new((400, 300), cmplx!(-2.5, 1.5), cmplx!(1.5, -1.5));
+        let mut pixels = vec![128u8; 400 * 300];
+        draw_scale_bar(&mut pixels, &region, 1);
+        // The bar is the only run of white across the full length of a row,
+        // just above the bottom of its black box.
+        let runs: Vec<usize> = (0..300)
+            .filter(|&row| {
+                let line = &pixels[row * 400..(row + 1) * 400];
+                line.iter().filter(|&&p| p == 255).count() == 100
+                    && line[6..106].iter().all(|&p| p == 255)
+            })
+            .collect();
+        assert_eq!(runs, [292, 293]);
+        assert_eq!(pixels[294 * 400 + 6], 0);
+        assert_eq!(pixels[295 * 400 + 6], 0);
+        assert_eq!(pixels[296 * 400 + 6], 128);
+        // The label's 1 is above the bar, with its foot three pixels wide.
+        assert_eq!(pixels[289 * 400 + 5..289 * 400 + 10], [0, 255, 255, 255, 0]);
+    }
+
+    #[test]
+    fn escaping_orbit() {
+        let params = Params::default();
+        let orbit = params.orbit(cmplx!(0.5, 0.0));
+        let re: Vec<f64> = orbit.iter().map(|z| z.re).collect();
+        assert_eq!(re[..4], [0.0, 0.5, 0.75, 1.0625]);
+        assert_eq!(re.len(), 6);
+        assert!(re[5] > 2.0);
+
+        // A tenth of a unit per pixel, with the real axis along row 10.
+        let bounds = (40, 20);
+        for flip_y in [false, true] {
+            let region = Region {
+                flip_y,
+                ..Region::new(bounds, cmplx!(-1.0, 1.0), cmplx!(3.0, -1.0))
+            };
+            let mut pixels = vec![0u8; bounds.0 * bounds.1];
+            draw_orbit(&mut pixels, &region, 1, &orbit);
+            let row = |row: usize| &pixels[row * bounds.0..(row + 1) * bounds.0];
+            let lit = |row: usize| -> Vec<usize> {
+                (0..bounds.0)
+                    .filter(|&col| pixels[row * bounds.0 + col] == 255)
+                    .collect()
+            };
+
+            // The line runs from the start to the right edge, where the
+            // orbit leaves the view.
+            assert!(row(10)[..9].iter().all(|&p| p == 0));
+            assert!(row(10)[9..].iter().all(|&p| p == 255));
+            // Dots above and below the points at 0.0, 0.5, 0.75, 1.0625
+            // and 1.6289...
+            let dots = [9, 10, 11, 14, 15, 16, 17, 18, 19, 20, 21, 25, 26, 27];
+            assert_eq!(lit(9), dots, "{}", flip_y);
+            assert_eq!(lit(11), dots, "{}", flip_y);
+            for other in (0..9).chain(12..20) {
+                assert!(lit(other).is_empty(), "{} {}", other, flip_y);
+            }
+        }
+    }
+
+    #[test]
+    fn interior_orbits_run_to_the_limit() {
+        let params = Params {
+            limit: 50,
+            ..Params::default()
+        };
+        let orbit = params.orbit(cmplx!(-1.0, 0.0));
+        assert_eq!(orbit.len(), 51);
+        assert_eq!(orbit[49..], [cmplx!(-1.0, 0.0), cmplx!(0.0, 0.0)]);
+    }
+}
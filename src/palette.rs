@@ -0,0 +1,343 @@
+//! Color palettes for RGB output.
+
+use std::f64::consts::TAU;
+
+use crate::random::SplitMix;
+
+/// The number of color stops a `Palette::random` gradient is laid out in.
+const RANDOM_STOPS: usize = 33;
+
+/// A built-in color gradient. Palettes are defined by a list of color stops
+/// between 0.0 (points that escape at once) and 1.0 (points that reach the
+/// iteration limit); colors in between are interpolated linearly.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Palette {
+    Grayscale,
+    Fire,
+    Ocean,
+    /// A gradient loaded with `parse`, through these stops.
+    Custom(&'static [(f64, [u8; 3])]),
+}
+
+impl Palette {
+    /// Look up a palette by the name used on the command line.
+    pub fn from_name(name: &str) -> Option<Palette> {
+        match name {
+            "grayscale" => Some(Palette::Grayscale),
+            "fire" => Some(Palette::Fire),
+            "ocean" => Some(Palette::Ocean),
+            _ => None,
+        }
+    }
+
+    /// The name of this palette on the command line, or `"custom"` for one
+    /// loaded with `parse`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Palette::Grayscale => "grayscale",
+            Palette::Fire => "fire",
+            Palette::Ocean => "ocean",
+            Palette::Custom(_) => "custom",
+        }
+    }
+
+    /// Parse a gradient with one color stop per line, written as its
+    /// position from 0.0 to 1.0 followed by the red, green and blue values
+    /// from 0 to 255, like `0.5 255 140 0`. Positions must not decrease from
+    /// one stop to the next. Blank lines and lines starting with `#` are
+    /// ignored. Returns `None` unless there's at least one valid stop and
+    /// nothing else.
+    ///
+    /// The stops are kept for the rest of the program, so that palettes
+    /// stay `Copy`; parse each gradient once.
+    pub fn parse(text: &str) -> Option<Palette> {
+        let mut stops: Vec<(f64, [u8; 3])> = Vec::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let pos: f64 = fields.next()?.parse().ok()?;
+            let mut color = [0; 3];
+            for channel in &mut color {
+                *channel = fields.next()?.parse().ok()?;
+            }
+            let ordered = stops.last().is_none_or(|&(last, _)| last <= pos);
+            if fields.next().is_some() || !(0.0..=1.0).contains(&pos) || !ordered {
+                return None;
+            }
+            stops.push((pos, color));
+        }
+        if stops.is_empty() {
+            return None;
+        }
+
+        Some(Palette::Custom(Box::leak(stops.into_boxed_slice())))
+    }
+
+    /// A smooth gradient drawn at random from `seed`, always the same for
+    /// the same seed. Three to five colors are picked in the Oklab color
+    /// space, each lighter than the last and a random step round the hue
+    /// circle from it, and the gradient runs evenly between them in Oklab,
+    /// so that it brightens steadily rather than in fits.
+    ///
+    /// Like `parse`, the stops are kept for the rest of the program, and
+    /// the palette's name is `"custom"`.
+    pub fn random(seed: u64) -> Palette {
+        let mut random = SplitMix(seed);
+        let count = 3 + (random.next_u64() % 3) as usize;
+        let (dark, light) = (0.05 + 0.15 * random.unit(), 0.8 + 0.2 * random.unit());
+        let turn = if random.unit() < 0.5 { 1.0 } else { -1.0 };
+        let mut hue = random.unit();
+        let mut colors = Vec::with_capacity(count);
+        for i in 0..count {
+            let lightness = dark + (light - dark) * i as f64 / (count - 1) as f64;
+            let chroma = 0.04 + 0.12 * random.unit();
+            colors.push([
+                lightness,
+                chroma * (hue * TAU).cos(),
+                chroma * (hue * TAU).sin(),
+            ]);
+            hue += turn * (0.08 + 0.25 * random.unit());
+        }
+
+        let stops: Vec<(f64, [u8; 3])> = (0..RANDOM_STOPS)
+            .map(|i| {
+                let t = i as f64 / (RANDOM_STOPS - 1) as f64;
+                let x = t * (count - 1) as f64;
+                let k = (x as usize).min(count - 2);
+                let (c0, c1, f) = (colors[k], colors[k + 1], x - k as f64);
+                (
+                    t,
+                    oklab_to_srgb([0, 1, 2].map(|j| c0[j] + (c1[j] - c0[j]) * f)),
+                )
+            })
+            .collect();
+        Palette::Custom(Box::leak(stops.into_boxed_slice()))
+    }
+
+    /// The color stops of this palette, in increasing order of position.
+    fn stops(self) -> &'static [(f64, [u8; 3])] {
+        match self {
+            Palette::Custom(stops) => stops,
+            Palette::Grayscale => &[(0.0, [255, 255, 255]), (1.0, [0, 0, 0])],
+            Palette::Fire => &[
+                (0.0, [0, 0, 0]),
+                (0.15, [200, 30, 0]),
+                (0.4, [255, 140, 0]),
+                (0.7, [255, 230, 80]),
+                (1.0, [255, 255, 255]),
+            ],
+            Palette::Ocean => &[
+                (0.0, [0, 7, 40]),
+                (0.2, [0, 80, 160]),
+                (0.5, [0, 180, 220]),
+                (0.8, [150, 235, 255]),
+                (1.0, [255, 255, 255]),
+            ],
+        }
+    }
+
+    /// The color at position `t`, which is clamped to `0.0..=1.0`.
+    pub fn color(self, t: f64) -> [u8; 3] {
+        self.level(t).map(|c| c.round() as u8)
+    }
+
+    /// Like `color`, but without rounding the channels, which are on the
+    /// 0–255 scale.
+    pub fn level(self, t: f64) -> [f64; 3] {
+        gradient(self.stops(), t)
+    }
+}
+
+/// Turns escape values into colors, for `render_colorized`. Colorizers are
+/// shared between render threads.
+pub trait Colorizer: Sync {
+    /// The color of a pixel with escape value `escape`, or `None` inside
+    /// the set, for a render with the iteration limit `limit`.
+    fn color(&self, escape: Option<f64>, limit: u32) -> [u8; 3];
+}
+
+/// Palettes color escape values by their fraction of the limit, and the
+/// inside of the set black.
+impl Colorizer for Palette {
+    fn color(&self, escape: Option<f64>, limit: u32) -> [u8; 3] {
+        match escape {
+            Some(value) => Palette::color(*self, value / f64::from(limit)),
+            None => [0; 3],
+        }
+    }
+}
+
+/// The fully saturated, fully bright color of hue `h`, in turns from 0.0
+/// (red) through 1/3 (green) and 2/3 (blue) back to red at 1.0, with the
+/// channels on the 0–255 scale. Hues outside that range wrap around.
+pub fn hue(h: f64) -> [f64; 3] {
+    let sector = h.rem_euclid(1.0) * 6.0;
+    let channel = |offset: f64| {
+        let k = (offset + sector) % 6.0;
+        255.0 * (1.0 - (k.min(4.0 - k).clamp(0.0, 1.0)))
+    };
+
+    [channel(5.0), channel(3.0), channel(1.0)]
+}
+
+/// The sRGB color of the Oklab color `lab`, its lightness and its `a` and
+/// `b` axes, with colors outside the sRGB gamut clipped to it.
+fn oklab_to_srgb([l, a, b]: [f64; 3]) -> [u8; 3] {
+    let l_ = l + 0.396_337_777_4 * a + 0.215_803_757_3 * b;
+    let m_ = l - 0.105_561_345_8 * a - 0.063_854_172_8 * b;
+    let s_ = l - 0.089_484_177_5 * a - 1.291_485_548_0 * b;
+    let (l, m, s) = (l_.powi(3), m_.powi(3), s_.powi(3));
+    let linear = [
+        4.076_741_662_1 * l - 3.307_711_591_3 * m + 0.230_969_929_2 * s,
+        -1.268_438_004_6 * l + 2.609_757_401_1 * m - 0.341_319_396_5 * s,
+        -0.004_196_086_3 * l - 0.703_418_614_7 * m + 1.707_614_701_0 * s,
+    ];
+
+    linear.map(|v| (from_linear(v) * 255.0).round() as u8)
+}
+
+/// The linear light of the sRGB-encoded channel value `v`, both from 0.0
+/// to 1.0.
+pub fn to_linear(v: f64) -> f64 {
+    match v <= 0.040_45 {
+        true => v / 12.92,
+        false => ((v + 0.055) / 1.055).powf(2.4),
+    }
+}
+
+/// The sRGB encoding of the linear light `v`, the inverse of `to_linear`,
+/// clamped to 0.0 to 1.0.
+pub fn from_linear(v: f64) -> f64 {
+    let v = v.clamp(0.0, 1.0);
+    match v <= 0.003_130_8 {
+        true => 12.92 * v,
+        false => 1.055 * v.powf(1.0 / 2.4) - 0.055,
+    }
+}
+
+/// Interpolate linearly between the two `stops` surrounding `t`.
+fn gradient(stops: &[(f64, [u8; 3])], t: f64) -> [f64; 3] {
+    let t = t.clamp(0.0, 1.0);
+    let idx = match stops.iter().position(|&(pos, _)| pos >= t) {
+        Some(idx) => idx,
+        None => return stops[stops.len() - 1].1.map(f64::from),
+    };
+    if idx == 0 {
+        return stops[0].1.map(f64::from);
+    }
+
+    let ((p0, c0), (p1, c1)) = (stops[idx - 1], stops[idx]);
+    let f = (t - p0) / (p1 - p0);
+    let mut color = [0.0; 3];
+    for i in 0..3 {
+        let (a, b) = (f64::from(c0[i]), f64::from(c1[i]));
+        color[i] = a + (b - a) * f;
+    }
+
+    color
+}
+
+#[cfg(test)]
+mod test {
+    use super::{from_linear, hue, oklab_to_srgb, to_linear, Colorizer, Palette};
+
+    #[test]
+    fn from_name() {
+        assert_eq!(Palette::from_name("fire"), Some(Palette::Fire));
+        assert_eq!(Palette::from_name("ocean"), Some(Palette::Ocean));
+        assert_eq!(Palette::from_name("grayscale"), Some(Palette::Grayscale));
+        assert_eq!(Palette::from_name("plaid"), None);
+        assert_eq!(
+            Palette::from_name(Palette::Fire.name()),
+            Some(Palette::Fire)
+        );
+    }
+
+    #[test]
+    fn color() {
+        assert_eq!(Palette::Grayscale.color(0.0), [255, 255, 255]);
+        assert_eq!(Palette::Grayscale.color(0.5), [128, 128, 128]);
+        assert_eq!(Palette::Grayscale.color(1.0), [0, 0, 0]);
+        assert_eq!(Palette::Fire.color(0.15), [200, 30, 0]);
+        assert_eq!(Palette::Ocean.color(-1.0), [0, 7, 40]);
+        assert_eq!(Palette::Ocean.color(2.0), [255, 255, 255]);
+    }
+
+    #[test]
+    fn colorizer() {
+        let fire: &dyn Colorizer = &Palette::Fire;
+        assert_eq!(fire.color(Some(15.0), 100), [200, 30, 0]);
+        assert_eq!(fire.color(Some(100.0), 100), [255, 255, 255]);
+        assert_eq!(fire.color(None, 100), [0, 0, 0]);
+    }
+
+    #[test]
+    fn hues() {
+        assert_eq!(hue(0.0), [255.0, 0.0, 0.0]);
+        assert_eq!(hue(1.0 / 6.0), [255.0, 255.0, 0.0]);
+        assert_eq!(hue(1.0 / 3.0), [0.0, 255.0, 0.0]);
+        assert_eq!(hue(0.5), [0.0, 255.0, 255.0]);
+        assert_eq!(hue(2.0 / 3.0), [0.0, 0.0, 255.0]);
+        assert_eq!(hue(1.0), hue(0.0));
+        assert_eq!(hue(-0.5), hue(0.5));
+        let orange = hue(1.0 / 12.0);
+        assert!((orange[1] - 127.5).abs() < 1e-9 && orange[2] == 0.0);
+    }
+
+    #[test]
+    fn parse() {
+        let two = Palette::parse("0 0 0 0\n1.0 255 100 50\n").unwrap();
+        assert_eq!(two.color(0.5), [128, 50, 25]);
+        assert_eq!(two.color(0.0), [0, 0, 0]);
+        assert_eq!(two.color(1.0), [255, 100, 50]);
+
+        let text = "# sunset\n\n0.25 10 20 30\n  0.5 10 20 30\n0.75 210 220 230\n";
+        let partial = Palette::parse(text).unwrap();
+        // Past the first and last stops their colors hold.
+        assert_eq!(partial.color(0.0), [10, 20, 30]);
+        assert_eq!(partial.color(0.625), [110, 120, 130]);
+        assert_eq!(partial.color(1.0), [210, 220, 230]);
+
+        for bad in [
+            "",
+            "# nothing",
+            "0 0 0",
+            "0 0 0 0 0",
+            "0 0 0 256",
+            "1.5 0 0 0",
+            "0.5 0 0 0\n0.2 0 0 0",
+        ] {
+            assert_eq!(Palette::parse(bad), None, "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn linear_light() {
+        assert_eq!((to_linear(0.0), to_linear(1.0)), (0.0, 1.0));
+        assert!((from_linear(0.5) * 255.0 - 187.5).abs() < 0.1);
+        for v in [0.01, 0.2, 0.5, 0.9] {
+            assert!((from_linear(to_linear(v)) - v).abs() < 1e-12, "{}", v);
+        }
+    }
+
+    #[test]
+    fn random() {
+        assert_eq!(oklab_to_srgb([1.0, 0.0, 0.0]), [255, 255, 255]);
+        assert_eq!(oklab_to_srgb([0.0, 0.0, 0.0]), [0, 0, 0]);
+
+        let palette = Palette::random(42);
+        assert_eq!(palette, Palette::random(42));
+        assert_ne!(palette, Palette::random(43));
+        assert_eq!(palette.name(), "custom");
+        let brightness = |t| palette.color(t).iter().map(|&c| u32::from(c)).sum::<u32>();
+        assert!(brightness(0.0) < brightness(0.5) && brightness(0.5) < brightness(1.0));
+        // Neighbouring stops are close, so the gradient is smooth.
+        let stops = palette.stops();
+        for pair in stops.windows(2) {
+            let step = (0..3).map(|i| pair[0].1[i].abs_diff(pair[1].1[i])).max();
+            assert!(step < Some(40), "{:?}", pair);
+        }
+    }
+}
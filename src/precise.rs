@@ -0,0 +1,571 @@
+//! Rendering in fixed point of any precision, for views too deep for
+//! `f64`. Every operation works on limbs on the heap, which makes this far
+//! slower than `f64`, but it's exact up to the chosen number of bits
+//! however deep the view.
+
+use std::cmp::Ordering;
+use std::ops::{Add, Mul, Neg, Sub};
+use std::time::Instant;
+
+use crate::{in_rows, worker_index, Params, Progress, Region, Sample, Shading, Stats, BAILOUT};
+
+/// A signed fixed-point number with 64 integer bits and a fraction of a
+/// whole number of 64-bit limbs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fixed {
+    negative: bool,
+    /// The magnitude, least significant limb first; the last limb is the
+    /// integer part.
+    limbs: Vec<u64>,
+}
+
+impl Fixed {
+    /// Zero, with a fraction of at least `bits` bits.
+    pub fn zero(bits: u32) -> Fixed {
+        Fixed {
+            negative: false,
+            limbs: vec![0; bits.div_ceil(64) as usize + 1],
+        }
+    }
+
+    /// Zero, at the precision of `self`.
+    fn zero_like(&self) -> Fixed {
+        Fixed {
+            negative: false,
+            limbs: vec![0; self.limbs.len()],
+        }
+    }
+
+    /// The number of limbs in the fraction.
+    fn fraction(&self) -> usize {
+        self.limbs.len() - 1
+    }
+
+    /// `x`, which must be finite and less than 2^63 in magnitude, with a
+    /// fraction of at least `bits` bits. Bits of `x` below the fraction are
+    /// cut off.
+    pub fn from_f64(x: f64, bits: u32) -> Fixed {
+        assert!(x.abs() < 2f64.powi(63), "{} doesn't fit in a Fixed", x);
+        let mut fixed = Fixed::zero(bits);
+        let raw = x.abs().to_bits();
+        let (biased, fraction) = ((raw >> 52) as i64, raw & ((1 << 52) - 1));
+        let (mantissa, exponent) = match biased {
+            0 => (fraction, -1074),
+            _ => (fraction | 1 << 52, biased - 1075),
+        };
+        let shift = exponent + 64 * fixed.fraction() as i64;
+        if shift >= 0 {
+            let (limb, bit) = ((shift / 64) as usize, shift % 64);
+            fixed.limbs[limb] |= mantissa << bit;
+            if bit > 0 && limb + 1 < fixed.limbs.len() {
+                fixed.limbs[limb + 1] |= mantissa >> (64 - bit);
+            }
+        } else if shift > -64 {
+            fixed.limbs[0] = mantissa >> -shift;
+        }
+        fixed.negative = x < 0.0 && !fixed.is_zero();
+
+        fixed
+    }
+
+    /// This number rounded to an `f64`, give or take an ulp.
+    pub fn to_f64(&self) -> f64 {
+        let fraction = self.fraction() as i32;
+        let magnitude: f64 = (self.limbs.iter().enumerate())
+            .map(|(i, &limb)| limb as f64 * 2f64.powi(64 * (i as i32 - fraction)))
+            .sum();
+        if self.negative {
+            -magnitude
+        } else {
+            magnitude
+        }
+    }
+
+    /// Parse a decimal number like `-0.7436438870371587`, `5` or `1.5e-3`
+    /// into a fixed-point number with at least `bits` bits of fraction.
+    /// Digits beyond those bits are cut off. Returns `None` unless the
+    /// number is well-formed and its magnitude less than 2^63.
+    pub fn parse(s: &str, bits: u32) -> Option<Fixed> {
+        let s = s.trim();
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (mantissa, exponent) = match s.find(['e', 'E']) {
+            Some(at) => (&s[..at], s[at + 1..].parse::<i32>().ok()?),
+            None => (s, 0),
+        };
+        let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let digits = || whole.bytes().chain(fraction.bytes());
+        if digits().next().is_none() || !digits().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        let mut fixed = Fixed::zero(bits);
+        let int = fixed.fraction();
+        for digit in fraction.bytes().rev() {
+            fixed.limbs[int] = u64::from(digit - b'0');
+            fixed.div_small(10);
+        }
+        for digit in whole.bytes() {
+            let limb = fixed.limbs[int].checked_mul(10)?;
+            fixed.limbs[int] = limb.checked_add(u64::from(digit - b'0'))?;
+        }
+        for _ in 0..exponent.unsigned_abs() {
+            match exponent < 0 {
+                true => fixed.div_small(10),
+                false if !fixed.mul_small(10) => return None,
+                false => (),
+            }
+        }
+        fixed.negative = negative && !fixed.is_zero();
+
+        (fixed.limbs[int] < 1 << 63).then_some(fixed)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.limbs.iter().all(|&limb| limb == 0)
+    }
+
+    /// Multiply the magnitude by `k`, returning whether it still fits.
+    fn mul_small(&mut self, k: u64) -> bool {
+        let mut carry = 0;
+        for limb in &mut self.limbs {
+            let product = u128::from(*limb) * u128::from(k) + carry;
+            *limb = product as u64;
+            carry = product >> 64;
+        }
+        carry == 0
+    }
+
+    /// Divide the magnitude by `k`, cutting off the remainder.
+    fn div_small(&mut self, k: u64) {
+        let mut remainder = 0;
+        for limb in self.limbs.iter_mut().rev() {
+            let dividend = remainder << 64 | u128::from(*limb);
+            *limb = (dividend / u128::from(k)) as u64;
+            remainder = dividend % u128::from(k);
+        }
+        if self.is_zero() {
+            self.negative = false;
+        }
+    }
+
+    /// Whether this number is greater than the whole number `n`.
+    fn exceeds(&self, n: u64) -> bool {
+        let (fraction, int) = self.limbs.split_at(self.fraction());
+        !self.negative && (int[0] > n || (int[0] == n && fraction.iter().any(|&l| l != 0)))
+    }
+
+    /// Compare the magnitudes of `self` and `other`.
+    fn cmp_magnitude(&self, other: &Fixed) -> Ordering {
+        self.limbs.iter().rev().cmp(other.limbs.iter().rev())
+    }
+
+    /// The sum of `self` and `other` if `subtract` is false, and their
+    /// difference otherwise.
+    fn add_signed(&self, other: &Fixed, subtract: bool) -> Fixed {
+        assert_eq!(self.limbs.len(), other.limbs.len(), "mixed precisions");
+        let other_negative = other.negative != subtract;
+        if self.negative == other_negative {
+            let mut limbs = self.limbs.clone();
+            let mut carry = false;
+            for (limb, &b) in limbs.iter_mut().zip(&other.limbs) {
+                let (sum, c1) = limb.overflowing_add(b);
+                let (sum, c2) = sum.overflowing_add(u64::from(carry));
+                *limb = sum;
+                carry = c1 || c2;
+            }
+            return Fixed {
+                negative: self.negative,
+                limbs,
+            };
+        }
+
+        let (big, small, negative) = match self.cmp_magnitude(other) {
+            Ordering::Less => (other, self, other_negative),
+            _ => (self, other, self.negative),
+        };
+        let mut limbs = big.limbs.clone();
+        let mut borrow = false;
+        for (limb, &b) in limbs.iter_mut().zip(&small.limbs) {
+            let (diff, b1) = limb.overflowing_sub(b);
+            let (diff, b2) = diff.overflowing_sub(u64::from(borrow));
+            *limb = diff;
+            borrow = b1 || b2;
+        }
+        let mut fixed = Fixed { negative, limbs };
+        fixed.negative &= !fixed.is_zero();
+        fixed
+    }
+}
+
+impl Ord for Fixed {
+    fn cmp(&self, other: &Fixed) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => self.cmp_magnitude(other),
+            (true, true) => other.cmp_magnitude(self),
+        }
+    }
+}
+
+impl PartialOrd for Fixed {
+    fn partial_cmp(&self, other: &Fixed) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Add for &Fixed {
+    type Output = Fixed;
+
+    fn add(self, other: &Fixed) -> Fixed {
+        self.add_signed(other, false)
+    }
+}
+
+impl Sub for &Fixed {
+    type Output = Fixed;
+
+    fn sub(self, other: &Fixed) -> Fixed {
+        self.add_signed(other, true)
+    }
+}
+
+impl Neg for &Fixed {
+    type Output = Fixed;
+
+    fn neg(self) -> Fixed {
+        Fixed {
+            negative: !self.negative && !self.is_zero(),
+            limbs: self.limbs.clone(),
+        }
+    }
+}
+
+/// Products are cut off below the fraction, and past the integer part.
+impl Mul for &Fixed {
+    type Output = Fixed;
+
+    fn mul(self, other: &Fixed) -> Fixed {
+        assert_eq!(self.limbs.len(), other.limbs.len(), "mixed precisions");
+        let n = self.limbs.len();
+        let mut product = vec![0u64; 2 * n];
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0;
+            for (j, &b) in other.limbs.iter().enumerate() {
+                let sum = u128::from(a) * u128::from(b) + u128::from(product[i + j]) + carry;
+                product[i + j] = sum as u64;
+                carry = sum >> 64;
+            }
+            product[i + n] = carry as u64;
+        }
+        let fraction = self.fraction();
+        let mut fixed = Fixed {
+            negative: self.negative != other.negative,
+            limbs: product[fraction..fraction + n].to_vec(),
+        };
+        fixed.negative &= !fixed.is_zero();
+        fixed
+    }
+}
+
+/// The escape time of the Mandelbrot set's `c = re + im·i`, counted as
+/// `escape_time` counts it, iterated at the precision of `re` and `im`.
+pub fn escape_time(re: &Fixed, im: &Fixed, limit: u32) -> Option<u32> {
+    let bailout_sqr = (BAILOUT * BAILOUT) as u64;
+    let (mut x, mut y) = (re.zero_like(), im.zero_like());
+    for i in 0..limit {
+        let xy = &x * &y;
+        x = &(&(&x * &x) - &(&y * &y)) + re;
+        y = &(&xy + &xy) + im;
+        if (&(&x * &x) + &(&y * &y)).exceeds(bailout_sqr) {
+            return Some(i);
+        }
+    }
+
+    None
+}
+
+/// The corners of a view and the size of its image, like `Region`, in
+/// fixed point.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreciseRegion {
+    /// The real and imaginary parts of the upper left corner.
+    pub upper_left: (Fixed, Fixed),
+    /// The real and imaginary parts of the lower right corner.
+    pub lower_right: (Fixed, Fixed),
+    /// The width and height of the image in pixels.
+    pub bounds: (usize, usize),
+    /// Point the imaginary axis down the image, as for `Region::flip_y`.
+    pub flip_y: bool,
+}
+
+impl PreciseRegion {
+    /// The view of `region`, whose corners are converted exactly, given a
+    /// fraction of at least `bits` bits.
+    pub fn from_region(region: &Region, bits: u32) -> PreciseRegion {
+        let (upper_l, lower_r) = region.corners();
+        let fixed = |x: f64| Fixed::from_f64(x, bits);
+        PreciseRegion {
+            upper_left: (fixed(upper_l.re), fixed(upper_l.im)),
+            lower_right: (fixed(lower_r.re), fixed(lower_r.im)),
+            bounds: region.bounds,
+            flip_y: region.flip_y,
+        }
+    }
+
+    /// The view of size `bounds` between two opposite corners written like
+    /// `"RE,IM"`, read with a fraction of at least `bits` bits. The corners
+    /// may be given in either order.
+    pub fn parse(
+        bounds: (usize, usize),
+        corner: &str,
+        opposite: &str,
+        bits: u32,
+    ) -> Option<PreciseRegion> {
+        let parse = |s: &str| {
+            let (re, im) = s.split_once(',')?;
+            Some((Fixed::parse(re, bits)?, Fixed::parse(im, bits)?))
+        };
+        let ((re0, im0), (re1, im1)) = (parse(corner)?, parse(opposite)?);
+        let (left, right) = (re0.clone().min(re1.clone()), re0.max(re1));
+        let (bottom, top) = (im0.clone().min(im1.clone()), im0.max(im1));
+        Some(PreciseRegion {
+            upper_left: (left, top),
+            lower_right: (right, bottom),
+            bounds,
+            flip_y: false,
+        })
+    }
+
+    /// The view of size `bounds` centered on `center`, written like
+    /// `"RE,IM"`, with `zoom` as for `corners_from_center`, or `None` if
+    /// its corners wouldn't fit in a `Fixed`.
+    pub fn from_center(
+        bounds: (usize, usize),
+        center: &str,
+        zoom: f64,
+        bits: u32,
+    ) -> Option<PreciseRegion> {
+        let (re, im) = center.split_once(',')?;
+        let (re, im) = (Fixed::parse(re, bits)?, Fixed::parse(im, bits)?);
+        let limit = 2f64.powi(63);
+        let half_size = zoom.abs() * (bounds.1 as f64 / bounds.0 as f64).max(1.0);
+        let reach = re.to_f64().abs().max(im.to_f64().abs()) + half_size;
+        if !zoom.is_finite() || zoom.abs() >= limit || reach >= limit {
+            return None;
+        }
+        let half_width = Fixed::from_f64(zoom, bits);
+        let mut half_height = half_width.clone();
+        half_height.mul_small(bounds.1 as u64);
+        half_height.div_small(bounds.0 as u64);
+        Some(PreciseRegion {
+            upper_left: (&re - &half_width, &im + &half_height),
+            lower_right: (&re + &half_width, &im - &half_height),
+            bounds,
+            flip_y: false,
+        })
+    }
+
+    /// The point the pixel `pixel` covers, which `Region::pixel_to_point`
+    /// would give for the same view.
+    pub fn pixel_to_point(&self, pixel: (usize, usize)) -> (Fixed, Fixed) {
+        let row = match self.flip_y {
+            false => pixel.1,
+            true => self.bounds.1 - 1 - pixel.1,
+        };
+        let (upper_l, lower_r) = (&self.upper_left, &self.lower_right);
+        let mut re = &lower_r.0 - &upper_l.0;
+        re.mul_small(pixel.0 as u64);
+        re.div_small(self.bounds.0 as u64);
+        let mut im = &upper_l.1 - &lower_r.1;
+        im.mul_small(row as u64);
+        im.div_small(self.bounds.1 as u64);
+
+        (&upper_l.0 + &re, &upper_l.1 - &im)
+    }
+}
+
+/// Render `region` into `pixels`, which holds `params.channels()` samples
+/// per pixel, on `threads` threads like `render_parallel`, iterating at the
+/// precision of its corners. `params` must ask for `plain_escape_counts`.
+pub fn render_precise<P: Sample>(
+    pixels: &mut [P],
+    region: &PreciseRegion,
+    params: &Params,
+    threads: usize,
+) -> Stats {
+    render_precise_inner(pixels, region, params, threads, None)
+}
+
+/// Like `render_precise`, counting finished rows in `progress` as
+/// `render_parallel_with_progress` does.
+pub fn render_precise_with_progress<P: Sample>(
+    pixels: &mut [P],
+    region: &PreciseRegion,
+    params: &Params,
+    threads: usize,
+    progress: &Progress,
+) -> Stats {
+    render_precise_inner(pixels, region, params, threads, Some(progress))
+}
+
+fn render_precise_inner<P: Sample>(
+    pixels: &mut [P],
+    region: &PreciseRegion,
+    params: &Params,
+    threads: usize,
+    progress: Option<&Progress>,
+) -> Stats {
+    assert!(
+        params.plain_escape_counts(),
+        "precise render of an unsupported render"
+    );
+    let (bounds, channels) = (region.bounds, params.channels());
+    assert!(pixels.len() == bounds.0 * bounds.1 * channels);
+
+    let paint = |band: &mut [P], top: usize| {
+        let start = Instant::now();
+        let mut stats = Stats::default();
+        for (i, line) in band.chunks_mut(bounds.0 * channels).enumerate() {
+            if progress.is_some_and(Progress::is_cancelled) {
+                break;
+            }
+            for (col, pixel) in line.chunks_mut(channels).enumerate() {
+                let (re, im) = region.pixel_to_point((col, top + i));
+                let value = escape_time(&re, &im, params.limit).map(f64::from);
+                params.fill(value, Shading::Linear, pixel);
+                params.apply_gamma(pixel);
+                stats.record(value);
+            }
+            if let Some(progress) = progress {
+                progress.add_rows(1);
+            }
+        }
+        if let Some(progress) = progress {
+            let rows = band.len() / (bounds.0 * channels).max(1);
+            progress.add_band(worker_index(), top, rows, start.elapsed());
+        }
+        stats
+    };
+    in_rows(
+        pixels,
+        bounds.0 * channels,
+        threads,
+        paint,
+        Stats::default,
+        Stats::merge,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{escape_time, render_precise, Fixed, PreciseRegion};
+    use crate::{cmplx, render, Palette, Params, Region};
+
+    #[test]
+    fn arithmetic() {
+        let f = |x: f64| Fixed::from_f64(x, 128);
+        assert_eq!((&f(1.5) + &f(-2.25)).to_f64(), -0.75);
+        assert_eq!((&f(-1.5) - &f(-2.25)).to_f64(), 0.75);
+        assert_eq!((&f(-1.5) * &f(2.25)).to_f64(), -3.375);
+        assert_eq!(&f(0.5) - &f(0.5), f(0.0));
+        assert_eq!((-&f(3.0)).to_f64(), -3.0);
+        assert!(f(-2.0) < f(-1.0) && f(-1.0) < f(0.0) && f(0.0) < f(1e-30));
+        for x in [0.0, 1.0, -0.1, 3e-20, -123.456, 2f64.powi(-128)] {
+            assert_eq!(f(x).to_f64(), x);
+        }
+        // Below the fraction bits are cut off.
+        assert_eq!(f(2f64.powi(-129)), f(0.0));
+
+        // 1 + 2^-100 keeps its last bit, which an f64 can't.
+        let tiny = &f(2f64.powi(-50)) * &f(2f64.powi(-50));
+        let sum = &f(1.0) + &tiny;
+        assert_eq!(&sum - &f(1.0), tiny);
+        assert_eq!(sum.to_f64(), 1.0);
+    }
+
+    #[test]
+    fn parse() {
+        let pp = |s: &str| Fixed::parse(s, 128);
+        assert_eq!(pp("2"), Some(Fixed::from_f64(2.0, 128)));
+        assert_eq!(pp("-0.5"), Some(Fixed::from_f64(-0.5, 128)));
+        assert_eq!(pp("+.25e1").map(|x| x.to_f64()), Some(2.5));
+        assert_eq!(pp("-0").map(|x| x.to_f64()), Some(0.0));
+        assert!((pp("-1.5e-3").unwrap().to_f64() + 1.5e-3).abs() < 1e-18);
+
+        // The digits past 17 are kept.
+        let diff = &pp("0.1000000000000000000000000001").unwrap() - &pp("0.1").unwrap();
+        assert!((diff.to_f64() - 1e-28).abs() < 1e-36, "{}", diff.to_f64());
+        for bad in ["", "-", ".", "1.2.3", "1e", "0x10", "inf", "1,5", "1e30"] {
+            assert_eq!(pp(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn matches_f64_escape_times() {
+        let bits = 128;
+        let fixed = |x: f64| Fixed::from_f64(x, bits);
+        for (re, im) in [
+            (-0.75, 0.1),
+            (0.3, 0.5),
+            (-1.25, 0.0),
+            (0.0, 0.0),
+            (2.0, 2.0),
+        ] {
+            assert_eq!(
+                escape_time(&fixed(re), &fixed(im), 500),
+                crate::escape_time(cmplx!(), cmplx!(re, im), 500),
+                "{} {}",
+                re,
+                im
+            );
+        }
+    }
+
+    #[test]
+    fn matches_f64_render() {
+        let bounds = (32, 24);
+        let region = Region::new(bounds, cmplx!(-1.20, 0.35), cmplx!(-1.0, 0.20));
+        let precise = PreciseRegion::from_region(&region, 128);
+        let (x, y) = precise.pixel_to_point((17, 9));
+        assert_eq!(
+            cmplx!(x.to_f64(), y.to_f64()),
+            region.pixel_to_point((17, 9))
+        );
+        // Decimal corners are read past the precision of f64.
+        let parsed = PreciseRegion::parse(bounds, "-1.0,0.20", "-1.20,0.35", 128).unwrap();
+        let (re, im) = &parsed.upper_left;
+        assert_eq!((re.to_f64(), im.to_f64()), (-1.2, 0.35));
+        assert_ne!(parsed, precise);
+
+        for palette in [None, Some(Palette::Ocean)] {
+            let params = Params {
+                palette,
+                ..Params::default()
+            };
+            let mut expected = vec![0u8; bounds.0 * bounds.1 * params.channels()];
+            render(&mut expected, &region, &params);
+            let mut pixels = vec![0u8; expected.len()];
+            render_precise(&mut pixels, &precise, &params, 3);
+            assert_eq!(pixels, expected);
+        }
+
+        let centered = PreciseRegion::from_center(bounds, "-1.1,0.275", 0.1, 128).unwrap();
+        let (re, im) = centered.upper_left;
+        assert!((re.to_f64() + 1.2).abs() < 1e-15 && (im.to_f64() - 0.35).abs() < 1e-15);
+    }
+
+    #[test]
+    fn from_center_out_of_range() {
+        let from_center = |center, zoom| PreciseRegion::from_center((4, 4), center, zoom, 128);
+        for zoom in [1e300, 2f64.powi(63), f64::INFINITY, f64::NAN] {
+            assert_eq!(from_center("0,0", zoom), None);
+        }
+        assert_eq!(from_center("9e18,0", 1e18), None);
+        assert_eq!(from_center("0,-9e18", 1e18), None);
+        assert!(from_center("0,0", 1e18).is_some());
+    }
+}
@@ -0,0 +1,91 @@
+//! Well-known views of the Mandelbrot set, for a first look around.
+
+use num_complex::Complex64;
+
+use crate::cmplx;
+
+/// A named view of the Mandelbrot set with an iteration limit that brings
+/// out its detail.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    /// The whole set.
+    Full,
+    /// The valley between the main cardioid and the period 2 bulb.
+    SeahorseValley,
+    /// The valley at the cusp on the right of the main cardioid.
+    ElephantValley,
+    /// Spirals of three arms in the valley between the main cardioid and
+    /// its largest bulb at the top.
+    TripleSpiral,
+}
+
+impl Preset {
+    /// Every preset, in the order they're listed in the help.
+    pub const ALL: [Preset; 4] = [
+        Preset::Full,
+        Preset::SeahorseValley,
+        Preset::ElephantValley,
+        Preset::TripleSpiral,
+    ];
+
+    /// Look up a preset by the name used on the command line.
+    pub fn from_name(name: &str) -> Option<Preset> {
+        Preset::ALL.into_iter().find(|preset| preset.name() == name)
+    }
+
+    /// The name of this preset on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Full => "full",
+            Preset::SeahorseValley => "seahorse-valley",
+            Preset::ElephantValley => "elephant-valley",
+            Preset::TripleSpiral => "triple-spiral",
+        }
+    }
+
+    /// The upper left and lower right corners of the view, in a 4:3 aspect
+    /// ratio.
+    pub fn corners(self) -> (Complex64, Complex64) {
+        match self {
+            Preset::Full => (cmplx!(-2.2, 1.2), cmplx!(1.0, -1.2)),
+            Preset::SeahorseValley => (cmplx!(-0.79, 0.13), cmplx!(-0.71, 0.07)),
+            Preset::ElephantValley => (cmplx!(0.26, 0.023), cmplx!(0.3, -0.007)),
+            Preset::TripleSpiral => (cmplx!(-0.0917, 0.6575), cmplx!(-0.0837, 0.6515)),
+        }
+    }
+
+    /// The iteration limit suggested for this view.
+    pub fn limit(self) -> u32 {
+        match self {
+            Preset::Full => 255,
+            Preset::SeahorseValley => 500,
+            Preset::ElephantValley => 1000,
+            Preset::TripleSpiral => 2000,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Preset;
+
+    #[test]
+    fn from_name() {
+        for preset in Preset::ALL {
+            assert_eq!(Preset::from_name(preset.name()), Some(preset));
+        }
+        assert_eq!(Preset::from_name("seahorse"), None);
+    }
+
+    #[test]
+    fn corners() {
+        for preset in Preset::ALL {
+            let (u_l, l_r) = preset.corners();
+            let (width, height) = (l_r.re - u_l.re, u_l.im - l_r.im);
+            assert!(width > 0.0 && height > 0.0, "{:?}", preset);
+            assert!((width / height - 4.0 / 3.0).abs() < 1e-9, "{:?}", preset);
+            assert!(u_l.norm() < 4.0 && l_r.norm() < 4.0, "{:?}", preset);
+            assert!(preset.limit() > 0);
+        }
+    }
+}
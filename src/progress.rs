@@ -0,0 +1,191 @@
+//! Tracking how far a render has got, for reporting while it runs.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A count of rendered rows shared between the render threads and whoever
+/// reports on them, who can also cancel the render.
+#[derive(Debug)]
+pub struct Progress {
+    rows: AtomicUsize,
+    total: usize,
+    start: Instant,
+    cancelled: AtomicBool,
+    /// The times of the bands rendered so far, if they're being recorded.
+    bands: Option<Mutex<Vec<BandTime>>>,
+}
+
+/// How long one band of rows took to render, one call of a render
+/// thread's work. On more than one thread, bands are single rows.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BandTime {
+    /// The render thread, numbered from 0.
+    pub thread: usize,
+    /// The first row of the band.
+    pub top: usize,
+    /// The number of rows in the band.
+    pub rows: usize,
+    /// The wall-clock time the band took.
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// Start tracking a render of `total` rows.
+    pub fn new(total: usize) -> Progress {
+        Progress {
+            rows: AtomicUsize::new(0),
+            total,
+            start: Instant::now(),
+            cancelled: AtomicBool::new(false),
+            bands: None,
+        }
+    }
+
+    /// Like `new`, also recording how long each band of rows takes to
+    /// render, for `band_times`.
+    pub fn with_band_times(total: usize) -> Progress {
+        Progress {
+            bands: Some(Mutex::new(Vec::new())),
+            ..Progress::new(total)
+        }
+    }
+
+    /// Record that the band of `rows` rows starting at row `top` took
+    /// `elapsed` to render on render thread `thread`, if band times are
+    /// being recorded. Called from the render threads.
+    pub fn add_band(&self, thread: usize, top: usize, rows: usize, elapsed: Duration) {
+        if let Some(bands) = &self.bands {
+            bands.lock().unwrap().push(BandTime {
+                thread,
+                top,
+                rows,
+                elapsed,
+            });
+        }
+    }
+
+    /// The times of the bands rendered so far, in the order they finished.
+    /// Empty unless made by `with_band_times`.
+    pub fn band_times(&self) -> Vec<BandTime> {
+        match &self.bands {
+            Some(bands) => bands.lock().unwrap().clone(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Count `rows` more rows as done. Called from the render threads.
+    pub fn add_rows(&self, rows: usize) {
+        self.rows.fetch_add(rows, Ordering::Relaxed);
+    }
+
+    /// Number of rows done so far.
+    pub fn rows(&self) -> usize {
+        self.rows.load(Ordering::Relaxed)
+    }
+
+    /// Whether every row has been rendered, or the render was cancelled.
+    pub fn is_done(&self) -> bool {
+        self.rows() >= self.total || self.is_cancelled()
+    }
+
+    /// Ask the render to stop. Rows that aren't started yet are skipped and
+    /// left black, so the image can still be written with what's done.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether `cancel` has been called.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// The fraction of rows done, from 0.0 to 1.0.
+    pub fn fraction(&self) -> f64 {
+        match self.total {
+            0 => 1.0,
+            total => self.rows().min(total) as f64 / total as f64,
+        }
+    }
+
+    /// Estimated time until the render is done, assuming the remaining rows
+    /// take as long as the ones so far. `None` until a row is done.
+    pub fn remaining(&self) -> Option<Duration> {
+        let fraction = self.fraction();
+        if fraction == 0.0 {
+            return None;
+        }
+        Some(self.start.elapsed().mul_f64((1.0 - fraction) / fraction))
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:3.0}% done", 100.0 * self.fraction())?;
+        match self.remaining() {
+            Some(eta) => {
+                let secs = eta.as_secs();
+                write!(f, ", about {}:{:02} remaining", secs / 60, secs % 60)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::Progress;
+
+    #[test]
+    fn fraction_and_eta() {
+        let progress = Progress::new(4);
+        assert_eq!(progress.fraction(), 0.0);
+        assert_eq!(progress.remaining(), None);
+        assert_eq!(progress.to_string(), "  0% done");
+
+        progress.add_rows(1);
+        assert_eq!(progress.fraction(), 0.25);
+        assert!(progress.remaining().is_some());
+        assert!(!progress.is_done());
+
+        progress.add_rows(3);
+        assert!(progress.is_done());
+        assert_eq!(progress.to_string(), "100% done, about 0:00 remaining");
+        assert!(Progress::new(0).is_done());
+    }
+
+    #[test]
+    fn band_times() {
+        let progress = Progress::with_band_times(4);
+        progress.add_band(0, 0, 2, Duration::from_millis(3));
+        std::thread::scope(|scope| {
+            scope.spawn(|| progress.add_band(1, 2, 2, Duration::from_millis(5)));
+        });
+        progress.add_band(0, 0, 2, Duration::from_millis(1));
+
+        let times = progress.band_times();
+        let threads: Vec<usize> = times.iter().map(|band| band.thread).collect();
+        assert_eq!(threads, [0, 1, 0]);
+        assert_eq!((times[1].top, times[1].rows), (2, 2));
+        assert_eq!(times[1].elapsed, Duration::from_millis(5));
+
+        let untimed = Progress::new(4);
+        untimed.add_band(0, 0, 2, Duration::from_millis(3));
+        assert!(untimed.band_times().is_empty());
+    }
+
+    #[test]
+    fn cancel() {
+        let progress = Progress::new(4);
+        progress.add_rows(1);
+        assert!(!progress.is_cancelled());
+
+        progress.cancel();
+        assert!(progress.is_cancelled());
+        assert!(progress.is_done());
+        assert_eq!(progress.fraction(), 0.25);
+    }
+}
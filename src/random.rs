@@ -0,0 +1,44 @@
+//! A small seedable random number generator, so that renders drawing on
+//! random points come out the same every time for the same seed.
+
+/// A SplitMix64 generator.
+pub struct SplitMix(pub u64);
+
+impl SplitMix {
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A float uniformly distributed between -1.0 and 1.0.
+    pub fn uniform(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+    }
+
+    /// A float uniformly distributed between 0.0 and 1.0, excluding 1.0.
+    pub fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SplitMix;
+
+    #[test]
+    fn uniform() {
+        let mut random = SplitMix(7);
+        let values: Vec<f64> = (0..10_000).map(|_| random.uniform()).collect();
+        assert!(values.iter().all(|v| (-1.0..1.0).contains(v)));
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        assert!(mean.abs() < 0.03, "{}", mean);
+
+        let values: Vec<f64> = (0..10_000).map(|_| random.unit()).collect();
+        assert!(values.iter().all(|v| (0.0..1.0).contains(v)));
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        assert!((mean - 0.5).abs() < 0.015, "{}", mean);
+    }
+}
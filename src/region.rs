@@ -0,0 +1,231 @@
+//! The part of the complex plane shown by an image.
+
+use num_complex::Complex64;
+
+use crate::{cmplx, pixel_to_point};
+
+/// An image of size `bounds` showing the rectangle of the complex plane
+/// between `upper_left` and `lower_right`, with the imaginary axis pointing
+/// up unless `flip_y` is set.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    /// The width and height of the image in pixels.
+    pub bounds: (usize, usize),
+    /// The point at the upper left corner of the first pixel.
+    pub upper_left: Complex64,
+    /// The point at the lower right corner of the last pixel.
+    pub lower_right: Complex64,
+    /// Whether the imaginary part grows down the image, as on math plots
+    /// with rows numbered from the bottom, so the image comes out mirrored
+    /// top to bottom.
+    pub flip_y: bool,
+}
+
+impl Region {
+    pub fn new(bounds: (usize, usize), upper_left: Complex64, lower_right: Complex64) -> Region {
+        Region {
+            bounds,
+            upper_left,
+            lower_right,
+            flip_y: false,
+        }
+    }
+
+    /// The corners of the view, upper left first.
+    pub fn corners(&self) -> (Complex64, Complex64) {
+        (self.upper_left, self.lower_right)
+    }
+
+    /// The point on the plane at the upper left corner of `pixel`, given
+    /// as its column and row. With `flip_y` this is the point of the pixel
+    /// in the mirrored row, so the two orientations sample the same points.
+    pub fn pixel_to_point(&self, pixel: (usize, usize)) -> Complex64 {
+        let row = match self.flip_y {
+            false => pixel.1,
+            true => self.bounds.1 - 1 - pixel.1,
+        };
+        pixel_to_point(
+            self.bounds,
+            (pixel.0, row),
+            self.upper_left,
+            self.lower_right,
+        )
+    }
+
+    /// The size of one pixel on the plane, as the step from one pixel to
+    /// the next along each axis.
+    pub fn pixel_size(&self) -> Complex64 {
+        let (upper_l, lower_r) = self.corners();
+        cmplx!(
+            (lower_r.re - upper_l.re) / self.bounds.0 as f64,
+            (lower_r.im - upper_l.im) / self.bounds.1 as f64
+        )
+    }
+
+    /// The pixel whose area contains `point`, the inverse of
+    /// `pixel_to_point`, or `None` if the point lies outside the image.
+    pub fn point_to_pixel(&self, point: Complex64) -> Option<(usize, usize)> {
+        let (upper_l, lower_r) = self.corners();
+        let (width, height) = (self.bounds.0 as f64, self.bounds.1 as f64);
+        let col = (point.re - upper_l.re) / (lower_r.re - upper_l.re) * width;
+        let row = (upper_l.im - point.im) / (upper_l.im - lower_r.im) * height;
+        if !((0.0..width).contains(&col) && (0.0..height).contains(&row)) {
+            return None;
+        }
+        let (col, row) = (col as usize, row as usize);
+        match self.flip_y {
+            false => Some((col, row)),
+            true => Some((col, self.bounds.1 - 1 - row)),
+        }
+    }
+
+    /// Where `point` falls on the image, as a column and row that count
+    /// fractions of a pixel from the upper left corner of the first. Points
+    /// outside the image give positions outside `0.0..width` and
+    /// `0.0..height`.
+    pub fn position(&self, point: Complex64) -> (f64, f64) {
+        let (upper_l, lower_r) = self.corners();
+        let (width, height) = (self.bounds.0 as f64, self.bounds.1 as f64);
+        let col = (point.re - upper_l.re) / (lower_r.re - upper_l.re) * width;
+        let row = (upper_l.im - point.im) / (upper_l.im - lower_r.im) * height;
+        match self.flip_y {
+            false => (col, row),
+            true => (col, height - row),
+        }
+    }
+
+    /// The view of the same image centered on the middle of `pixel`, with
+    /// its width and height scaled by `factor`: below 1.0 zooms in, above
+    /// 1.0 zooms out.
+    pub fn zoomed_at(&self, pixel: (usize, usize), factor: f64) -> Region {
+        let center = self.pixel_to_point(pixel) + self.pixel_size() * 0.5;
+        let half = (self.lower_right - self.upper_left) * 0.5 * factor;
+        Region {
+            upper_left: center - half,
+            lower_right: center + half,
+            ..*self
+        }
+    }
+
+    /// The view moved by `cols` pixels to the right and `rows` pixels down
+    /// the image, either of which may be negative or fractional.
+    pub fn panned(&self, cols: f64, rows: f64) -> Region {
+        let size = self.pixel_size();
+        let rows = if self.flip_y { -rows } else { rows };
+        let offset = cmplx!(cols * size.re, rows * size.im);
+        Region {
+            upper_left: self.upper_left + offset,
+            lower_right: self.lower_right + offset,
+            ..*self
+        }
+    }
+
+    /// The `height` rows of this image starting at row `top`, as an image
+    /// of their own. Its points can differ from those of the same pixels
+    /// here in the last bit, so the render functions work out rows of a
+    /// band from the whole region instead.
+    pub fn sub_band(&self, top: usize, height: usize) -> Region {
+        if self.flip_y {
+            let unflipped = Region {
+                flip_y: false,
+                ..*self
+            };
+            let band = unflipped.sub_band(self.bounds.1 - top - height, height);
+            return Region {
+                flip_y: true,
+                ..band
+            };
+        }
+        Region {
+            bounds: (self.bounds.0, height),
+            upper_left: self.pixel_to_point((0, top)),
+            lower_right: self.pixel_to_point((self.bounds.0, top + height)),
+            flip_y: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Region;
+    use crate::cmplx;
+
+    #[test]
+    fn sub_band() {
+        let region = Region::new((100, 100), cmplx!(-1.0, 1.0), cmplx!(1.0, -1.0));
+        let band = region.sub_band(25, 50);
+
+        assert_eq!(band.bounds, (100, 50));
+        assert_eq!(band.corners(), (cmplx!(-1.0, 0.5), cmplx!(1.0, -0.5)));
+        assert_eq!(band.pixel_size(), region.pixel_size());
+        for pixel in [(0, 0), (30, 10), (99, 49)] {
+            let whole = region.pixel_to_point((pixel.0, pixel.1 + 25));
+            assert!((band.pixel_to_point(pixel) - whole).norm() < 1e-12);
+        }
+        assert_eq!(region.sub_band(0, 100), region);
+
+        let flipped = Region {
+            flip_y: true,
+            ..region
+        };
+        let band = flipped.sub_band(10, 50);
+        for pixel in [(0, 0), (30, 10), (99, 49)] {
+            let whole = flipped.pixel_to_point((pixel.0, pixel.1 + 10));
+            assert!((band.pixel_to_point(pixel) - whole).norm() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn flip_y() {
+        let region = Region::new((4, 4), cmplx!(-1.0, 1.0), cmplx!(1.0, -1.0));
+        let flipped = Region {
+            flip_y: true,
+            ..region
+        };
+        assert_eq!(region.pixel_to_point((0, 0)), cmplx!(-1.0, 1.0));
+        assert_eq!(flipped.pixel_to_point((0, 0)), cmplx!(-1.0, -0.5));
+        for row in 0..4 {
+            assert_eq!(
+                flipped.pixel_to_point((1, row)),
+                region.pixel_to_point((1, 3 - row))
+            );
+        }
+        assert_eq!(flipped.pixel_size(), region.pixel_size());
+    }
+
+    #[test]
+    fn point_to_pixel() {
+        let region = Region::new((8, 6), cmplx!(-2.0, 1.5), cmplx!(2.0, -1.5));
+        for flip_y in [false, true] {
+            let region = Region { flip_y, ..region };
+            for pixel in [(0, 0), (3, 2), (7, 5)] {
+                let point = region.pixel_to_point(pixel);
+                assert_eq!(region.point_to_pixel(point), Some(pixel));
+                let inside = point + region.pixel_size() * 0.5;
+                assert_eq!(region.point_to_pixel(inside), Some(pixel));
+            }
+        }
+        for outside in [cmplx!(2.0, 0.0), cmplx!(-2.1, 0.0), cmplx!(0.0, 1.6)] {
+            assert_eq!(region.point_to_pixel(outside), None);
+        }
+    }
+
+    #[test]
+    fn zoom_and_pan() {
+        let region = Region::new((8, 6), cmplx!(-2.0, 1.5), cmplx!(2.0, -1.5));
+        let zoomed = region.zoomed_at((5, 1), 0.5);
+        assert_eq!(zoomed.corners(), (cmplx!(-0.25, 1.5), cmplx!(1.75, 0.0)));
+        assert_eq!(region.zoomed_at((4, 3), 3.0).bounds, region.bounds);
+
+        let panned = region.panned(2.0, -1.0);
+        assert_eq!(panned.corners(), (cmplx!(-1.0, 2.0), cmplx!(3.0, -1.0)));
+        for flip_y in [false, true] {
+            let region = Region { flip_y, ..region };
+            let point = region.pixel_to_point((3, 2));
+            assert_eq!(region.panned(2.0, -1.0).pixel_to_point((1, 3)), point);
+            let zoomed = region.zoomed_at((3, 2), 0.25);
+            let center = point + region.pixel_size() * 0.5;
+            assert_eq!((zoomed.upper_left + zoomed.lower_right) * 0.5, center);
+        }
+    }
+}
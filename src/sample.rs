@@ -0,0 +1,111 @@
+//! The channel types a render can produce: 8 or 16 bits per channel.
+
+use std::borrow::Cow;
+
+use image::ColorType;
+
+/// One channel of a rendered pixel. Rendering is generic over this so the
+/// same code fills `u8` buffers for ordinary images and `u16` buffers for
+/// 16-bit images, which keep high iteration counts apart.
+pub trait Sample: Copy + Default + PartialEq + Send + Sync {
+    /// The value of white.
+    const MAX: u64;
+
+    /// Convert `value`, which is at most `MAX`.
+    fn from_u64(value: u64) -> Self;
+
+    /// Convert an 8-bit channel value, possibly fractional, to this depth.
+    fn from_8bit(value: f64) -> Self;
+
+    /// This value as a float, for averaging.
+    fn to_f64(self) -> f64;
+
+    /// The color type of a buffer with `channels` of these per pixel.
+    fn color_type(channels: usize) -> ColorType;
+
+    /// The bytes of `pixels` as the image encoders expect them, which for
+    /// 16-bit samples is native endian.
+    fn as_bytes(pixels: &[Self]) -> Cow<'_, [u8]>;
+
+    /// The bytes of `pixels` as PNG stores them, which for 16-bit samples
+    /// is big endian.
+    fn as_png_bytes(pixels: &[Self]) -> Cow<'_, [u8]>;
+}
+
+impl Sample for u8 {
+    const MAX: u64 = u8::MAX as u64;
+
+    fn from_u64(value: u64) -> Self {
+        value as u8
+    }
+
+    fn from_8bit(value: f64) -> Self {
+        value.round() as u8
+    }
+
+    fn to_f64(self) -> f64 {
+        f64::from(self)
+    }
+
+    fn color_type(channels: usize) -> ColorType {
+        match channels {
+            1 => ColorType::L8,
+            4 => ColorType::Rgba8,
+            _ => ColorType::Rgb8,
+        }
+    }
+
+    fn as_bytes(pixels: &[Self]) -> Cow<'_, [u8]> {
+        Cow::Borrowed(pixels)
+    }
+
+    fn as_png_bytes(pixels: &[Self]) -> Cow<'_, [u8]> {
+        Cow::Borrowed(pixels)
+    }
+}
+
+impl Sample for u16 {
+    const MAX: u64 = u16::MAX as u64;
+
+    fn from_u64(value: u64) -> Self {
+        value as u16
+    }
+
+    fn from_8bit(value: f64) -> Self {
+        (value * 257.0).round() as u16
+    }
+
+    fn to_f64(self) -> f64 {
+        f64::from(self)
+    }
+
+    fn color_type(channels: usize) -> ColorType {
+        match channels {
+            1 => ColorType::L16,
+            4 => ColorType::Rgba16,
+            _ => ColorType::Rgb16,
+        }
+    }
+
+    fn as_bytes(pixels: &[Self]) -> Cow<'_, [u8]> {
+        Cow::Owned(pixels.iter().flat_map(|p| p.to_ne_bytes()).collect())
+    }
+
+    fn as_png_bytes(pixels: &[Self]) -> Cow<'_, [u8]> {
+        Cow::Owned(pixels.iter().flat_map(|p| p.to_be_bytes()).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Sample;
+
+    #[test]
+    fn from_8bit() {
+        assert_eq!(u8::from_8bit(127.5), 128);
+        assert_eq!(u16::from_8bit(255.0), u16::MAX);
+        assert_eq!(u16::from_8bit(1.0), 257);
+        assert_eq!(u16::as_bytes(&[0x0102]).len(), 2);
+        assert_eq!(*u16::as_png_bytes(&[0x0102]), [1, 2]);
+    }
+}
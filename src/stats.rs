@@ -0,0 +1,120 @@
+//! Statistics about the escape values of a render.
+
+use std::fmt;
+
+/// Escape statistics over the pixels of a render. Escape values are
+/// iteration counts, or continuous escape values for smooth shading.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stats {
+    /// Number of pixels rendered.
+    pub pixels: u64,
+    /// Number of pixels that reached the iteration limit without escaping.
+    pub interior: u64,
+    /// Smallest escape value of an escaping pixel.
+    pub min: f64,
+    /// Largest escape value of an escaping pixel.
+    pub max: f64,
+    /// Sum of the escape values of the escaping pixels.
+    pub sum: f64,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Stats {
+            pixels: 0,
+            interior: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            sum: 0.0,
+        }
+    }
+}
+
+impl Stats {
+    /// Count one pixel with escape value `value`, or `None` for a pixel
+    /// that reached the limit.
+    pub fn record(&mut self, value: Option<f64>) {
+        self.pixels += 1;
+        match value {
+            None => self.interior += 1,
+            Some(value) => {
+                self.min = self.min.min(value);
+                self.max = self.max.max(value);
+                self.sum += value;
+            }
+        }
+    }
+
+    /// Combine the statistics of two disjoint sets of pixels.
+    pub fn merge(self, other: Stats) -> Stats {
+        Stats {
+            pixels: self.pixels + other.pixels,
+            interior: self.interior + other.interior,
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+            sum: self.sum + other.sum,
+        }
+    }
+
+    /// Number of pixels that escaped before the limit.
+    pub fn escaped(&self) -> u64 {
+        self.pixels - self.interior
+    }
+
+    /// Mean escape value of the escaping pixels, if there are any.
+    pub fn mean(&self) -> Option<f64> {
+        match self.escaped() {
+            0 => None,
+            n => Some(self.sum / n as f64),
+        }
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "pixels:      {}", self.pixels)?;
+        write!(f, "at limit:    {}", self.interior)?;
+        if self.pixels > 0 {
+            let percent = 100.0 * self.interior as f64 / self.pixels as f64;
+            write!(f, " ({:.1}%)", percent)?;
+        }
+        match self.mean() {
+            None => write!(f, "\nno pixels escaped"),
+            Some(mean) => write!(
+                f,
+                "\nescape min:  {:.2}\nescape max:  {:.2}\nescape mean: {:.2}",
+                self.min, self.max, mean
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Stats;
+
+    #[test]
+    fn record_and_merge() {
+        let mut a = Stats::default();
+        a.record(Some(3.0));
+        a.record(None);
+        let mut b = Stats::default();
+        b.record(Some(1.0));
+        b.record(Some(8.0));
+
+        let stats = a.merge(b);
+        assert_eq!((stats.pixels, stats.interior, stats.escaped()), (4, 1, 3));
+        assert_eq!((stats.min, stats.max), (1.0, 8.0));
+        assert_eq!(stats.mean(), Some(4.0));
+        assert_eq!(Stats::default().merge(stats), stats);
+    }
+
+    #[test]
+    fn nothing_escaped() {
+        let mut stats = Stats::default();
+        stats.record(None);
+
+        assert_eq!(stats.mean(), None);
+        assert!(stats.to_string().ends_with("no pixels escaped"));
+    }
+}
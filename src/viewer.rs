@@ -0,0 +1,265 @@
+//! The `--interactive` window, for exploring the set by clicking around
+//! it: a left click zooms in on the point clicked, a right click zooms
+//! out from it, the arrow keys pan, `s` writes the view to the image file
+//! and `q` or Escape closes the window.
+//!
+//! The window is drawn with Xlib directly, so this only runs on X11.
+
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int, c_long, c_uint, c_ulong, c_void};
+use std::ptr;
+use std::time::Instant;
+
+use mandelbrot::{render_to_vec, write_image, Params, Region};
+
+use crate::CliError;
+
+/// How much a click zooms in, or out, by.
+const ZOOM_FACTOR: f64 = 0.5;
+
+/// How far the arrow keys pan, as a fraction of the view.
+const PAN_FRACTION: f64 = 0.125;
+
+/// An X server connection.
+enum Display {}
+
+/// The fields of `XButtonEvent` and `XKeyEvent`, which only differ in the
+/// name of the last but one.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct InputEvent {
+    kind: c_int,
+    serial: c_ulong,
+    send_event: c_int,
+    display: *mut Display,
+    window: c_ulong,
+    root: c_ulong,
+    subwindow: c_ulong,
+    time: c_ulong,
+    x: c_int,
+    y: c_int,
+    x_root: c_int,
+    y_root: c_int,
+    state: c_uint,
+    /// The button, or the keycode.
+    detail: c_uint,
+    same_screen: c_int,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ClientMessageEvent {
+    kind: c_int,
+    serial: c_ulong,
+    send_event: c_int,
+    display: *mut Display,
+    window: c_ulong,
+    message_type: c_ulong,
+    format: c_int,
+    data: [c_long; 5],
+}
+
+#[repr(C)]
+union Event {
+    kind: c_int,
+    input: InputEvent,
+    client: ClientMessageEvent,
+    pad: [c_long; 24],
+}
+
+const KEY_PRESS: c_int = 2;
+const BUTTON_PRESS: c_int = 4;
+const EXPOSE: c_int = 12;
+const CLIENT_MESSAGE: c_int = 33;
+
+const KEY_PRESS_MASK: c_long = 1 << 0;
+const BUTTON_PRESS_MASK: c_long = 1 << 2;
+const EXPOSURE_MASK: c_long = 1 << 15;
+
+const Z_PIXMAP: c_int = 2;
+
+const XK_ESCAPE: c_ulong = 0xff1b;
+const XK_LEFT: c_ulong = 0xff51;
+const XK_UP: c_ulong = 0xff52;
+const XK_RIGHT: c_ulong = 0xff53;
+const XK_DOWN: c_ulong = 0xff54;
+const XK_Q: c_ulong = 0x71;
+const XK_S: c_ulong = 0x73;
+
+#[link(name = "X11")]
+extern "C" {
+    fn XOpenDisplay(name: *const c_char) -> *mut Display;
+    fn XCloseDisplay(display: *mut Display) -> c_int;
+    fn XDefaultScreen(display: *mut Display) -> c_int;
+    fn XRootWindow(display: *mut Display, screen: c_int) -> c_ulong;
+    fn XBlackPixel(display: *mut Display, screen: c_int) -> c_ulong;
+    fn XDefaultDepth(display: *mut Display, screen: c_int) -> c_int;
+    fn XDefaultVisual(display: *mut Display, screen: c_int) -> *mut c_void;
+    fn XDefaultGC(display: *mut Display, screen: c_int) -> *mut c_void;
+    fn XCreateSimpleWindow(
+        display: *mut Display,
+        parent: c_ulong,
+        x: c_int,
+        y: c_int,
+        width: c_uint,
+        height: c_uint,
+        border_width: c_uint,
+        border: c_ulong,
+        background: c_ulong,
+    ) -> c_ulong;
+    fn XStoreName(display: *mut Display, window: c_ulong, name: *const c_char) -> c_int;
+    fn XSelectInput(display: *mut Display, window: c_ulong, mask: c_long) -> c_int;
+    fn XInternAtom(display: *mut Display, name: *const c_char, only_if_exists: c_int) -> c_ulong;
+    fn XSetWMProtocols(
+        display: *mut Display,
+        window: c_ulong,
+        protocols: *mut c_ulong,
+        count: c_int,
+    ) -> c_int;
+    fn XMapWindow(display: *mut Display, window: c_ulong) -> c_int;
+    fn XCreateImage(
+        display: *mut Display,
+        visual: *mut c_void,
+        depth: c_uint,
+        format: c_int,
+        offset: c_int,
+        data: *mut c_char,
+        width: c_uint,
+        height: c_uint,
+        bitmap_pad: c_int,
+        bytes_per_line: c_int,
+    ) -> *mut c_void;
+    fn XPutImage(
+        display: *mut Display,
+        drawable: c_ulong,
+        gc: *mut c_void,
+        image: *mut c_void,
+        src_x: c_int,
+        src_y: c_int,
+        dest_x: c_int,
+        dest_y: c_int,
+        width: c_uint,
+        height: c_uint,
+    ) -> c_int;
+    fn XNextEvent(display: *mut Display, event: *mut Event) -> c_int;
+    fn XLookupKeysym(event: *mut InputEvent, index: c_int) -> c_ulong;
+    fn XFlush(display: *mut Display) -> c_int;
+}
+
+/// Show the image of `region` in a window, re-rendering it as the view is
+/// zoomed and panned until the window is closed. `s` writes the current
+/// view to `filename`. Each new view's corners are printed, so that it can
+/// be rendered again from the command line.
+pub fn explore(filename: &str, mut region: Region, params: &Params) -> Result<(), CliError> {
+    let (width, height) = region.bounds;
+    let (cols, rows) = (width as f64, height as f64);
+    // 24-bit displays store each pixel as a little-endian 32-bit word.
+    let mut frame = vec![0u32; width * height];
+    let title = CString::new(format!("mandelbrot {}", filename)).unwrap();
+    let delete = CString::new("WM_DELETE_WINDOW").unwrap();
+
+    // SAFETY: the display is only used on this thread and closed at the
+    // end, and `frame` outlives the image drawn from it.
+    unsafe {
+        let display = XOpenDisplay(ptr::null());
+        if display.is_null() {
+            return Err(CliError::Display);
+        }
+        let screen = XDefaultScreen(display);
+        if XDefaultDepth(display, screen) != 24 {
+            XCloseDisplay(display);
+            return Err(CliError::Display);
+        }
+        let root = XRootWindow(display, screen);
+        let black = XBlackPixel(display, screen);
+        let (w, h) = (width as c_uint, height as c_uint);
+        let window = XCreateSimpleWindow(display, root, 0, 0, w, h, 0, black, black);
+        XStoreName(display, window, title.as_ptr());
+        XSelectInput(
+            display,
+            window,
+            KEY_PRESS_MASK | BUTTON_PRESS_MASK | EXPOSURE_MASK,
+        );
+        let mut delete = XInternAtom(display, delete.as_ptr(), 0);
+        XSetWMProtocols(display, window, &mut delete, 1);
+        XMapWindow(display, window);
+        let gc = XDefaultGC(display, screen);
+        let visual = XDefaultVisual(display, screen);
+        // Never destroyed, as that would free `frame` along with it.
+        let data = frame.as_mut_ptr().cast();
+        let image = XCreateImage(display, visual, 24, Z_PIXMAP, 0, data, w, h, 32, 0);
+
+        let (mut rendered, mut pixels) = (None, Vec::new());
+        let result = loop {
+            if rendered != Some(region) {
+                pixels = show(&mut frame, &region, params);
+                XPutImage(display, window, gc, image, 0, 0, 0, 0, w, h);
+                XFlush(display);
+                rendered = Some(region);
+                print_view(&region);
+            }
+            let mut event = Event { pad: [0; 24] };
+            XNextEvent(display, &mut event);
+            match event.kind {
+                EXPOSE => {
+                    XPutImage(display, window, gc, image, 0, 0, 0, 0, w, h);
+                }
+                BUTTON_PRESS => {
+                    let InputEvent { x, y, detail, .. } = event.input;
+                    let pixel = (x.clamp(0, w as c_int - 1), y.clamp(0, h as c_int - 1));
+                    let pixel = (pixel.0 as usize, pixel.1 as usize);
+                    match detail {
+                        1 => region = region.zoomed_at(pixel, ZOOM_FACTOR),
+                        3 => region = region.zoomed_at(pixel, 1.0 / ZOOM_FACTOR),
+                        _ => {}
+                    }
+                }
+                KEY_PRESS => match XLookupKeysym(&mut event.input, 0) {
+                    XK_LEFT => region = region.panned(-cols * PAN_FRACTION, 0.0),
+                    XK_RIGHT => region = region.panned(cols * PAN_FRACTION, 0.0),
+                    XK_UP => region = region.panned(0.0, -rows * PAN_FRACTION),
+                    XK_DOWN => region = region.panned(0.0, rows * PAN_FRACTION),
+                    XK_S => {
+                        match write_image(filename, &pixels, region.bounds, params.color_type()) {
+                            Ok(()) => eprintln!("Wrote {}", filename),
+                            Err(err) => break Err(CliError::Io(err)),
+                        }
+                    }
+                    XK_Q | XK_ESCAPE => break Ok(()),
+                    _ => {}
+                },
+                CLIENT_MESSAGE if event.client.data[0] as c_ulong == delete => break Ok(()),
+                _ => {}
+            }
+        };
+        XCloseDisplay(display);
+        result
+    }
+}
+
+/// Render `region` with `render_to_vec` and copy it into `frame` as X
+/// pixels, reporting how long it took. Returns the rendered image, for
+/// writing out.
+fn show(frame: &mut [u32], region: &Region, params: &Params) -> Vec<u8> {
+    let start = Instant::now();
+    let pixels = render_to_vec(region, params);
+    for (out, pixel) in frame.iter_mut().zip(pixels.chunks(params.channels())) {
+        let [r, g, b] = match *pixel {
+            [gray] => [gray; 3],
+            [r, g, b] => [r, g, b],
+            _ => unreachable!(),
+        };
+        *out = u32::from_be_bytes([0, r, g, b]);
+    }
+    eprintln!("Rendered in {:.2?}", start.elapsed());
+    pixels
+}
+
+/// Print the corners of `region` as they're given on the command line.
+fn print_view(region: &Region) {
+    let (upper_l, lower_r) = region.corners();
+    eprintln!(
+        "View: {:?},{:?} {:?},{:?}",
+        upper_l.re, upper_l.im, lower_r.re, lower_r.im
+    );
+}
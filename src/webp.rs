@@ -0,0 +1,450 @@
+//! A small encoder for lossless WebP (VP8L) images.
+//!
+//! The image crate only decodes WebP, so this writes the format itself,
+//! with the parts of it that suit rendered fractals: the subtract green
+//! transform, which zeroes the red and blue of gray pixels, and backward
+//! references to the pixel on the left and the one above, which cover the
+//! flat bands and the interior. Everything left is a literal, coded with
+//! one Huffman code per channel.
+//!
+//! Lossy files are made the way libwebp's near lossless mode makes them:
+//! the low bits of each channel are rounded off before encoding, which
+//! lengthens runs and shortens codes.
+
+use std::io::Write;
+
+use image::error::UnsupportedErrorKind;
+use image::error::{ImageFormatHint, LimitError, LimitErrorKind, UnsupportedError};
+use image::{ColorType, ImageError, ImageFormat};
+
+/// Largest width or height of a WebP image.
+pub const MAX_DIMENSION: usize = 1 << 14;
+
+/// Longest code allowed for a pixel symbol, and for a code length symbol.
+const MAX_CODE_LENGTH: u8 = 15;
+const MAX_CODE_LENGTH_CODE_LENGTH: u8 = 7;
+
+/// The order code length code lengths are stored in.
+const CODE_LENGTH_CODE_ORDER: [usize; 19] = [
+    17, 18, 0, 1, 2, 3, 4, 5, 16, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+
+/// Sizes of the green (with backward reference lengths), red, blue, alpha
+/// and distance alphabets.
+const ALPHABETS: [usize; 5] = [256 + 24, 256, 256, 256, 40];
+
+/// Shortest and longest backward references worth taking.
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 4096;
+
+/// Distance codes for the pixel above and the pixel on the left, the first
+/// two entries of the format's table of nearby pixels.
+const ABOVE: usize = 1;
+const LEFT: usize = 2;
+
+/// Write the 8-bit gray or RGB buffer `pixels` of size `bounds` to `output`
+/// as a WebP file. `quality` from 1 to 100 rounds off up to five low bits
+/// of each channel, fewer the higher it is; `None` keeps every bit.
+pub fn encode<W: Write>(
+    mut output: W,
+    pixels: &[u8],
+    bounds: (usize, usize),
+    color: ColorType,
+    quality: Option<u8>,
+) -> Result<(), ImageError> {
+    let channels = match color {
+        ColorType::L8 => 1,
+        ColorType::Rgb8 => 3,
+        _ => {
+            return Err(ImageError::Unsupported(
+                UnsupportedError::from_format_and_kind(
+                    ImageFormatHint::Exact(ImageFormat::WebP),
+                    UnsupportedErrorKind::Color(color.into()),
+                ),
+            ));
+        }
+    };
+    let (width, height) = bounds;
+    if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(ImageError::Limits(LimitError::from_kind(
+            LimitErrorKind::DimensionError,
+        )));
+    }
+
+    let drop = quality.map_or(0, |quality| {
+        u32::from(100 - quality.clamp(1, 100) + 19) / 20
+    });
+    let argb: Vec<u32> = pixels
+        .chunks_exact(channels)
+        .map(|pixel| {
+            let channel = |i: usize| round_off(pixel[i.min(channels - 1)], drop);
+            subtract_green(0xff00_0000 | channel(0) << 16 | channel(1) << 8 | channel(2))
+        })
+        .collect();
+
+    let mut bits = BitWriter::default();
+    bits.write(width as u32 - 1, 14);
+    bits.write(height as u32 - 1, 14);
+    // No alpha, version 0, then the subtract green transform and no other.
+    bits.write(0, 1);
+    bits.write(0, 3);
+    bits.write(1, 1);
+    bits.write(2, 2);
+    bits.write(0, 1);
+    // No color cache and a single set of codes for the whole image.
+    bits.write(0, 1);
+    bits.write(0, 1);
+
+    let symbols = backward_references(&argb, width);
+    let mut counts = ALPHABETS.map(|size| vec![0u32; size]);
+    for symbol in &symbols {
+        match *symbol {
+            Symbol::Literal(argb) => {
+                for (tree, shift) in [(0, 8), (1, 16), (2, 0), (3, 24)] {
+                    counts[tree][(argb >> shift & 0xff) as usize] += 1;
+                }
+            }
+            Symbol::Copy { length, distance } => {
+                counts[0][256 + prefix(length).0 as usize] += 1;
+                counts[4][prefix(distance).0 as usize] += 1;
+            }
+        }
+    }
+    let codes = counts.map(|counts| {
+        let code = HuffmanCode::new(&counts, MAX_CODE_LENGTH);
+        code.write_lengths(&mut bits);
+        code
+    });
+
+    for symbol in &symbols {
+        match *symbol {
+            Symbol::Literal(argb) => {
+                for (tree, shift) in [(0, 8), (1, 16), (2, 0), (3, 24)] {
+                    codes[tree].write_symbol(&mut bits, (argb >> shift & 0xff) as usize);
+                }
+            }
+            Symbol::Copy { length, distance } => {
+                let (code, extra, extra_bits) = prefix(length);
+                codes[0].write_symbol(&mut bits, 256 + code as usize);
+                bits.write(extra, extra_bits);
+                let (code, extra, extra_bits) = prefix(distance);
+                codes[4].write_symbol(&mut bits, code as usize);
+                bits.write(extra, extra_bits);
+            }
+        }
+    }
+
+    let mut data = vec![0x2f];
+    data.extend(bits.finish());
+    let padding = data.len() % 2;
+    let riff_size = 4 + 8 + data.len() + padding;
+    let riff_size = u32::try_from(riff_size)
+        .map_err(|_| ImageError::Limits(LimitError::from_kind(LimitErrorKind::DimensionError)))?;
+
+    output.write_all(b"RIFF")?;
+    output.write_all(&riff_size.to_le_bytes())?;
+    output.write_all(b"WEBPVP8L")?;
+    output.write_all(&(data.len() as u32).to_le_bytes())?;
+    output.write_all(&data)?;
+    output.write_all(&[0][..padding])?;
+
+    Ok(())
+}
+
+/// Round `value` to the nearest multiple of `2^bits` that fits in a byte.
+fn round_off(value: u8, bits: u32) -> u32 {
+    match bits {
+        0 => u32::from(value),
+        _ => ((u32::from(value) + (1 << (bits - 1))) >> bits << bits).min(255),
+    }
+}
+
+/// Subtract the green channel of `argb` from its red and blue, modulo 256.
+fn subtract_green(argb: u32) -> u32 {
+    let green = argb >> 8 & 0xff;
+    let red = (argb >> 16).wrapping_sub(green) & 0xff;
+    let blue = argb.wrapping_sub(green) & 0xff;
+    argb & 0xff00_ff00 | red << 16 | blue
+}
+
+/// One coded element of the image: a pixel, or a copy of `length` pixels
+/// from `distance` back, given as a distance code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Symbol {
+    Literal(u32),
+    Copy { length: usize, distance: usize },
+}
+
+/// Code the pixels of an image `width` wide, taking the longer of the
+/// runs copied from the left and from above wherever one is long enough.
+fn backward_references(argb: &[u32], width: usize) -> Vec<Symbol> {
+    let run = |i: usize, back: usize| {
+        (i..argb.len().min(i + MAX_MATCH))
+            .take_while(|&j| argb[j] == argb[j - back])
+            .count()
+    };
+
+    let mut symbols = Vec::new();
+    let mut i = 0;
+    while i < argb.len() {
+        let left = if i >= 1 { run(i, 1) } else { 0 };
+        let above = if i >= width { run(i, width) } else { 0 };
+        let (length, distance) = match above >= left {
+            true => (above, ABOVE),
+            false => (left, LEFT),
+        };
+        if length >= MIN_MATCH {
+            symbols.push(Symbol::Copy { length, distance });
+            i += length;
+        } else {
+            symbols.push(Symbol::Literal(argb[i]));
+            i += 1;
+        }
+    }
+
+    symbols
+}
+
+/// The prefix code of the length or distance `value`, with its extra bits
+/// and their number.
+fn prefix(value: usize) -> (u32, u32, u32) {
+    let d = value as u32 - 1;
+    if d < 4 {
+        return (d, 0, 0);
+    }
+    let high = 31 - d.leading_zeros();
+    let second = d >> (high - 1) & 1;
+    let extra_bits = high - 1;
+
+    (2 * high + second, d & ((1 << extra_bits) - 1), extra_bits)
+}
+
+/// Bits packed into bytes from the least significant bit up.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    used: u32,
+}
+
+impl BitWriter {
+    /// Append the low `count` bits of `value`.
+    fn write(&mut self, value: u32, count: u32) {
+        self.buffer |= u64::from(value) << self.used;
+        self.used += count;
+        while self.used >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.used -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.used > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// A canonical Huffman code over an alphabet.
+struct HuffmanCode {
+    lengths: Vec<u8>,
+    /// Each symbol's code with its bits reversed, since codes are read a
+    /// bit at a time from the stream starting with their first bit.
+    codes: Vec<u32>,
+    /// Whether only one symbol is used. Decoders read it with no bits at
+    /// all, though it's stored with a length of one.
+    single: bool,
+}
+
+impl HuffmanCode {
+    /// The code for symbols occurring `counts` times, no longer than
+    /// `max_length`. Every code has at least one symbol.
+    fn new(counts: &[u32], max_length: u8) -> HuffmanCode {
+        let mut counts = counts.to_vec();
+        let used = counts.iter().filter(|&&count| count > 0).count();
+        if used == 0 {
+            counts[0] = 1;
+        }
+        let lengths = loop {
+            let lengths = code_lengths(&counts);
+            if lengths.iter().all(|&length| length <= max_length) {
+                break lengths;
+            }
+            // Flatten the distribution until the deepest code fits.
+            for count in counts.iter_mut().filter(|count| **count > 0) {
+                *count = count.div_ceil(2);
+            }
+        };
+
+        // The first code of each length follows the codes of all shorter
+        // ones, as in the format's decoders.
+        let mut histogram = [0u32; MAX_CODE_LENGTH as usize + 1];
+        for &length in lengths.iter().filter(|&&length| length > 0) {
+            histogram[length as usize] += 1;
+        }
+        let mut next = [0u32; MAX_CODE_LENGTH as usize + 1];
+        for length in 1..next.len() {
+            next[length] = (next[length - 1] + histogram[length - 1]) << 1;
+        }
+        let codes = lengths
+            .iter()
+            .map(|&length| {
+                let code = next[length as usize];
+                next[length as usize] += 1;
+                match length {
+                    0 => 0,
+                    _ => code.reverse_bits() >> (32 - u32::from(length)),
+                }
+            })
+            .collect();
+
+        HuffmanCode {
+            single: used <= 1,
+            lengths,
+            codes,
+        }
+    }
+
+    fn write_symbol(&self, bits: &mut BitWriter, symbol: usize) {
+        if !self.single {
+            bits.write(self.codes[symbol], u32::from(self.lengths[symbol]));
+        }
+    }
+
+    /// Store the code lengths, themselves Huffman coded, as a normal code.
+    fn write_lengths(&self, bits: &mut BitWriter) {
+        let mut counts = [0u32; 19];
+        for &length in &self.lengths {
+            counts[length as usize] += 1;
+        }
+        let code = HuffmanCode::new(&counts, MAX_CODE_LENGTH_CODE_LENGTH);
+        let stored = CODE_LENGTH_CODE_ORDER
+            .iter()
+            .rposition(|&symbol| code.lengths[symbol] > 0)
+            .map_or(4, |last| (last + 1).max(4));
+
+        bits.write(0, 1);
+        bits.write(stored as u32 - 4, 4);
+        for &symbol in &CODE_LENGTH_CODE_ORDER[..stored] {
+            bits.write(u32::from(code.lengths[symbol]), 3);
+        }
+        // Lengths follow for the whole alphabet.
+        bits.write(0, 1);
+        for &length in &self.lengths {
+            code.write_symbol(bits, length as usize);
+        }
+    }
+}
+
+/// Huffman code lengths for symbols occurring `counts` times, with no
+/// limit on their length; symbols that don't occur get none. A lone
+/// symbol gets a length of one.
+fn code_lengths(counts: &[u32]) -> Vec<u8> {
+    // Merge the two lightest trees until one is left, tracking each
+    // tree's symbols so their depths can be bumped as it sinks.
+    let mut trees: Vec<(u64, Vec<usize>)> = counts
+        .iter()
+        .enumerate()
+        .filter(|&(_, &count)| count > 0)
+        .map(|(symbol, &count)| (u64::from(count), vec![symbol]))
+        .collect();
+    let mut lengths = vec![0u8; counts.len()];
+    if trees.len() == 1 {
+        lengths[trees[0].1[0]] = 1;
+        return lengths;
+    }
+    while trees.len() > 1 {
+        trees.sort_by_key(|tree| std::cmp::Reverse(tree.0));
+        let (weight_a, symbols_a) = trees.pop().unwrap();
+        let (weight_b, symbols_b) = trees.pop().unwrap();
+        let mut symbols = symbols_a;
+        symbols.extend(symbols_b);
+        for &symbol in &symbols {
+            lengths[symbol] = lengths[symbol].saturating_add(1);
+        }
+        trees.push((weight_a + weight_b, symbols));
+    }
+
+    lengths
+}
+
+#[cfg(test)]
+mod test {
+    use super::{encode, prefix, MAX_MATCH};
+    use image::ColorType;
+
+    fn decode(pixels: &[u8], bounds: (usize, usize), quality: Option<u8>) -> Vec<u8> {
+        let color = match pixels.len() / (bounds.0 * bounds.1) {
+            1 => ColorType::L8,
+            _ => ColorType::Rgb8,
+        };
+        let mut buffer = Vec::new();
+        encode(&mut buffer, pixels, bounds, color, quality).unwrap();
+        assert_eq!(buffer.len() % 2, 0);
+
+        let decoded = image::load_from_memory(&buffer).unwrap();
+        assert_eq!(
+            (decoded.width(), decoded.height()),
+            (bounds.0 as u32, bounds.1 as u32)
+        );
+        match color {
+            ColorType::L8 => decoded.into_luma8().into_raw(),
+            _ => decoded.into_rgb8().into_raw(),
+        }
+    }
+
+    #[test]
+    fn lossless_round_trips() {
+        // Noise, runs along rows and runs down columns, in gray and color.
+        let bounds = (37, 23);
+        let gray: Vec<u8> = (0..bounds.0 * bounds.1)
+            .map(|i| match (i / bounds.0) % 3 {
+                0 => (i * 7919 % 251) as u8,
+                1 => (i / 9) as u8,
+                _ => (i % bounds.0) as u8,
+            })
+            .collect();
+        assert_eq!(decode(&gray, bounds, None), gray);
+
+        let rgb: Vec<u8> = gray
+            .iter()
+            .flat_map(|&g| [g, g.wrapping_mul(3), 255 - g])
+            .collect();
+        assert_eq!(decode(&rgb, bounds, None), rgb);
+
+        let flat = vec![9u8; 3 * 5000];
+        assert_eq!(decode(&flat, (100, 50), None), flat);
+    }
+
+    #[test]
+    fn lossy_rounds_off() {
+        let bounds = (16, 16);
+        let gray: Vec<u8> = (0..=255).collect();
+
+        assert_eq!(decode(&gray, bounds, Some(100)), gray);
+        for (quality, error) in [(90, 1), (50, 4), (1, 16)] {
+            let decoded = decode(&gray, bounds, Some(quality));
+            for (&a, &b) in gray.iter().zip(&decoded) {
+                assert!(a.abs_diff(b) <= error, "{} {} {}", quality, a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn prefix_codes() {
+        assert_eq!(prefix(1), (0, 0, 0));
+        assert_eq!(prefix(4), (3, 0, 0));
+        assert_eq!(prefix(5), (4, 0, 1));
+        assert_eq!(prefix(7), (5, 0, 1));
+        assert_eq!(prefix(9), (6, 0, 2));
+        assert_eq!(prefix(MAX_MATCH), (23, 1023, 10));
+    }
+
+    #[test]
+    fn rejects_sixteen_bits() {
+        let result = encode(Vec::new(), &[0; 8], (2, 2), ColorType::L16, None);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,609 @@
+use std::time::Duration;
+
+use mandelbrot::WriteOptions;
+use mandelbrot::{cmplx, escape_time, parse_complex, render, render_to_vec, write_image_with};
+use mandelbrot::{escape_counts, render_colorized, render_parallel_with_progress};
+use mandelbrot::{
+    read_checkpoint, render_strips, render_tiled, render_tiled_resumable,
+    render_tiled_with_progress, render_window, tiled_rows, write_checkpoint, write_image,
+};
+use mandelbrot::{render_parallel, render_parallel_streaming};
+use mandelbrot::{
+    ColorMode, Colorizer, Dither, Ditherer, Fractal, Json, Palette, Params, Power, Precision,
+    Preset, Progress, Region, Stats, TiledState, PHOENIX_P,
+};
+
+#[test]
+fn render_through_public_api() {
+    let bounds = (16, 12);
+    let region = Region::new(bounds, cmplx!(-2.0, 1.0), cmplx!(1.0, -1.0));
+    let mut pixels = vec![0u8; bounds.0 * bounds.1];
+
+    render(&mut pixels, &region, &Params::default());
+
+    // The origin lies inside the set, the far corner escapes immediately.
+    assert_eq!(escape_time(cmplx!(), cmplx!(), 255), None);
+    let corner = escape_time(cmplx!(), region.upper_left, 255).unwrap();
+    assert_eq!(pixels[0], 255 - corner as u8);
+    assert!(pixels.contains(&0));
+}
+
+#[test]
+fn render_to_vec_matches_render() {
+    let bounds = (30, 20);
+    let region = Region::new(bounds, cmplx!(-1.20, 0.35), cmplx!(-1.0, 0.20));
+    let params = Params::default();
+    let mut expected = vec![0u8; bounds.0 * bounds.1];
+
+    render(&mut expected, &region, &params);
+
+    assert_eq!(render_to_vec(&region, &params), expected);
+}
+
+#[test]
+fn png_records_parameters() {
+    let bounds = (24, 16);
+    let region = Region::new(bounds, cmplx!(-1.8, -0.08), cmplx!(-1.7, 0.01 / 3.0));
+    let params = Params {
+        limit: 500,
+        fractal: Fractal::BurningShip,
+        palette: Some(Palette::Ocean),
+        ..Params::default()
+    };
+    let pixels = render_to_vec(&region, &params);
+    let options = WriteOptions {
+        text: params.metadata(&region),
+        ..WriteOptions::default()
+    };
+    let path = std::env::temp_dir().join("mandelbrot-render-test-metadata.png");
+    let filename = path.to_str().unwrap();
+    write_image_with(filename, &pixels, bounds, params.color_type(), &options).unwrap();
+
+    let decoder = png::Decoder::new(std::fs::File::open(&path).unwrap());
+    let reader = decoder.read_info().unwrap();
+    let text = |keyword: &str| {
+        let chunks = &reader.info().uncompressed_latin1_text;
+        let chunk = chunks.iter().find(|chunk| chunk.keyword == keyword);
+        chunk.map(|chunk| chunk.text.clone()).unwrap()
+    };
+    assert_eq!(text("Bounds"), "24x16");
+    assert_eq!(parse_complex(&text("UpperLeft")), Ok(region.upper_left));
+    assert_eq!(parse_complex(&text("LowerRight")), Ok(region.lower_right));
+    assert_eq!(text("Iterations"), "500");
+    assert_eq!(Fractal::from_name(&text("Fractal")), Some(params.fractal));
+    assert_eq!(Palette::from_name(&text("Palette")), params.palette);
+    drop(reader);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn metadata_json_describes_render() {
+    let bounds = (24, 16);
+    let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(1.0, -0.8));
+    let params = Params {
+        limit: 300,
+        palette: Some(Palette::Fire),
+        ..Params::default()
+    };
+    let mut pixels = vec![0u8; bounds.0 * bounds.1 * params.channels()];
+    let stats = render(&mut pixels, &region, &params);
+
+    let text = params
+        .metadata_json(&region, &stats, Duration::from_millis(1500))
+        .to_string();
+    let json = Json::parse(&text).unwrap();
+    let number = |value: Option<&Json>| value.and_then(Json::as_f64).unwrap();
+    let point = |key| {
+        let point = json.get(key).unwrap();
+        cmplx!(number(point.get("re")), number(point.get("im")))
+    };
+    assert_eq!(number(json.get("width")), 24.0);
+    assert_eq!(number(json.get("height")), 16.0);
+    assert_eq!(point("upper_left"), region.upper_left);
+    assert_eq!(point("lower_right"), region.lower_right);
+    assert_eq!(number(json.get("iterations")), 300.0);
+    assert_eq!(
+        json.get("fractal").and_then(Json::as_str),
+        Some("mandelbrot")
+    );
+    assert_eq!(json.get("palette").and_then(Json::as_str), Some("fire"));
+    assert_eq!(number(json.get("elapsed_seconds")), 1.5);
+
+    let counts = json.get("stats").unwrap();
+    assert_eq!(number(counts.get("pixels")), stats.pixels as f64);
+    assert_eq!(number(counts.get("interior")), stats.interior as f64);
+    assert_eq!(number(counts.get("escaped")), stats.escaped() as f64);
+    assert_eq!(number(counts.get("max")), stats.max);
+    assert_eq!(counts.get("mean").and_then(Json::as_f64), stats.mean());
+}
+
+#[test]
+fn cancelled_render_writes_partial_image() {
+    let bounds = (32, 24);
+    let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(1.0, -1.2));
+    let params = Params::default();
+
+    // Cancelled before it starts, nothing is rendered.
+    let progress = Progress::new(bounds.1);
+    progress.cancel();
+    let mut pixels = vec![0u8; bounds.0 * bounds.1];
+    render_parallel_with_progress(&mut pixels, &region, &params, 4, &progress);
+    assert!(pixels.iter().all(|&p| p == 0));
+
+    // Cancelled after the first strip of eight rows, the rest stays black.
+    let progress = Progress::new(bounds.1);
+    let mut partial = Vec::new();
+    let write = |strip: &[u8]| -> Result<(), ()> {
+        partial.extend_from_slice(strip);
+        progress.cancel();
+        Ok(())
+    };
+    render_strips(&region, &params, 8, 2, Some(&progress), write).unwrap();
+    let mut whole = vec![0u8; bounds.0 * bounds.1];
+    render(&mut whole, &region, &params);
+    let (done, rest) = partial.split_at(8 * bounds.0);
+    assert_eq!(done, &whole[..8 * bounds.0]);
+    assert!(rest.iter().all(|&p| p == 0));
+
+    let path = std::env::temp_dir().join("mandelbrot-render-test-cancelled.png");
+    write_image(
+        path.to_str().unwrap(),
+        &partial,
+        bounds,
+        params.color_type(),
+    )
+    .unwrap();
+    let decoded = image::open(&path).unwrap().into_luma8();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(decoded.dimensions(), (32, 24));
+    assert_eq!(decoded.into_raw(), partial);
+}
+
+#[test]
+fn band_times_cover_every_row() {
+    let bounds = (32, 24);
+    let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(1.0, -1.2));
+    for color_mode in [ColorMode::Linear, ColorMode::Histogram] {
+        let params = Params {
+            color_mode,
+            ..Params::default()
+        };
+        let progress = Progress::with_band_times(bounds.1 * params.passes());
+        let mut pixels = vec![0u8; bounds.0 * bounds.1];
+        render_parallel_with_progress(&mut pixels, &region, &params, 3, &progress);
+
+        let times = progress.band_times();
+        let rows: usize = times.iter().map(|band| band.rows).sum();
+        assert_eq!(rows, bounds.1 * params.passes());
+        assert!(times.iter().all(|band| band.thread < 3 && band.rows > 0));
+    }
+}
+
+#[test]
+fn tiles_match_serial_render() {
+    let bounds = (50, 37);
+    let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(1.0, -1.2));
+    let plain = Params::default();
+    let all = [
+        plain,
+        Params {
+            color_mode: ColorMode::Histogram,
+            ..plain
+        },
+        Params {
+            supersample: 3,
+            adaptive: true,
+            palette: Some(Palette::Fire),
+            ..plain
+        },
+        Params {
+            simd: true,
+            ..plain
+        },
+    ];
+    for params in all {
+        let len = bounds.0 * bounds.1 * params.channels();
+        let mut expected = vec![0u8; len];
+        render(&mut expected, &region, &params);
+        // Sizes that divide neither side, a single tile and single pixels.
+        for tile_size in [1, 7, 16, 50, 64] {
+            for threads in [1, 3] {
+                let mut pixels = vec![0u8; len];
+                render_tiled(&mut pixels, &region, &params, threads, tile_size);
+                assert!(pixels == expected, "{:?} {} {}", params, tile_size, threads);
+            }
+        }
+
+        let total = tiled_rows(bounds, 16) * params.passes();
+        let progress = Progress::new(total);
+        let mut pixels = vec![0u8; len];
+        render_tiled_with_progress(&mut pixels, &region, &params, 2, 16, &progress);
+        assert_eq!(progress.rows(), total);
+        assert!(progress.is_done());
+    }
+}
+
+#[test]
+fn resumed_checkpoint_matches_uninterrupted_render() {
+    let bounds = (50, 37);
+    let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(1.0, -1.2));
+    let path = std::env::temp_dir().join("mandelbrot-resume-test.ck");
+    let file = path.to_str().unwrap();
+    let plain = Params::default();
+    let histogram = Params {
+        color_mode: ColorMode::Histogram,
+        ..plain
+    };
+    for params in [plain, histogram] {
+        let len = bounds.0 * bounds.1 * params.channels();
+        let mut expected = vec![0u16; len];
+        let expected_stats = render(&mut expected, &region, &params);
+        let total = tiled_rows(bounds, 8) * params.passes();
+
+        // Stop after the first batch of tiles, saving what's done.
+        let progress = Progress::new(total);
+        let mut pixels = vec![0u16; len];
+        let mut state = TiledState::new(bounds, 8);
+        let save = |pixels: &[u16], state: &TiledState| {
+            progress.cancel();
+            write_checkpoint(file, &region, &params, pixels, state)
+        };
+        render_tiled_resumable(
+            &mut pixels,
+            &region,
+            &params,
+            2,
+            &mut state,
+            Some(&progress),
+            save,
+        )
+        .unwrap();
+        assert!(state.done.contains(&true) && !state.is_finished());
+        assert_ne!(pixels, expected);
+
+        let (mut pixels, mut state) = read_checkpoint::<u16>(file, &region, &params).unwrap();
+        let progress = Progress::new(total);
+        let save = |_: &[u16], _: &TiledState| Ok::<(), std::io::Error>(());
+        let stats = render_tiled_resumable(
+            &mut pixels,
+            &region,
+            &params,
+            2,
+            &mut state,
+            Some(&progress),
+            save,
+        )
+        .unwrap();
+        assert!(pixels == expected, "{:?}", params);
+        assert_eq!(stats, expected_stats);
+        assert!(state.is_finished());
+        assert_eq!(progress.rows(), total);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn window_matches_whole_render() {
+    let bounds = (45, 32);
+    let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(1.0, -1.2));
+    let (cols, rows) = (7..30, 11..32);
+    for params in [
+        Params::default(),
+        Params {
+            color_mode: ColorMode::Histogram,
+            palette: Some(Palette::Ocean),
+            supersample: 2,
+            ..Params::default()
+        },
+    ] {
+        let channels = params.channels();
+        let whole = render_to_vec(&region, &params);
+        let expected: Vec<u8> = whole
+            .chunks(bounds.0 * channels)
+            .skip(rows.start)
+            .take(rows.len())
+            .flat_map(|row| &row[cols.start * channels..cols.end * channels])
+            .copied()
+            .collect();
+
+        let total = bounds.1 * (params.passes() - 1) + rows.len();
+        let progress = Progress::new(total);
+        let mut window = vec![0u8; cols.len() * rows.len() * channels];
+        let (c, r) = (cols.clone(), rows.clone());
+        render_window(&mut window, &region, c, r, &params, 3, Some(&progress));
+        assert!(window == expected, "{:?}", params);
+        assert_eq!(progress.rows(), total);
+    }
+}
+
+#[test]
+fn streamed_rows_rebuild_the_image() {
+    let bounds = (37, 24);
+    let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(1.0, -1.2));
+    for params in [
+        Params::default(),
+        Params {
+            color_mode: ColorMode::Histogram,
+            palette: Some(Palette::Fire),
+            adaptive: true,
+            supersample: 3,
+            ..Params::default()
+        },
+        Params {
+            fractal: Fractal::Buddhabrot,
+            samples: 2,
+            ..Params::default()
+        },
+    ] {
+        let row_len = bounds.0 * params.channels();
+        let mut expected = vec![0u8; row_len * bounds.1];
+        let expected_stats = render_parallel(&mut expected, &region, &params, 2);
+        for threads in [1, 4] {
+            let mut rows = vec![None; bounds.1];
+            let mut pixels = vec![0u8; expected.len()];
+            let stats =
+                render_parallel_streaming(&mut pixels, &region, &params, threads, |i, row| {
+                    assert!(rows[i].is_none(), "row {} came twice", i);
+                    rows[i] = Some(row.to_vec());
+                });
+            let rebuilt: Vec<u8> = rows.into_iter().flat_map(Option::unwrap).collect();
+            assert!(rebuilt == expected, "{:?} on {} threads", params, threads);
+            assert_eq!(pixels, expected);
+            // The sum is added up in whatever order the rows finish in.
+            let counts = |s: Stats| (s.pixels, s.interior, s.min, s.max);
+            assert_eq!(counts(stats), counts(expected_stats));
+            assert!((stats.sum - expected_stats.sum).abs() < 1e-6 * expected_stats.sum);
+        }
+    }
+}
+
+#[test]
+fn alpha_interior_is_transparent() {
+    let bounds = (40, 30);
+    let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(1.0, -1.2));
+    let gray = Params {
+        alpha_interior: true,
+        ..Params::default()
+    };
+    let fire = Params {
+        palette: Some(Palette::Fire),
+        ..gray
+    };
+    let counts = escape_counts(&region, &gray, 1);
+    assert!(counts.contains(&gray.limit) && counts.iter().any(|&count| count < gray.limit));
+    for params in [gray, fire] {
+        assert_eq!(params.channels(), 4);
+        let pixels = render_to_vec(&region, &params);
+        let path = std::env::temp_dir().join("mandelbrot-render-test-alpha.png");
+        write_image(path.to_str().unwrap(), &pixels, bounds, params.color_type()).unwrap();
+        let image = image::open(&path).unwrap().into_rgba8();
+        std::fs::remove_file(&path).unwrap();
+
+        for (pixel, &count) in image.pixels().zip(&counts) {
+            let alpha = if count == params.limit { 0 } else { 255 };
+            assert_eq!(pixel.0[3], alpha, "{:?}", params.palette);
+        }
+        // The colors are those of the opaque render.
+        let opaque = Params {
+            alpha_interior: false,
+            ..params
+        };
+        let colors = image.pixels().flat_map(|pixel| pixel.0[..3].to_vec());
+        let colors: Vec<u8> = colors.collect();
+        let expected: Vec<u8> = match opaque.palette {
+            Some(_) => render_to_vec(&region, &opaque),
+            None => render_to_vec(&region, &opaque)
+                .iter()
+                .flat_map(|&gray| [gray; 3])
+                .collect(),
+        };
+        assert!(colors == expected, "{:?}", params.palette);
+    }
+}
+
+#[test]
+fn flip_y_mirrors_rows() {
+    let bounds = (20, 15);
+    let region = Region::new(bounds, cmplx!(-1.9, 0.1), cmplx!(-1.6, -0.125));
+    let flipped = Region {
+        flip_y: true,
+        ..region
+    };
+    for params in [
+        Params::default(),
+        Params {
+            fractal: Fractal::BurningShip,
+            palette: Some(Palette::Fire),
+            supersample: 2,
+            ..Params::default()
+        },
+    ] {
+        let row = bounds.0 * params.channels();
+        let pixels = render_to_vec(&region, &params);
+        let mirrored: Vec<u8> = pixels.chunks(row).rev().flatten().copied().collect();
+
+        assert_ne!(pixels, mirrored);
+        assert_eq!(render_to_vec(&flipped, &params), mirrored);
+    }
+}
+
+/// The 64-bit FNV-1a hash of `bytes`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Renders of the whole set, hashed, to catch changes to the pixels that
+/// come out of escape times, pixel positions or coloring. When a change to
+/// the output is intended, check the new images by eye and replace each
+/// hash with the one its failure reports.
+#[test]
+fn golden_images() {
+    let (u_l, l_r) = Preset::Full.corners();
+    let region = Region::new((64, 48), u_l, l_r);
+    let cases = [
+        ("gray", Params::default(), 0x2fd300086f408fd9),
+        (
+            "fire, smooth",
+            Params {
+                palette: Some(Palette::Fire),
+                smooth: true,
+                ..Params::default()
+            },
+            0xc4e447ff97016d5f,
+        ),
+        (
+            "ocean, histogram, supersampled",
+            Params {
+                palette: Some(Palette::Ocean),
+                color_mode: ColorMode::Histogram,
+                supersample: 2,
+                ..Params::default()
+            },
+            0xcb0ea5f486d2ae7b,
+        ),
+    ];
+    for (name, params, expected) in cases {
+        assert_eq!(params.limit, 255);
+        let hash = fnv1a(&render_to_vec(&region, &params));
+        assert_eq!(hash, expected, "{}: got {:#018x}", name, hash);
+    }
+}
+
+#[test]
+fn reproducible_render_matches_reference() {
+    let region = Region::new((64, 48), cmplx!(-0.8, 0.25), cmplx!(-0.6, 0.1));
+    let params = Params {
+        palette: Some(Palette::Ocean),
+        color_mode: ColorMode::Histogram,
+        fractal: Fractal::Phoenix(PHOENIX_P),
+        power: Power::Int(3),
+        adaptive: true,
+        supersample: 3,
+        linear_averaging: Some(false),
+        ..Params::default()
+    };
+    assert!(params.is_reproducible());
+    let hash = fnv1a(&render_to_vec(&region, &params));
+    assert_eq!(hash, 0x8738b7d342fbffc5, "got {:#018x}", hash);
+
+    for other in [
+        Params {
+            smooth: true,
+            ..params
+        },
+        Params {
+            gamma: 2.2,
+            ..params
+        },
+        Params {
+            linear_averaging: None,
+            ..params
+        },
+        Params {
+            precision: Precision::F32,
+            ..params
+        },
+        Params {
+            simd: true,
+            ..Params::default()
+        },
+    ] {
+        assert!(!other.is_reproducible(), "{:?}", other);
+    }
+}
+
+#[test]
+fn dithering_breaks_up_bands() {
+    // Far from the set, where smooth escape values change by less than a
+    // gray level over dozens of pixels.
+    let bounds = (96, 64);
+    let region = Region::new(bounds, cmplx!(-2.2, 1.2), cmplx!(-1.6, 0.8));
+    let params = Params {
+        smooth: true,
+        ..Params::default()
+    };
+    let mut wide = vec![0u16; bounds.0 * bounds.1];
+    render(&mut wide, &region, &params);
+
+    let changes = |dither| {
+        let gray = Ditherer::new(dither, bounds.0, 1).quantize(&wide);
+        let rows = gray.chunks(bounds.0);
+        rows.map(|row| row.windows(2).filter(|pair| pair[0] != pair[1]).count())
+            .sum::<usize>()
+    };
+    let banded = changes(Dither::None);
+    for dither in [Dither::Ordered, Dither::FloydSteinberg] {
+        assert!(
+            changes(dither) > 4 * banded,
+            "{:?}: {} vs {}",
+            dither,
+            changes(dither),
+            banded
+        );
+    }
+}
+
+/// Colors escaping points red or blue by whether their count is even.
+struct Parity;
+
+impl Colorizer for Parity {
+    fn color(&self, escape: Option<f64>, _limit: u32) -> [u8; 3] {
+        match escape {
+            Some(count) if (count as u32).is_multiple_of(2) => [255, 0, 0],
+            Some(_) => [0, 0, 255],
+            None => [0, 255, 0],
+        }
+    }
+}
+
+#[test]
+fn custom_colorizer() {
+    let bounds = (40, 30);
+    let region = Region::new(bounds, cmplx!(-2.0, 1.2), cmplx!(0.6, -1.2));
+    let params = Params::default();
+    let mut pixels = vec![0u8; bounds.0 * bounds.1 * 3];
+    render_colorized(&mut pixels, &region, &params, &Parity, 3);
+
+    let counts = escape_counts(&region, &params, 1);
+    for (pixel, &count) in pixels.chunks(3).zip(&counts) {
+        let expected = match count {
+            255 => Parity.color(None, 255),
+            _ => Parity.color(Some(f64::from(count)), 255),
+        };
+        assert_eq!(pixel, expected);
+    }
+
+    // The built-in palettes color as rendering with them does.
+    for (color_mode, supersample) in [(ColorMode::Linear, 1), (ColorMode::Histogram, 2)] {
+        let params = Params {
+            color_mode,
+            supersample,
+            palette: Some(Palette::Ocean),
+            ..Params::default()
+        };
+        render_colorized(&mut pixels, &region, &params, &Palette::Ocean, 2);
+        assert_eq!(pixels, render_to_vec(&region, &params), "{:?}", color_mode);
+    }
+}
+
+#[test]
+fn buddhabrot_smoke() {
+    let bounds = (48, 36);
+    let region = Region::new(bounds, cmplx!(-2.0, 1.5), cmplx!(1.0, -1.5));
+    let params = Params {
+        fractal: Fractal::Buddhabrot,
+        samples: 5,
+        palette: Some(Palette::Fire),
+        ..Params::default()
+    };
+    let pixels = render_to_vec(&region, &params);
+    let path = std::env::temp_dir().join("mandelbrot-render-test-buddhabrot.png");
+    write_image(path.to_str().unwrap(), &pixels, bounds, params.color_type()).unwrap();
+
+    let image = image::open(&path).unwrap().to_rgb8();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(image.dimensions(), (48, 36));
+    assert!(image.pixels().any(|pixel| pixel.0 != [0; 3]));
+}